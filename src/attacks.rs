@@ -0,0 +1,208 @@
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::board::ChessBoard;
+use crate::variant::Variant;
+use crate::math::{search_ray, V2, KING_OFFSETS};
+
+// square control queries. unlike the move generators in `moves` these count
+// tiles held by the piece's own color too, since a piece standing on a friend
+// is defending it
+
+fn slides_along(piece: Option<ChessPiece>, direction: (i8, i8)) -> bool {
+    piece.is_some_and(|piece| piece.movement().rides.contains(&direction))
+}
+
+#[allow(dead_code)]
+impl ChessBoard {
+    // every tile the piece on src attacks or defends
+    pub fn attacks_from(&self, src: &V2) -> Vec<V2> {
+        let tile = self.tile(src);
+
+        let color = match tile.color() {
+            Some(color) => color,
+            None => return vec![]
+        };
+
+        match tile.piece() {
+            Some(ChessPiece::Pawn) => {
+                let forward = self.forward(color);
+                self.offsets(src, &[(-1, forward), (1, forward)])
+            },
+            Some(piece) => {
+                let movement = piece.movement();
+                let mut attacks = self.offsets(src, movement.leaps);
+                for direction in movement.rides {
+                    attacks.extend(search_ray(src, *direction, self));
+                }
+                attacks
+            },
+            None => vec![]
+        }
+    }
+
+    fn offsets(&self, src: &V2, offsets: &[(i8, i8)]) -> Vec<V2> {
+        offsets.iter()
+            .filter_map(|(x, y)| self.offset(src, *x, *y))
+            .collect()
+    }
+
+    // pieces of the given color that attack (or defend) the square. this looks
+    // outward from the square instead of generating every move on the board
+    pub fn attackers_of(&self, square: &V2, color: ChessPieceColor) -> Vec<V2> {
+        let mut attackers = vec![];
+
+        let own = |v2: &V2| match self.tile(v2) {
+            tile if tile.color() == Some(color) => tile.piece(),
+            _ => None
+        };
+
+        // a pawn attacks diagonally forward, so look one step backward
+        let backward = -self.forward(color);
+        for pawn in self.offsets(square, &[(-1, backward), (1, backward)]) {
+            if own(&pawn) == Some(ChessPiece::Pawn) {
+                attackers.push(pawn);
+            }
+        }
+
+        // a leaper that could jump here is found by jumping back the other
+        // way. the king is checked last, after the riders
+        for piece in ChessPiece::ALL.into_iter().filter(|piece| *piece != ChessPiece::King) {
+            for (x, y) in piece.movement().leaps {
+                if let Some(v2) = self.offset(square, -x, -y) {
+                    if own(&v2) == Some(piece) {
+                        attackers.push(v2);
+                    }
+                }
+            }
+        }
+
+        for direction in KING_OFFSETS {
+            if let Some(blocker) = search_ray(square, direction, self).last() {
+                let tile = self.tile(blocker);
+                if tile.color() == Some(color) && slides_along(tile.piece(), (-direction.0, -direction.1)) {
+                    attackers.push(*blocker);
+                }
+            }
+        }
+
+        for king in self.offsets(square, &KING_OFFSETS) {
+            if own(&king) == Some(ChessPiece::King) {
+                attackers.push(king);
+            }
+        }
+
+        attackers
+    }
+
+    pub fn is_attacked(&self, square: &V2, color: ChessPieceColor) -> bool {
+        !self.attackers_of(square, color).is_empty()
+    }
+
+    pub fn king_square(&self, color: ChessPieceColor) -> Option<V2> {
+        self.squares()
+            .find(|v2| {
+                let tile = self.tile(v2);
+                tile.piece() == Some(ChessPiece::King) && tile.color() == Some(color)
+            })
+    }
+
+    pub fn in_check(&self, color: ChessPieceColor) -> bool {
+        match self.king_square(color) {
+            Some(king) if self.variant() == Variant::Atomic => self.atomic_check(&king, color),
+            Some(king) => self.is_attacked(&king, color.opposite()),
+            None => false
+        }
+    }
+
+    // pieces of the occupant's own color guarding the square
+    pub fn defenders_of(&self, square: &V2) -> Vec<V2> {
+        match self.tile(square).color() {
+            Some(color) => self.attackers_of(square, color),
+            None => vec![]
+        }
+    }
+
+    // sliders of the given color lined up behind the first piece on a line to
+    // the square. they would attack it as soon as that piece moves away
+    pub fn xray_attackers_of(&self, square: &V2, color: ChessPieceColor) -> Vec<V2> {
+        let mut attackers = vec![];
+
+        for direction in KING_OFFSETS {
+            let blocker = match search_ray(square, direction, self).last() {
+                Some(blocker) if self.tile(blocker).piece().is_some() => *blocker,
+                _ => continue
+            };
+
+            if let Some(behind) = search_ray(&blocker, direction, self).last() {
+                let tile = self.tile(behind);
+                if tile.color() == Some(color) && slides_along(tile.piece(), (-direction.0, -direction.1)) {
+                    attackers.push(*behind);
+                }
+            }
+        }
+
+        attackers
+    }
+
+    // number of pieces of the given color attacking each tile, indexed like the
+    // board (x + files * y)
+    pub fn attack_map(&self, color: ChessPieceColor) -> Vec<u8> {
+        let mut map = vec![0; (self.files() * self.ranks()) as usize];
+
+        for src in self.squares() {
+            if self.tile(&src).color() == Some(color) {
+                for dst in self.attacks_from(&src) {
+                    map[(dst.x + self.files() * dst.y) as usize] += 1;
+                }
+            }
+        }
+
+        map
+    }
+
+    // number of defenders on each of the color's own pieces. empty tiles and
+    // enemy pieces are always zero
+    pub fn defence_map(&self, color: ChessPieceColor) -> Vec<u8> {
+        let mut map = self.attack_map(color);
+
+        for (count, src) in map.iter_mut().zip(self.squares()) {
+            if self.tile(&src).color() != Some(color) {
+                *count = 0;
+            }
+        }
+
+        map
+    }
+
+    // pieces of the given color that can be won: attacked and undefended, or
+    // attacked by something clearly cheaper. knights and bishops trade evenly
+    pub fn hanging_pieces(&self, color: ChessPieceColor) -> Vec<V2> {
+        let mut hanging = vec![];
+
+        for src in self.squares() {
+            let tile = self.tile(&src);
+
+            let piece = match tile.piece() {
+                Some(ChessPiece::King) | None => continue,
+                Some(piece) if tile.color() == Some(color) => piece,
+                _ => continue
+            };
+
+            let attackers = self.attackers_of(&src, color.opposite());
+            if attackers.is_empty() {
+                continue;
+            }
+
+            let cheapest = attackers.iter()
+                .filter_map(|attacker| self.tile(attacker).piece())
+                .map(|attacker| attacker.value())
+                .min()
+                .unwrap_or(0);
+
+            if self.defenders_of(&src).is_empty() || cheapest + 50 < piece.value() {
+                hanging.push(src);
+            }
+        }
+
+        hanging
+    }
+}
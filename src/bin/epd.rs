@@ -0,0 +1,64 @@
+use std::env;
+use std::process;
+use std::time::Duration;
+
+use chess_engine::epd::{load_suite, run_suite};
+use chess_engine::selfplay::MoveLimit;
+use chess_engine::search::SearchConfig;
+
+// epd <test suite> <milliseconds | depth N> [threads]
+//
+// searches every position in the suite and prints whether the engine found
+// the best move (or avoided the bad one), with the solved count at the end
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("usage: {} <test suite> <milliseconds | depth N> [threads]", args[0]);
+        process::exit(1);
+    }
+
+    let number = |index: usize| args.get(index).and_then(|text| text.parse::<u64>().ok());
+
+    let (limit, rest) = match args[2].as_str() {
+        "depth" => (number(3).map(|depth| MoveLimit::Depth(depth as u8)), 4),
+        _ => (number(2).map(|millis| MoveLimit::MoveTime(Duration::from_millis(millis))), 3)
+    };
+
+    let limit = limit.unwrap_or_else(|| {
+        eprintln!("the limit is a number of milliseconds, or depth and a number");
+        process::exit(1);
+    });
+
+    let config = SearchConfig {
+        threads: number(rest).map_or(1, |threads| threads.max(1) as usize),
+        ..SearchConfig::default()
+    };
+
+    let records = load_suite(&args[1]).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+
+    let report = run_suite(&records, config, limit, |record, result| {
+        let chosen = result.chosen.map_or("none".to_string(), |chess_move| record.board.move_to_san(&chess_move));
+        let expected = record.operation("bm").map(|moves| format!("bm {}", moves.join(" ")))
+            .or_else(|| record.operation("am").map(|moves| format!("am {}", moves.join(" "))))
+            .unwrap_or_default();
+
+        println!(
+            "{} {}: {} ({}), depth {} score {}",
+            match result.solved {
+                true => "solved",
+                false => "failed"
+            },
+            result.id,
+            chosen,
+            expected,
+            result.depth,
+            result.score
+        );
+    });
+
+    println!("solved {} of {}, {} skipped", report.solved(), report.total(), report.skipped);
+}
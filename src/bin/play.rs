@@ -0,0 +1,307 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use chess_engine::board::ChessBoard;
+use chess_engine::pieces::ChessPieceColor;
+use chess_engine::search::{Searcher, SearchConfig};
+use chess_engine::pgn::{PgnGame, GameResult};
+use chess_engine::render::{RenderOptions, Glyphs};
+
+// play [white | black | both | none] [depth]
+//
+// plays a game in the terminal. the argument says which sides are played
+// by people, the engine moves for the rest. moves go in as san (Nf3, exd5,
+// O-O) or as coordinates (g1f3, e7e8q)
+
+const DEFAULT_DEPTH: u8 = 6;
+
+const HELP: &str = "\
+moves as san (Nf3, O-O, e8=Q) or coordinates (g1f3, e7e8q)
+  moves           the moves so far
+  undo            take back your last move
+  go              let the engine move for the side to move
+  draw            offer a draw
+  accept          accept a draw offer
+  resign          resign the game for the side to move
+  flip            turn the board around
+  style <name>    draw the board in ascii, unicode or color
+  fen             print the position as fen
+  save <file>     save the game as pgn, or the position if the file ends in .fen
+  load <file>     load a pgn game or a fen position
+  new             start again
+  quit            leave";
+
+struct Session {
+    game: PgnGame,
+    humans: [bool; 2], // white, black
+    searcher: Searcher,
+    depth: u8,
+    draw_offer: Option<ChessPieceColor>, // the side that offered
+    options: RenderOptions
+}
+
+impl Session {
+    fn board(&self) -> ChessBoard {
+        self.game.board()
+    }
+
+    fn orientation(&self) -> ChessPieceColor {
+        self.game.start().player_color()
+    }
+
+    fn is_over(&self) -> bool {
+        self.game.result() != GameResult::Unfinished
+    }
+
+    fn human_to_move(&self) -> bool {
+        self.humans[self.board().side_to_move().index()]
+    }
+
+    fn new_game(&mut self, game: PgnGame) {
+        let mut game = game.oriented(self.orientation());
+        for (tag, human) in ["White", "Black"].iter().zip(self.humans) {
+            if game.tag(tag) == Some("?") {
+                game.set_tag(tag, match human {
+                    true => "human",
+                    false => "chess-engine"
+                });
+            }
+        }
+
+        self.game = game;
+        self.searcher.clear();
+        self.draw_offer = None;
+        self.check_outcome();
+    }
+
+    fn show(&self) {
+        println!();
+        print!("{}", self.board().render(&self.options));
+        if let Some(last) = self.game.moves().last() {
+            let mut before = *self.game.start();
+            for chess_move in &self.game.moves()[..self.game.moves().len() - 1] {
+                before.make_move(chess_move);
+            }
+            println!("last move: {}", before.move_to_san(last));
+        }
+    }
+
+    fn finish(&mut self, result: GameResult, reason: &str) {
+        self.game.set_result(result);
+        self.game.set_tag("Termination", reason);
+        println!("{} ({})", result.as_str(), reason);
+    }
+
+    fn check_outcome(&mut self) {
+        if let Some((result, reason)) = self.game.outcome() {
+            self.finish(result, reason);
+        }
+    }
+
+    fn play(&mut self, text: &str) -> bool {
+        let board = self.board();
+        let Some(chess_move) = board.parse_san(text).or_else(|| board.parse_uci_move(text)) else {
+            return false;
+        };
+
+        // an offer stands through the offerer's own move, and lapses once
+        // the other side moves instead of accepting
+        if self.draw_offer.is_some_and(|side| side != board.side_to_move()) {
+            self.draw_offer = None;
+        }
+
+        self.game.push(chess_move);
+        self.show();
+        self.check_outcome();
+        true
+    }
+
+    fn engine_move(&mut self) {
+        let board = self.board();
+        let result = self.searcher.search(&board, self.depth);
+
+        if let Some(best_move) = result.best_move {
+            println!("engine plays {} (score {})", board.move_to_san(&best_move), result.score);
+            self.play(&board.move_to_uci(&best_move));
+        }
+    }
+
+    // the engine accepts when it isn't ahead
+    fn offer_draw(&mut self) {
+        let board = self.board();
+        let opponent = board.side_to_move().opposite().index();
+
+        if self.humans[opponent] {
+            println!("draw offered, the other side can accept on their turn");
+            self.draw_offer = Some(board.side_to_move());
+            return;
+        }
+
+        // the engine answers as if it were its move
+        let mut engine_side = board;
+        engine_side.make_null_move();
+        let score = self.searcher.search(&engine_side, self.depth).score;
+
+        match score <= 0 {
+            true => self.finish(GameResult::Draw, "agreement"),
+            false => println!("the engine declines")
+        }
+    }
+
+    // takes back moves until it's a person's turn again
+    fn undo(&mut self) {
+        if self.game.pop().is_none() {
+            println!("nothing to undo");
+            return;
+        }
+
+        while !self.human_to_move() && self.game.pop().is_some() {}
+
+        self.game.set_result(GameResult::Unfinished);
+        self.draw_offer = None;
+        self.show();
+    }
+
+    fn save(&self, path: &str) {
+        let text = match path.ends_with(".fen") {
+            true => self.board().to_fen(),
+            false => self.game.to_pgn()
+        };
+
+        match fs::write(path, text + "\n") {
+            Ok(()) => println!("saved {}", path),
+            Err(_) => println!("could not write {}", path)
+        }
+    }
+
+    fn load(&mut self, path: &str) {
+        let Ok(text) = fs::read_to_string(path) else {
+            println!("could not read {}", path);
+            return;
+        };
+
+        let game = match ChessBoard::from_fen(self.orientation(), text.trim()) {
+            Ok(board) => Ok(PgnGame::new(board)),
+            Err(_) => PgnGame::parse(self.orientation(), &text)
+        };
+
+        match game {
+            Ok(game) => {
+                self.new_game(game);
+                self.show();
+            },
+            Err(error) => println!("{}", error)
+        }
+    }
+
+    // false to quit
+    fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice() {
+            [] => (),
+            ["quit"] | ["exit"] => return false,
+            ["help"] => println!("{}", HELP),
+            ["moves"] => println!("{}", self.game.movetext()),
+            ["fen"] => println!("{}", self.board().to_fen()),
+            ["flip"] => {
+                self.options.flipped = !self.options.flipped;
+                self.show();
+            },
+            ["style", style] => {
+                let (glyphs, colors) = match *style {
+                    "ascii" => (Glyphs::Ascii, false),
+                    "unicode" => (Glyphs::Unicode, false),
+                    _ => (Glyphs::Unicode, true)
+                };
+                self.options = RenderOptions { glyphs, colors, ..self.options };
+                self.show();
+            },
+            ["new"] => {
+                self.new_game(PgnGame::new(ChessBoard::new(self.orientation())));
+                self.show();
+            },
+            ["undo"] => self.undo(),
+            ["save", path] => self.save(path),
+            ["load", path] => self.load(path),
+            _ if self.is_over() => println!("the game is over, try undo, new or load"),
+            ["go"] => self.engine_move(),
+            ["resign"] => {
+                let winner = self.board().side_to_move().opposite();
+                self.finish(GameResult::win_for(winner), "resignation");
+            },
+            ["draw"] => self.offer_draw(),
+            ["accept"] if self.draw_offer.is_some_and(|side| side != self.board().side_to_move()) => {
+                self.finish(GameResult::Draw, "agreement");
+            },
+            ["accept"] if self.draw_offer.is_some() => println!("the offer is the other side's to accept"),
+            ["accept"] => println!("there is no draw offer"),
+            [text] if self.human_to_move() => if !self.play(text) {
+                println!("no legal move {}, try help", text);
+            },
+            _ => println!("unknown command, try help")
+        }
+
+        while !self.is_over() && !self.human_to_move() {
+            self.engine_move();
+        }
+
+        true
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let humans = match args.get(1).map(String::as_str) {
+        None | Some("white") => [true, false],
+        Some("black") => [false, true],
+        Some("both") => [true, true],
+        Some("none") => [false, false],
+        Some(_) => {
+            eprintln!("usage: {} [white | black | both | none] [depth]", args[0]);
+            process::exit(1);
+        }
+    };
+
+    let depth = args.get(2).and_then(|depth| depth.parse().ok()).unwrap_or(DEFAULT_DEPTH);
+
+    // the first person to move sits at the bottom
+    let orientation = match humans {
+        [false, true] => ChessPieceColor::Black,
+        _ => ChessPieceColor::White
+    };
+
+    let mut session = Session {
+        game: PgnGame::new(ChessBoard::new(orientation)),
+        humans,
+        searcher: Searcher::new(SearchConfig::default()),
+        depth,
+        draw_offer: None,
+        options: RenderOptions { glyphs: Glyphs::Unicode, ..RenderOptions::default() }
+    };
+
+    session.new_game(PgnGame::new(ChessBoard::new(orientation)));
+    println!("type help for the commands");
+    session.show();
+
+    while !session.is_over() && !session.human_to_move() {
+        session.engine_move();
+    }
+
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => if !session.handle(line.trim()) {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::process;
+use std::time::Duration;
+
+use chess_engine::selfplay::{load_openings, Engine, MatchRunner, MoveLimit, Sprt};
+use chess_engine::search::SearchConfig;
+use chess_engine::time::TimeControl;
+use chess_engine::eval::EvalParams;
+
+// selfplay <first params> <second params> <games> <seconds+increment> [openings] [pgn]
+//
+// plays the evaluation in the first parameter file against the one in the
+// second (a missing file means the built in evaluation) with a 0/5 sprt,
+// printing the standings after every game
+
+fn clock(text: &str) -> Option<TimeControl> {
+    let (base, increment) = text.split_once('+').unwrap_or((text, "0"));
+
+    Some(TimeControl {
+        remaining: Duration::from_secs_f64(base.parse().ok()?),
+        increment: Duration::from_secs_f64(increment.parse().ok()?),
+        moves_to_go: None
+    })
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 5 {
+        eprintln!("usage: {} <first params> <second params> <games> <seconds+increment> [openings] [pgn]", args[0]);
+        process::exit(1);
+    }
+
+    let engine = |path: &str| Engine {
+        params: EvalParams::load(path).unwrap_or_default(),
+        ..Engine::new(path, SearchConfig::default())
+    };
+
+    let games = args[3].parse().unwrap_or(100);
+    let control = clock(&args[4]).unwrap_or_else(|| {
+        eprintln!("time control should look like 10+0.1");
+        process::exit(1);
+    });
+
+    let mut runner = MatchRunner::new(engine(&args[1]), engine(&args[2]), MoveLimit::Clock(control));
+    runner.set_sprt(Some(Sprt { elo0: 0.0, elo1: 5.0, alpha: 0.05, beta: 0.05 }));
+
+    if let Some(path) = args.get(5) {
+        match load_openings(path) {
+            Ok(openings) => runner.set_openings(openings),
+            Err(error) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+        }
+    }
+
+    let mut pgn = args.get(6).map(|path| File::create(path).unwrap_or_else(|_| {
+        eprintln!("could not create {}", path);
+        process::exit(1);
+    }));
+
+    let stats = runner.run(games, |record, stats| {
+        if let Some(file) = pgn.as_mut() {
+            let _ = writeln!(file, "{}", record.pgn.to_pgn());
+        }
+
+        println!(
+            "game {}: {} ({}), +{} ={} -{}, elo {:.1} +/- {:.1}, los {:.1}%",
+            stats.games(),
+            record.result.as_str(),
+            record.termination,
+            stats.wins,
+            stats.draws,
+            stats.losses,
+            stats.elo(),
+            stats.elo_error(),
+            stats.los() * 100.0
+        );
+    });
+
+    println!("finished after {} games", stats.games());
+}
@@ -0,0 +1,39 @@
+use std::env;
+use std::process;
+
+use chess_engine::tuning::{load_positions, Tuner};
+use chess_engine::eval::EvalParams;
+
+// tune <labelled positions> <parameter file> [passes]
+//
+// starts from the parameter file if it exists, otherwise from the built in
+// evaluation, and writes the tuned values back to it
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("usage: {} <labelled positions> <parameter file> [passes]", args[0]);
+        process::exit(1);
+    }
+
+    let passes = args.get(3).and_then(|passes| passes.parse().ok()).unwrap_or(100);
+
+    let positions = load_positions(&args[1]).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+
+    let params = EvalParams::load(&args[2]).unwrap_or_default();
+
+    let mut tuner = Tuner::new(&positions);
+    let scale = tuner.fit_scale(&params);
+    println!("{} positions, scale {:.3}, loss {:.6}", tuner.len(), scale, tuner.loss(&params));
+
+    let tuned = tuner.tune(&params, passes);
+    println!("tuned loss {:.6}", tuner.loss(&tuned));
+
+    if let Err(error) = tuned.save(&args[2]) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
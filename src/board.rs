@@ -1,6 +1,7 @@
 use crate::pieces::{ChessPiece, ChessPieceColor};
-use crate::moves::{ChessMove};
+use crate::moves::{ChessMove, ChessMoveExt};
 use crate::math::V2;
+use crate::variant::Variant;
 
 const DEFAULT_CHESS_BOARD_MAP: [u8; 64] = [
     2, 3, 4, 5, 6, 4, 3, 2,
@@ -13,6 +14,11 @@ const DEFAULT_CHESS_BOARD_MAP: [u8; 64] = [
     2, 3, 4, 5, 6, 4, 3, 2
 ];
 
+// the largest board there's room for. tiles are kept in rows of MAX_FILES
+// whatever the board's own width, so a tile's index never changes
+pub const MAX_FILES: u16 = 10;
+pub const MAX_RANKS: u16 = 10;
+
 #[allow(dead_code)]
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct ChessTile (u8);
@@ -20,10 +26,16 @@ pub struct ChessTile (u8);
 #[allow(dead_code)]
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct ChessBoard {
-    turn: u8, // keeping track of the current turn
-    tiles: [ChessTile; 64], // 8x8 board
-    moves: [ChessMove; 50], // game ends at 50 moves
-    player_color: ChessPieceColor
+    turn: u16, // plies played, white moves on even turns
+    tiles: [ChessTile; (MAX_FILES * MAX_RANKS) as usize],
+    files: u16, // 8x8 unless set up from a wider or taller fen
+    ranks: u16,
+    last_move: Option<ChessMove>, // needed for en passant
+    player_color: ChessPieceColor,
+    chess960: bool, // castling from any file, written as the king taking its rook
+    variant: Variant,
+    checks: [u8; 2], // checks given by white and black, for three-check
+    pockets: [[u8; 5]; 2] // crazyhouse pieces in hand, white's then black's
 }
 
 #[allow(dead_code)]
@@ -31,8 +43,8 @@ impl ChessTile {
     pub fn new(piece: Option<ChessPiece>, color: Option<ChessPieceColor>) -> Result<Self, &'static str> {
         match piece {
             Some(piece) => match color {
-                Some(ChessPieceColor::Black) => Ok(ChessTile(piece as u8 | 1 << 3)),
-                Some(ChessPieceColor::White) => Ok(ChessTile(piece as u8 | 2 << 3)),
+                Some(ChessPieceColor::Black) => Ok(ChessTile(piece as u8 | 1 << 4)),
+                Some(ChessPieceColor::White) => Ok(ChessTile(piece as u8 | 2 << 4)),
                 None => Err("occupied tile requires a color")
             },
             None => Ok(ChessTile(0))
@@ -40,26 +52,49 @@ impl ChessTile {
     }
 
     pub fn has_moved(&self) -> bool {
-        (self.0 >> 5) & 1 == 1
+        (self.0 >> 6) & 1 == 1
     }
 
     pub fn moved(&self) -> Self {
-        ChessTile(self.0 | 1 << 5) // set the 6th bit to indicate the piece has moved
+        ChessTile(self.0 | 1 << 6) // set the 7th bit to indicate the piece has moved
+    }
+
+    // only tracked in crazyhouse, where a promoted piece is captured as a pawn
+    pub fn is_promoted(&self) -> bool {
+        (self.0 >> 7) & 1 == 1
+    }
+
+    pub fn promoted(&self) -> Self {
+        ChessTile(self.0 | 1 << 7)
     }
 
     pub fn piece(&self) -> Option<ChessPiece> {
-        ChessPiece::from(self.0 & 0x7)
+        ChessPiece::from(self.0 & 0xf)
             .expect("not a valid chess piece")
     }
 
     pub fn color(&self) -> Option<ChessPieceColor> {
-        match (self.0 >> 3) & 3 {
+        match (self.0 >> 4) & 3 {
             1 => Some(ChessPieceColor::Black),
             2 => Some(ChessPieceColor::White),
             _ => None
         }
     }
 
+    // the tile as stored, for the binary formats
+    pub(crate) fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub(crate) fn from_bits(bits: u8) -> Result<Self, &'static str> {
+        let piece = ChessPiece::from(bits & 0xf)?;
+        match (piece, (bits >> 4) & 3) {
+            (None, 0) if bits == 0 => Ok(ChessTile(0)),
+            (Some(_), 1 | 2) => Ok(ChessTile(bits)),
+            _ => Err("not a valid tile")
+        }
+    }
+
     pub fn from_layout(index: usize, value: &u8, colors: (ChessPieceColor, ChessPieceColor)) -> ChessTile {
         let color = match index {
             0..16 => Some(colors.1),
@@ -78,113 +113,382 @@ impl ChessTile {
 #[allow(dead_code)]
 impl ChessBoard {
     pub fn from_layout(player_color: ChessPieceColor, chess_board_map: [u8; 64]) -> Self {
-        let opponent_color = match player_color {
-            ChessPieceColor::White => ChessPieceColor::Black,
-            ChessPieceColor::Black => ChessPieceColor::White
-        };
+        let colors = (player_color, player_color.opposite());
+        let mut board = Self::empty(player_color, 8, 8).expect("8x8 fits");
+
+        for (index, value) in chess_board_map.iter().enumerate() {
+            let v2 = V2 { x: index as u16 % 8, y: index as u16 / 8 };
+            board.place(&v2, ChessTile::from_layout(index, value, colors));
+        }
 
-        let colors = (player_color, opponent_color);
+        board
+    }
 
-        ChessBoard {
-            player_color: player_color,
-            tiles: chess_board_map.iter()
-                .enumerate()
-                .map(|(index, value)| ChessTile::from_layout(index, value, colors))
-                .collect::<Vec<ChessTile>>()
-                .try_into()
-                .unwrap(),
-            moves: [ChessMove(0); 50],
-            turn: 0
+    // a board of any size up to MAX_FILES by MAX_RANKS with nothing on it
+    pub fn empty(player_color: ChessPieceColor, files: u16, ranks: u16) -> Result<Self, &'static str> {
+        if !(1..=MAX_FILES).contains(&files) || !(1..=MAX_RANKS).contains(&ranks) {
+            return Err("the board is too big");
         }
-    } 
+
+        Ok(ChessBoard {
+            player_color,
+            tiles: [ChessTile(0); (MAX_FILES * MAX_RANKS) as usize],
+            files,
+            ranks,
+            last_move: None,
+            turn: 0,
+            chess960: false,
+            variant: Variant::Standard,
+            checks: [0, 0],
+            pockets: [[0; 5]; 2]
+        })
+    }
 
     pub fn new(player_color: ChessPieceColor) -> Self {
         Self::from_layout(player_color, DEFAULT_CHESS_BOARD_MAP)
     }
 
+    // one of the 960 fischer random start positions, numbered the usual way
+    // so that 518 is the standard one. the bishops go on opposite colors,
+    // then the queen and the knights on the free files, and the king between
+    // the rooks on the three left over
+    pub fn chess960(player_color: ChessPieceColor, index: u16) -> Result<Self, &'static str> {
+        if index >= 960 {
+            return Err("chess960 positions are numbered 0 to 959");
+        }
+
+        // the codes from_layout takes
+        let (rook, knight, bishop, queen, king) = (2, 3, 4, 5, 6);
+        let knights = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+
+        let mut back_rank = [0u8; 8];
+        let mut rest = index as usize;
+
+        back_rank[2 * (rest % 4) + 1] = bishop;
+        rest /= 4;
+        back_rank[2 * (rest % 4)] = bishop;
+        rest /= 4;
+
+        let free = |back_rank: &[u8; 8]| (0..8).filter(|x| back_rank[*x] == 0).collect::<Vec<usize>>();
+
+        back_rank[free(&back_rank)[rest % 6]] = queen;
+        rest /= 6;
+
+        let (first, second) = knights[rest];
+        let files = free(&back_rank);
+        back_rank[files[first]] = knight;
+        back_rank[files[second]] = knight;
+
+        for (file, piece) in free(&back_rank).into_iter().zip([rook, king, rook]) {
+            back_rank[file] = piece;
+        }
+
+        let mut layout = [0u8; 64];
+        layout[..8].copy_from_slice(&back_rank);
+        layout[8..16].fill(1);
+        layout[48..56].fill(1);
+        layout[56..].copy_from_slice(&back_rank);
+
+        let mut board = Self::from_layout(player_color, layout);
+        board.chess960 = true;
+        Ok(board)
+    }
+
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    // changes how castling moves are generated and written, so it's best
+    // done before the game starts
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    // the rules the moves are generated and the game is ended by. the start
+    // position is up to the caller, see new_variant
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+    }
+
+    pub(crate) fn checks(&self) -> [u8; 2] {
+        self.checks
+    }
+
+    pub(crate) fn set_checks(&mut self, checks: [u8; 2]) {
+        self.checks = checks;
+    }
+
+    pub(crate) fn pockets(&self) -> [[u8; 5]; 2] {
+        self.pockets
+    }
+
+    pub(crate) fn set_pockets(&mut self, pockets: [[u8; 5]; 2]) {
+        self.pockets = pockets;
+    }
+
+    pub fn files(&self) -> u16 {
+        self.files
+    }
+
+    pub fn ranks(&self) -> u16 {
+        self.ranks
+    }
+
+    // 8x8 with only the six usual pieces, what the tablebases, books and
+    // networks know about
+    pub fn is_orthodox(&self) -> bool {
+        self.files == 8 && self.ranks == 8
+            && self.squares().all(|v2| self.tile(&v2).piece().is_none_or(|piece| piece.is_standard()))
+    }
+
+    // every tile of the board, a row at a time from the top
+    pub fn squares(&self) -> impl Iterator<Item = V2> {
+        let files = self.files;
+        (0..self.ranks).flat_map(move |y| (0..files).map(move |x| V2 { x, y }))
+    }
+
+    pub fn contains(&self, x: i8, y: i8) -> bool {
+        (0..self.files as i8).contains(&x) && (0..self.ranks as i8).contains(&y)
+    }
+
+    // the tile x and y away from origin, if it's on the board
+    pub fn offset(&self, origin: &V2, x: i8, y: i8) -> Option<V2> {
+        let (x, y) = (origin.x as i8 + x, origin.y as i8 + y);
+
+        match self.contains(x, y) {
+            true => Some(V2 { x: x as u16, y: y as u16 }),
+            false => None
+        }
+    }
+
     pub fn player_color(&self) -> ChessPieceColor {
         self.player_color
     }
 
+    // the player's pieces start at the bottom of the board and move up,
+    // the opponent's start at the top and move down
+    pub fn forward(&self, color: ChessPieceColor) -> i8 {
+        match color == self.player_color {
+            true => -1,
+            false => 1
+        }
+    }
+
     pub fn clear(&mut self, src: &V2) {
         let x = (*src).x as usize; 
         let y = (*src).y as usize;
-        self.tiles[x+ 8 * y] = ChessTile(0)
+        self.tiles[x + MAX_FILES as usize * y] = ChessTile(0)
     }
 
     pub fn place(&mut self, src: &V2, tile: ChessTile) {
         let x = (*src).x as usize; 
         let y = (*src).y as usize;
-        self.tiles[x + 8 * y] = tile;
+        self.tiles[x + MAX_FILES as usize * y] = tile;
     }
 
     pub fn last_turn(&self) -> Option<&ChessMove> {
-        match self.turn {
-            0 => None,
-            _ => Some(&self.moves[(self.turn - 1) as usize])
+        self.last_move.as_ref()
+    }
+
+    pub fn turn(&self) -> u16 {
+        self.turn
+    }
+
+    // for positions set up from a description rather than played out
+    pub(crate) fn set_turn(&mut self, turn: u16) {
+        self.turn = turn;
+    }
+
+    pub(crate) fn set_last_move(&mut self, last_move: Option<ChessMove>) {
+        self.last_move = last_move;
+    }
+
+    pub fn side_to_move(&self) -> ChessPieceColor {
+        match self.turn % 2 {
+            0 => ChessPieceColor::White,
+            _ => ChessPieceColor::Black
+        }
+    }
+
+    // the row the color's back rank pieces start on
+    pub fn home_row(&self, color: ChessPieceColor) -> u16 {
+        match color == self.player_color {
+            true => self.ranks - 1,
+            false => 0
+        }
+    }
+
+    // (king side, queen side). a side keeps the right to castle while its
+    // king and that rook have never left their starting tiles
+    pub fn castling_rights(&self, color: ChessPieceColor) -> (bool, bool) {
+        let (king_side, queen_side) = self.castling_rooks(color);
+        (king_side.is_some(), queen_side.is_some())
+    }
+
+    // the rooks each side could castle with. the king starts in the middle
+    // and the rooks in the corners, except in chess960, where the king may
+    // start on any file and the outermost unmoved rook on either side of it
+    // is the one that castles
+    pub fn castling_rooks(&self, color: ChessPieceColor) -> (Option<V2>, Option<V2>) {
+        let row = self.home_row(color);
+
+        let unmoved = |x: u16, piece: ChessPiece| {
+            let tile = self.tile(&V2 { x, y: row });
+            tile.piece() == Some(piece) && tile.color() == Some(color) && !tile.has_moved()
+        };
+
+        let king = (0..self.files).find(|x| unmoved(*x, ChessPiece::King))
+            .filter(|x| self.chess960 || *x == self.files / 2);
+
+        let Some(king) = king else {
+            return (None, None);
+        };
+
+        let mut rooks = (None, None);
+        for x in 0..self.files {
+            let corner = x == 0 || x == self.files - 1;
+            if !(self.chess960 || corner) || !unmoved(x, ChessPiece::Rook) {
+                continue;
+            }
+
+            let rook = Some(V2 { x, y: row });
+            match x > king {
+                true => rooks.0 = rook, // the last one is the outermost
+                false => if rooks.1.is_none() {
+                    rooks.1 = rook;
+                }
+            }
+        }
+
+        rooks
+    }
+
+    // the tile a pawn skipped over with a double step on the previous turn.
+    // horde pawns double stepping off the first rank can't be taken this way
+    pub fn en_passant(&self) -> Option<V2> {
+        match self.last_move {
+            Some(last_move) if last_move.ext() == ChessMoveExt::DoublePawnPush && last_move.src().y != 0 && last_move.src().y != self.ranks - 1 => {
+                let src = last_move.src();
+                let dst = last_move.dst();
+                Some(V2 { x: src.x, y: (src.y + dst.y) / 2 })
+            },
+            _ => None
         }
     }
 
     pub fn tile(&self, src: &V2) -> ChessTile {
         let x = (*src).x as usize; 
         let y = (*src).y as usize;
-        self.tiles[x+ 8 * y]
+        self.tiles[x + MAX_FILES as usize * y]
     }
 
     pub fn tile_pair(&self, src: &V2, dst: &V2) -> (ChessTile, ChessTile) {
         (self.tile(src), self.tile(dst))
     }
 
-    pub fn take_turn(&mut self, src: &V2, dst: &V2) -> bool {
-        if self.turn == 50 {
-            return false;
+    // where the king and the rook go when castling, then where the rook
+    // starts outside chess960. on a wider board they go just as far from
+    // the edge as on the usual one
+    pub fn castled_files(&self, king_side: bool) -> (u16, u16, u16) {
+        match king_side {
+            true => (self.files - 2, self.files - 3, self.files - 1),
+            false => (2, 3, 0)
         }
+    }
+
+    // plays a move without checking it, the caller is expected to take it
+    // from the move generator
+    pub fn make_move(&mut self, chess_move: &ChessMove) {
+        let src = chess_move.src();
+        let dst = chess_move.dst();
 
+        // castling moves the king and the rook onto the files they always
+        // end up on, the squares may overlap in chess960
+        let castle = match chess_move.ext() {
+            ChessMoveExt::KingCastle => Some(self.castled_files(true)),
+            ChessMoveExt::QueenCastle => Some(self.castled_files(false)),
+            _ => None
+        };
+
+        match (castle, chess_move.dropped()) {
+            (Some((king_to, rook_to, rook_from)), _) => {
+                let rook_from = match self.chess960 {
+                    true => dst,
+                    false => V2 { x: rook_from, y: src.y }
+                };
+
+                let (king, rook) = (self.tile(&src).moved(), self.tile(&rook_from).moved());
+                self.clear(&src);
+                self.clear(&rook_from);
+                self.place(&V2 { x: king_to, y: src.y }, king);
+                self.place(&V2 { x: rook_to, y: src.y }, rook);
+            },
+            (None, Some(piece)) => self.drop_piece(piece, &dst),
+            (None, None) => {
+                // make sure to set the moved bit
+                let mut tile = self.tile(&src).moved();
+
+                if let Some(piece) = chess_move.promotion() {
+                    tile = ChessTile::new(Some(piece), tile.color())
+                        .expect("promoting an empty tile")
+                        .moved();
+
+                    // crazyhouse hands a promoted piece back as a pawn
+                    if self.variant == Variant::Crazyhouse {
+                        tile = tile.promoted();
+                    }
+                }
+
+                let victim = match chess_move.ext() {
+                    ChessMoveExt::EpCapture => V2 { x: dst.x, y: src.y },
+                    _ => dst
+                };
+
+                if self.variant == Variant::Crazyhouse && chess_move.is_capture() {
+                    self.pocket_capture(self.tile(&victim));
+                }
+
+                if chess_move.ext() == ChessMoveExt::EpCapture {
+                    self.clear(&victim);
+                }
+
+                self.place(&dst, tile);
+                self.clear(&src);
+
+                if self.variant == Variant::Atomic && chess_move.is_capture() {
+                    self.explode(&dst);
+                }
+            }
+        }
+
+        self.last_move = Some(*chess_move);
+        self.turn += 1;
+
+        let mover = self.side_to_move().opposite();
+        if self.variant == Variant::ThreeCheck && self.in_check(self.side_to_move()) {
+            self.set_checks_given(mover, self.checks_given(mover) + 1);
+        }
+    }
+
+    // passes the turn without moving anything. not a legal chess move, the
+    // search uses it to measure how good a position is for the opponent
+    pub fn make_null_move(&mut self) {
+        self.last_move = None;
+        self.turn += 1;
+    }
+
+    pub fn take_turn(&mut self, src: &V2, dst: &V2) -> bool {
         // i would name this vaiable "move" but that's a keyword in rust
         match ChessMove::new(src, dst, *self) {
             Ok(movement) => {
-                // make sure to set the moved bit
-                self.place(dst, self.tile(src).moved());
-                self.clear(src);
-    
-                self.moves[self.turn as usize] = movement;
-                self.turn += 1;
-    
+                self.make_move(&movement);
                 true
             },
             Err(_) => false
         }
     }
-
-    pub fn show(&self) {
-        println!();
-        for y in 0..8 {
-            for x in 0..8 {
-                match self.tiles[x + 8 * y].piece() {
-                    Some(piece) => {
-                        let symbol = match piece {
-                            ChessPiece::Pawn => 'p',
-                            ChessPiece::Rook => 'r',
-                            ChessPiece::Knight => 'k',
-                            ChessPiece::Bishop => 'b',
-                            ChessPiece::Queen => 'q',
-                            ChessPiece::King => 'K'
-                        };
-
-                        let color = match self.tiles[x + 8 * y].color() {
-                            Some(ChessPieceColor::Black) => 'B',
-                            Some(ChessPieceColor::White) => 'W',
-                            _ => '_'
-                        };
-
-                        print!("{}{}", color, symbol);
-                    },
-                    None => print!("__")
-                };
-            }
-            println!();
-        }
-        println!("turn: {}", self.turn);
-        println!();
-    }
-}
\ No newline at end of file
+}
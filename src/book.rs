@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::board::ChessBoard;
+use crate::moves::{ChessMove, ChessMoveExt};
+use crate::pieces::ChessPiece;
+
+// polyglot opening books. a .bin book is a list of 16 byte big endian
+// entries sorted by position key: the key, a move, a weight and a learn
+// field nobody uses. a position can have several entries, one per move, and
+// the weights say how often each one should be played
+
+const ENTRY_SIZE: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub chess_move: u16, // polyglot's own move encoding, see decode_move
+    pub weight: u16,
+    pub learn: u32
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BookSelection {
+    Best, // always the heaviest move
+    Weighted // at random, in proportion to the weights
+}
+
+#[derive(Clone, Debug)]
+pub struct OpeningBook {
+    entries: Vec<BookEntry>,
+    state: u64 // for picking weighted moves
+}
+
+// polyglot squares count from a1 = 0 to h8 = 63
+fn polyglot_square(index: u16) -> String {
+    format!("{}{}", (b'a' + (index % 8) as u8) as char, index / 8 + 1)
+}
+
+#[allow(dead_code)]
+impl BookEntry {
+    pub fn new(board: &ChessBoard, chess_move: &ChessMove, weight: u16) -> Self {
+        BookEntry {
+            key: board.hash(),
+            chess_move: encode_move(board, chess_move),
+            weight,
+            learn: 0
+        }
+    }
+}
+
+// to and from square in the low twelve bits, then the promotion piece.
+// castling is written as the king taking its own rook, e1h1 rather than e1g1
+fn encode_move(board: &ChessBoard, chess_move: &ChessMove) -> u16 {
+    let src = board.square_index(&chess_move.src()) as u16;
+    let mut dst = board.square_index(&chess_move.dst()) as u16;
+
+    // chess960 moves are written that way already
+    match (chess_move.ext(), board.is_chess960()) {
+        (ChessMoveExt::KingCastle, false) => dst += 1,
+        (ChessMoveExt::QueenCastle, false) => dst -= 2,
+        _ => ()
+    }
+
+    let promotion = match chess_move.promotion() {
+        Some(ChessPiece::Knight) => 1,
+        Some(ChessPiece::Bishop) => 2,
+        Some(ChessPiece::Rook) => 3,
+        Some(ChessPiece::Queen) => 4,
+        _ => 0
+    };
+
+    dst | src << 6 | promotion << 12
+}
+
+// the legal move a book move stands for, if there is one
+fn decode_move(board: &ChessBoard, book_move: u16) -> Option<ChessMove> {
+    let src = (book_move >> 6) & 0x3f;
+    let mut dst = book_move & 0x3f;
+
+    let promotion = match (book_move >> 12) & 0x7 {
+        1 => "n",
+        2 => "b",
+        3 => "r",
+        4 => "q",
+        _ => ""
+    };
+
+    // a king on its starting file "capturing" a rook on its home row castles
+    let king = board.parse_square(&polyglot_square(src))
+        .map(|v2| board.tile(&v2).piece() == Some(ChessPiece::King))
+        .unwrap_or(false);
+
+    if king && !board.is_chess960() && src % 8 == 4 && src / 8 == dst / 8 {
+        dst = match dst % 8 {
+            7 => dst - 1,
+            0 => dst + 2,
+            _ => dst
+        };
+    }
+
+    let text = format!("{}{}{}", polyglot_square(src), polyglot_square(dst), promotion);
+    board.parse_uci_move(&text)
+}
+
+#[allow(dead_code)]
+impl OpeningBook {
+    pub fn new(mut entries: Vec<BookEntry>) -> Self {
+        // stable, so moves of one position keep the order they were given in
+        entries.sort_by_key(|entry| entry.key);
+
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+
+        OpeningBook { entries, state: seed | 1 }
+    }
+
+    // fixes the random choices, so a run can be repeated
+    pub fn with_seed(self, seed: u64) -> Self {
+        OpeningBook { state: seed | 1, ..self }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err("opening book is not a whole number of entries");
+        }
+
+        let entries = bytes.chunks_exact(ENTRY_SIZE)
+            .map(|chunk| BookEntry {
+                key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+                chess_move: u16::from_be_bytes(chunk[8..10].try_into().unwrap()),
+                weight: u16::from_be_bytes(chunk[10..12].try_into().unwrap()),
+                learn: u32::from_be_bytes(chunk[12..16].try_into().unwrap())
+            })
+            .collect();
+
+        Ok(Self::new(entries))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let bytes = fs::read(path).map_err(|_| "could not read opening book")?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries.iter()
+            .flat_map(|entry| {
+                let mut bytes = entry.key.to_be_bytes().to_vec();
+                bytes.extend(entry.chess_move.to_be_bytes());
+                bytes.extend(entry.weight.to_be_bytes());
+                bytes.extend(entry.learn.to_be_bytes());
+                bytes
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+
+    // the book's moves for the position with their weights. entries that
+    // don't decode to a legal move (a hash collision, or a broken book) are
+    // left out
+    pub fn moves(&self, board: &ChessBoard) -> Vec<(ChessMove, u16)> {
+        self.entries(board.hash()).iter()
+            .filter_map(|entry| decode_move(board, entry.chess_move).map(|chess_move| (chess_move, entry.weight)))
+            .collect()
+    }
+
+    pub fn pick(&mut self, board: &ChessBoard, selection: BookSelection) -> Option<ChessMove> {
+        let moves = self.moves(board);
+
+        match selection {
+            BookSelection::Best => moves.iter()
+                .filter(|(_, weight)| *weight > 0)
+                .rev() // max_by_key keeps the last of equals, prefer the first
+                .max_by_key(|(_, weight)| *weight)
+                .map(|(chess_move, _)| *chess_move),
+            BookSelection::Weighted => {
+                let total: u64 = moves.iter().map(|(_, weight)| *weight as u64).sum();
+                if total == 0 {
+                    return None;
+                }
+
+                let mut target = self.next_random() % total;
+                for (chess_move, weight) in moves {
+                    match target < weight as u64 {
+                        true => return Some(chess_move),
+                        false => target -= weight as u64
+                    }
+                }
+
+                None
+            }
+        }
+    }
+
+    // xorshift64, plenty for choosing between a handful of openings
+    fn next_random(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
+
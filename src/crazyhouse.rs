@@ -0,0 +1,102 @@
+use crate::board::{ChessBoard, ChessTile};
+use crate::moves::ChessMove;
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::math::V2;
+
+// crazyhouse. a captured piece changes sides and goes into the capturer's
+// pocket, and instead of moving a player may drop a piece from their pocket
+// onto any empty tile, pawns only between the first and last ranks. pieces
+// that were promoted go back into the pocket as pawns, so the tiles remember
+// which ones were
+
+// the order fen writes a pocket in
+pub const POCKET_PIECES: [ChessPiece; 5] = [
+    ChessPiece::Queen, ChessPiece::Rook, ChessPiece::Bishop, ChessPiece::Knight, ChessPiece::Pawn
+];
+
+// pawn, rook, knight, bishop and queen are 1 to 5. kings and the fairy
+// pieces have no place in a pocket
+fn pocket_index(piece: ChessPiece) -> Option<usize> {
+    match POCKET_PIECES.contains(&piece) {
+        true => Some(piece as usize - 1),
+        false => None
+    }
+}
+
+#[allow(dead_code)]
+impl ChessBoard {
+    pub fn pocket(&self, color: ChessPieceColor, piece: ChessPiece) -> u8 {
+        match pocket_index(piece) {
+            Some(index) => self.pockets()[color.index()][index],
+            None => 0
+        }
+    }
+
+    // kings and fairy pieces never go into a pocket
+    pub fn set_pocket(&mut self, color: ChessPieceColor, piece: ChessPiece, count: u8) {
+        let Some(index) = pocket_index(piece) else {
+            return;
+        };
+
+        let mut pockets = self.pockets();
+        pockets[color.index()][index] = count;
+        self.set_pockets(pockets);
+    }
+
+    // what the color's pocket is worth, for the evaluation
+    pub fn pocket_value(&self, color: ChessPieceColor) -> i32 {
+        POCKET_PIECES.iter()
+            .map(|piece| piece.value() * self.pocket(color, *piece) as i32)
+            .sum()
+    }
+
+    // the piece about to be captured goes to the other side. a captured
+    // fairy piece is just gone, there's no dropping it
+    pub(crate) fn pocket_capture(&mut self, captured: ChessTile) {
+        let (Some(piece), Some(color)) = (captured.piece(), captured.color()) else {
+            return;
+        };
+
+        let piece = match captured.is_promoted() {
+            true => ChessPiece::Pawn,
+            false => piece
+        };
+
+        let capturer = color.opposite();
+        self.set_pocket(capturer, piece, self.pocket(capturer, piece) + 1);
+    }
+
+    // a dropped piece counts as moved, a rook dropped in the corner can't
+    // castle
+    pub(crate) fn drop_piece(&mut self, piece: ChessPiece, dst: &V2) {
+        let side = self.side_to_move();
+        let tile = ChessTile::new(Some(piece), Some(side))
+            .expect("dropping a piece")
+            .moved();
+
+        self.place(dst, tile);
+        self.set_pocket(side, piece, self.pocket(side, piece).saturating_sub(1));
+    }
+
+    // every drop from the side to move's pocket, legal or not
+    pub fn drop_moves(&self) -> Vec<ChessMove> {
+        let side = self.side_to_move();
+        let mut moves = vec![];
+
+        for piece in POCKET_PIECES {
+            if self.pocket(side, piece) == 0 {
+                continue;
+            }
+
+            for dst in self.squares() {
+                let back_rank = dst.y == 0 || dst.y == self.ranks() - 1;
+
+                if self.tile(&dst).piece().is_none() && !(piece == ChessPiece::Pawn && back_rank) {
+                    moves.push(ChessMove::drop(piece, &dst));
+                }
+            }
+        }
+
+        moves
+    }
+}
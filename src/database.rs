@@ -0,0 +1,483 @@
+use std::fs;
+use std::path::Path;
+
+use crate::board::ChessBoard;
+use crate::moves::ChessMove;
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::variant::Variant;
+use crate::notation::{piece_letter, letter_piece};
+use crate::packed::PACKED_SIZE;
+use crate::pgn::{PgnGame, GameResult, parse_games};
+
+// a library of games to search. every position of every game is indexed by
+// its zobrist hash and every material balance a game passes through by its
+// signature, so "which games reach this position" and "what was played
+// from here, and how did it go" are a lookup rather than a replay.
+//
+// on disk it's a magic number and the number of games, then for each game
+// its tags, its result, its start position packed (or as fen with the
+// variant when it doesn't pack) and its moves packed. the two sorted index
+// tables follow, so loading doesn't have to hash every position again.
+// all little endian
+
+const MAGIC: [u8; 4] = *b"CEGD";
+
+const RESULTS: [GameResult; 4] = [GameResult::WhiteWins, GameResult::BlackWins, GameResult::Draw, GameResult::Unfinished];
+
+// the order a material signature lists each side's pieces in, like KRPvKR
+const MATERIAL_ORDER: [ChessPiece; 9] = [
+    ChessPiece::King, ChessPiece::Amazon, ChessPiece::Queen, ChessPiece::Chancellor, ChessPiece::Archbishop,
+    ChessPiece::Rook, ChessPiece::Bishop, ChessPiece::Knight, ChessPiece::Pawn
+];
+
+// what the games have to match, everything that's set. positions and
+// material only have to come up at some point in the game
+#[derive(Clone, Debug, Default)]
+pub struct GameQuery {
+    pub position: Option<ChessBoard>,
+    pub material: Option<String>, // a signature like KRPvKR
+    pub tags: Vec<(String, String)>, // like ("White", "Carlsen") or ("ECO", "C42")
+    pub result: Option<GameResult>
+}
+
+// how a move played from a position turned out
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MoveStats {
+    pub chess_move: ChessMove,
+    pub games: u32, // unfinished games count here and nowhere else
+    pub white_wins: u32,
+    pub draws: u32,
+    pub black_wins: u32
+}
+
+#[derive(Clone, Debug)]
+pub struct GameDatabase {
+    player_color: ChessPieceColor, // the games are kept this way up
+    games: Vec<PgnGame>,
+    positions: Vec<(u64, u32, u16)>, // hash, game and ply, sorted by hash
+    materials: Vec<(String, u32)> // signature and game, sorted by signature
+}
+
+// the pieces on the board, white's then black's, like KRPvKR
+pub fn material_signature(board: &ChessBoard) -> String {
+    let side = |color: ChessPieceColor| {
+        let mut pieces: Vec<ChessPiece> = board.squares()
+            .map(|v2| board.tile(&v2))
+            .filter(|tile| tile.color() == Some(color))
+            .filter_map(|tile| tile.piece())
+            .collect();
+
+        pieces.sort_by_key(|piece| MATERIAL_ORDER.iter().position(|other| other == piece));
+        pieces.into_iter().map(|piece| piece_letter(piece).to_ascii_uppercase()).collect::<String>()
+    };
+
+    format!("{}v{}", side(ChessPieceColor::White), side(ChessPieceColor::Black))
+}
+
+// a signature with its letters in any order, put in the usual one
+fn normalise_signature(signature: &str) -> Option<String> {
+    let (white, black) = signature.split_once('v')?;
+
+    let side = |letters: &str| {
+        let mut pieces = letters.chars().map(letter_piece).collect::<Option<Vec<ChessPiece>>>()?;
+        pieces.sort_by_key(|piece| MATERIAL_ORDER.iter().position(|other| other == piece));
+        Some(pieces.into_iter().map(|piece| piece_letter(piece).to_ascii_uppercase()).collect::<String>())
+    };
+
+    Some(format!("{}v{}", side(white)?, side(black)?))
+}
+
+// reads the saved form a piece at a time
+struct Reader<'a> {
+    bytes: &'a [u8]
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], &'static str> {
+        if self.bytes.len() < length {
+            return Err("game database ends part way through");
+        }
+
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, &'static str> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn text(&mut self) -> Result<String, &'static str> {
+        let length = self.u16()? as usize;
+        let text = std::str::from_utf8(self.take(length)?).map_err(|_| "game database text isn't utf-8")?;
+        Ok(text.to_string())
+    }
+}
+
+fn write_text(bytes: &mut Vec<u8>, text: &str) {
+    // tags are short, anything that isn't is cut at a character boundary
+    let mut length = text.len().min(u16::MAX as usize);
+    while !text.is_char_boundary(length) {
+        length -= 1;
+    }
+
+    bytes.extend((length as u16).to_le_bytes());
+    bytes.extend(&text.as_bytes()[..length]);
+}
+
+#[allow(dead_code)]
+impl MoveStats {
+    // the points the side that played the move scored, as a fraction of the
+    // finished games
+    pub fn score(&self, color: ChessPieceColor) -> Option<f64> {
+        let finished = self.white_wins + self.draws + self.black_wins;
+        if finished == 0 {
+            return None;
+        }
+
+        let wins = match color {
+            ChessPieceColor::White => self.white_wins,
+            ChessPieceColor::Black => self.black_wins
+        };
+
+        Some((wins as f64 + self.draws as f64 / 2.0) / finished as f64)
+    }
+}
+
+#[allow(dead_code)]
+impl GameDatabase {
+    pub fn new(player_color: ChessPieceColor) -> Self {
+        GameDatabase { player_color, games: vec![], positions: vec![], materials: vec![] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    pub fn game(&self, index: usize) -> Option<&PgnGame> {
+        self.games.get(index)
+    }
+
+    pub fn games(&self) -> &[PgnGame] {
+        &self.games
+    }
+
+    // the moves have to be legal, the same as a game read from pgn
+    pub fn add(&mut self, game: PgnGame) -> Result<(), &'static str> {
+        game.start().pack_moves(game.moves())?;
+
+        self.index(game);
+        self.sort();
+        Ok(())
+    }
+
+    // every game in the text, returns how many there were. nothing is added
+    // if one of them doesn't read
+    pub fn import_pgn(&mut self, text: &str) -> Result<usize, &'static str> {
+        let games = parse_games(self.player_color, text)?;
+        let count = games.len();
+
+        for game in games {
+            self.index(game);
+        }
+        self.sort();
+
+        Ok(count)
+    }
+
+    pub fn import_pgn_file<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, &'static str> {
+        let text = fs::read_to_string(path).map_err(|_| "could not read pgn")?;
+        self.import_pgn(&text)
+    }
+
+    // added to the end of the index, which is sorted again once a batch of
+    // games is in
+    fn index(&mut self, game: PgnGame) {
+        let game = match game.start().player_color() == self.player_color {
+            true => game,
+            false => game.oriented(self.player_color)
+        };
+
+        let number = self.games.len() as u32;
+        let mut board = *game.start();
+        let mut materials = vec![];
+
+        for ply in 0..=game.moves().len() {
+            self.positions.push((board.hash(), number, ply.min(u16::MAX as usize) as u16));
+
+            let signature = material_signature(&board);
+            if !materials.contains(&signature) {
+                materials.push(signature);
+            }
+
+            if let Some(chess_move) = game.moves().get(ply) {
+                board.make_move(chess_move);
+            }
+        }
+
+        self.materials.extend(materials.into_iter().map(|signature| (signature, number)));
+        self.games.push(game);
+    }
+
+    // stable, so the games of one key stay in the order they were added
+    fn sort(&mut self) {
+        self.positions.sort_by_key(|(hash, _, _)| *hash);
+        self.materials.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+
+    // every time a game reaches the position, as the game and the ply
+    fn occurrences(&self, board: &ChessBoard) -> &[(u64, u32, u16)] {
+        let hash = board.hash();
+        let start = self.positions.partition_point(|(key, _, _)| *key < hash);
+        let end = self.positions.partition_point(|(key, _, _)| *key <= hash);
+        &self.positions[start..end]
+    }
+
+    // the games that reach the position, each one once
+    pub fn games_reaching(&self, board: &ChessBoard) -> Vec<usize> {
+        let mut games: Vec<usize> = self.occurrences(board).iter().map(|(_, game, _)| *game as usize).collect();
+        games.sort();
+        games.dedup();
+        games
+    }
+
+    pub fn games_with_material(&self, signature: &str) -> Vec<usize> {
+        let Some(signature) = normalise_signature(signature) else {
+            return vec![];
+        };
+
+        let start = self.materials.partition_point(|(other, _)| *other < signature);
+        let end = self.materials.partition_point(|(other, _)| *other <= signature);
+
+        let mut games: Vec<usize> = self.materials[start..end].iter().map(|(_, game)| *game as usize).collect();
+        games.sort();
+        games
+    }
+
+    // the games matching everything the query sets, in the order they were
+    // added
+    pub fn search(&self, query: &GameQuery) -> Vec<usize> {
+        let mut games: Vec<usize> = match (&query.position, &query.material) {
+            (Some(board), _) => self.games_reaching(board),
+            (None, Some(signature)) => self.games_with_material(signature),
+            (None, None) => (0..self.games.len()).collect()
+        };
+
+        if let (Some(_), Some(signature)) = (&query.position, &query.material) {
+            let material = self.games_with_material(signature);
+            games.retain(|game| material.binary_search(game).is_ok());
+        }
+
+        games.retain(|game| {
+            let game = &self.games[*game];
+            query.result.is_none_or(|result| game.result() == result)
+                && query.tags.iter().all(|(name, value)| game.tag(name) == Some(value.as_str()))
+        });
+
+        games
+    }
+
+    // the moves played from the position and how those games ended, most
+    // played first. the board can be either way up, the moves come back for
+    // it. a game that comes back to the position counts each move it played
+    // from there once
+    pub fn move_stats(&self, board: &ChessBoard) -> Vec<MoveStats> {
+        let oriented = match board.player_color() == self.player_color {
+            true => *board,
+            false => {
+                let mut oriented = ChessBoard::from_fen_sized(self.player_color, &board.to_fen()).unwrap_or(*board);
+                oriented.set_variant(board.variant());
+                oriented.set_chess960(board.is_chess960());
+                oriented
+            }
+        };
+
+        let legal = oriented.legal_moves();
+        let mut played: Vec<(ChessMove, u32)> = vec![];
+        let mut stats: Vec<MoveStats> = vec![];
+
+        for (_, game, ply) in self.occurrences(board) {
+            // a hash collision plays a move that isn't legal here
+            let Some(chess_move) = self.games[*game as usize].moves().get(*ply as usize).copied() else {
+                continue;
+            };
+            if !legal.contains(&chess_move) || played.contains(&(chess_move, *game)) {
+                continue;
+            }
+            played.push((chess_move, *game));
+
+            let index = match stats.iter().position(|stats| stats.chess_move == chess_move) {
+                Some(index) => index,
+                None => {
+                    stats.push(MoveStats { chess_move, games: 0, white_wins: 0, draws: 0, black_wins: 0 });
+                    stats.len() - 1
+                }
+            };
+
+            let entry = &mut stats[index];
+            entry.games += 1;
+            match self.games[*game as usize].result() {
+                GameResult::WhiteWins => entry.white_wins += 1,
+                GameResult::BlackWins => entry.black_wins += 1,
+                GameResult::Draw => entry.draws += 1,
+                GameResult::Unfinished => ()
+            }
+        }
+
+        if oriented.player_color() != board.player_color() {
+            stats.retain_mut(|stats| match board.parse_uci_move(&oriented.move_to_uci(&stats.chess_move)) {
+                Some(chess_move) => {
+                    stats.chess_move = chess_move;
+                    true
+                },
+                None => false
+            });
+        }
+
+        // stable, equals stay in the order they were first played
+        stats.sort_by_key(|stats| std::cmp::Reverse(stats.games));
+        stats
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend((self.games.len() as u32).to_le_bytes());
+
+        for game in &self.games {
+            bytes.extend((game.tags().len() as u16).to_le_bytes());
+            for (name, value) in game.tags() {
+                write_text(&mut bytes, name);
+                write_text(&mut bytes, value);
+            }
+
+            bytes.push(RESULTS.iter().position(|result| *result == game.result()).unwrap() as u8);
+
+            let start = game.start();
+            match start.pack() {
+                Ok(packed) => {
+                    bytes.push(0);
+                    bytes.extend(packed);
+                },
+                Err(_) => {
+                    bytes.push(1);
+                    bytes.push(Variant::ALL.iter().position(|variant| *variant == start.variant()).unwrap() as u8);
+                    bytes.push(start.is_chess960() as u8);
+                    write_text(&mut bytes, &start.to_fen());
+                }
+            }
+
+            // every game's moves were checked on the way in
+            let moves = start.pack_moves(game.moves()).expect("the moves of a game in the database are legal");
+            bytes.extend((moves.len() as u32).to_le_bytes());
+            bytes.extend(moves);
+        }
+
+        bytes.extend((self.positions.len() as u32).to_le_bytes());
+        for (hash, game, ply) in &self.positions {
+            bytes.extend(hash.to_le_bytes());
+            bytes.extend(game.to_le_bytes());
+            bytes.extend(ply.to_le_bytes());
+        }
+
+        bytes.extend((self.materials.len() as u32).to_le_bytes());
+        for (signature, game) in &self.materials {
+            write_text(&mut bytes, signature);
+            bytes.extend(game.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(player_color: ChessPieceColor, bytes: &[u8]) -> Result<Self, &'static str> {
+        if bytes.len() < 8 || bytes[0..4] != MAGIC {
+            return Err("not a game database");
+        }
+
+        let mut reader = Reader { bytes: &bytes[4..] };
+        let count = reader.u32()?;
+        let mut database = Self::new(player_color);
+
+        for _ in 0..count {
+            let tags = (0..reader.u16()?)
+                .map(|_| Ok((reader.text()?, reader.text()?)))
+                .collect::<Result<Vec<(String, String)>, &'static str>>()?;
+
+            let result = *RESULTS.get(reader.u8()? as usize).ok_or("not a game result")?;
+
+            let start = match reader.u8()? {
+                0 => ChessBoard::unpack(player_color, reader.take(PACKED_SIZE)?)?,
+                1 => {
+                    let variant = *Variant::ALL.get(reader.u8()? as usize).ok_or("not a variant")?;
+                    let chess960 = reader.u8()? != 0;
+                    let mut start = ChessBoard::from_fen_sized(player_color, &reader.text()?)?;
+                    start.set_variant(variant);
+                    start.set_chess960(chess960);
+                    start
+                },
+                _ => return Err("not a game database")
+            };
+
+            let length = reader.u32()? as usize;
+            let moves = start.unpack_moves(reader.take(length)?)?;
+
+            let mut game = PgnGame::new(start);
+            for (name, value) in &tags {
+                game.set_tag(name, value);
+            }
+            for chess_move in moves {
+                game.push(chess_move);
+            }
+            game.set_result(result);
+
+            database.games.push(game);
+        }
+
+        // the hashes and signatures don't depend on which way up the board
+        // is, so the tables are good for any player color
+        for _ in 0..reader.u32()? {
+            database.positions.push((reader.u64()?, reader.u32()?, reader.u16()?));
+        }
+        for _ in 0..reader.u32()? {
+            database.materials.push((reader.text()?, reader.u32()?));
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err("game database has bytes after its index");
+        }
+
+        let games = database.games.len() as u32;
+        if database.positions.iter().any(|(_, game, _)| *game >= games) || database.materials.iter().any(|(_, game)| *game >= games) {
+            return Err("game database index names a game it doesn't have");
+        }
+        if !database.positions.is_sorted_by_key(|(hash, _, _)| *hash) || !database.materials.is_sorted_by(|a, b| a.0 <= b.0) {
+            return Err("game database index isn't sorted");
+        }
+
+        Ok(database)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), &'static str> {
+        fs::write(path, self.to_bytes()).map_err(|_| "could not write game database")
+    }
+
+    pub fn load<P: AsRef<Path>>(player_color: ChessPieceColor, path: P) -> Result<Self, &'static str> {
+        let bytes = fs::read(path).map_err(|_| "could not read game database")?;
+        Self::from_bytes(player_color, &bytes)
+    }
+}
@@ -0,0 +1,682 @@
+use std::fs;
+use std::path::Path;
+
+use crate::board::{ChessBoard, ChessTile};
+use crate::moves::ChessMove;
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::math::V2;
+
+// endgame tables the crate builds for itself. every position of a small
+// material balance gets a slot, the checkmates are found with the legal move
+// generator, and the results are walked backwards one ply at a time through
+// un-made moves (retrograde analysis) until nothing changes. a slot keeps the
+// distance to mate, so unlike syzygy tables they can be played from directly.
+// castling and en passant are left out, like in every other tablebase
+
+pub const MAX_PIECES: usize = 4;
+
+const MAGIC: [u8; 4] = *b"CETB";
+const EXTENSION: &str = "etb";
+
+// a slot holds the plies to mate, or one of these
+const DRAW: u8 = 254;
+const INVALID: u8 = 255; // an impossible position, or a copy of another one
+
+// without pawns the board can be turned and mirrored until the white king
+// stands in the a1-d1-d4 triangle. with pawns it can only be mirrored, so the
+// king stays on the a to d files
+const TRIANGLE: [usize; 10] = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+
+const LETTERS: [(char, ChessPiece); 6] = [
+    ('K', ChessPiece::King), ('Q', ChessPiece::Queen), ('R', ChessPiece::Rook),
+    ('B', ChessPiece::Bishop), ('N', ChessPiece::Knight), ('P', ChessPiece::Pawn)
+];
+
+// plies to mate for the side to move
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dtm { Win(u8), Loss(u8), Draw }
+
+#[derive(Clone, Debug)]
+pub struct EndgameTable {
+    name: String, // like KQvK, white's pieces first
+    pieces: Vec<(ChessPiece, ChessPieceColor)>, // in name order, white king first
+    values: Vec<u8>
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EndgameTables {
+    tables: Vec<EndgameTable>
+}
+
+type Pieces = Vec<(ChessPiece, ChessPieceColor)>;
+
+fn letter(piece: ChessPiece) -> char {
+    LETTERS.iter().find(|(_, other)| *other == piece).unwrap().0
+}
+
+fn order(piece: ChessPiece) -> usize {
+    LETTERS.iter().position(|(_, other)| *other == piece).unwrap()
+}
+
+fn material_name(pieces: &[(ChessPiece, ChessPieceColor)]) -> String {
+    let side = |color: ChessPieceColor| {
+        let mut letters: Vec<ChessPiece> = pieces.iter()
+            .filter(|(_, other)| *other == color)
+            .map(|(piece, _)| *piece)
+            .collect();
+
+        letters.sort_by_key(|piece| order(*piece));
+        letters.into_iter().map(letter).collect::<String>()
+    };
+
+    format!("{}v{}", side(ChessPieceColor::White), side(ChessPieceColor::Black))
+}
+
+fn parse_material(name: &str) -> Result<Pieces, &'static str> {
+    let (white, black) = name.split_once('v').ok_or("not a material name like KQvK")?;
+    let mut pieces = vec![];
+
+    for (letters, color) in [(white, ChessPieceColor::White), (black, ChessPieceColor::Black)] {
+        let mut side = letters.chars()
+            .map(|c| LETTERS.iter().find(|(other, _)| *other == c).map(|(_, piece)| *piece))
+            .collect::<Option<Vec<ChessPiece>>>()
+            .ok_or("not a material name like KQvK")?;
+
+        if side.iter().filter(|piece| **piece == ChessPiece::King).count() != 1 {
+            return Err("each side needs exactly one king");
+        }
+
+        side.sort_by_key(|piece| order(*piece));
+        pieces.extend(side.into_iter().map(|piece| (piece, color)));
+    }
+
+    if pieces.len() > MAX_PIECES {
+        return Err("endgame tables go up to four pieces");
+    }
+
+    Ok(pieces)
+}
+
+// bare kings, or a lone minor piece, can never mate
+fn insufficient(pieces: &[(ChessPiece, ChessPieceColor)]) -> bool {
+    let extra: Vec<ChessPiece> = pieces.iter()
+        .map(|(piece, _)| *piece)
+        .filter(|piece| *piece != ChessPiece::King)
+        .collect();
+
+    extra.len() <= 1 && extra.iter().all(|piece| matches!(piece, ChessPiece::Knight | ChessPiece::Bishop))
+}
+
+// every material a capture or a promotion can lead to
+fn smaller_materials(pieces: &[(ChessPiece, ChessPieceColor)]) -> Vec<String> {
+    let mut names = vec![];
+
+    for (index, (piece, color)) in pieces.iter().enumerate() {
+        if *piece == ChessPiece::King {
+            continue;
+        }
+
+        let mut rest = pieces.to_vec();
+        rest.remove(index);
+        names.push(material_name(&rest));
+
+        if *piece == ChessPiece::Pawn {
+            for promotion in [ChessPiece::Queen, ChessPiece::Rook, ChessPiece::Bishop, ChessPiece::Knight] {
+                let mut promoted = pieces.to_vec();
+                promoted[index] = (promotion, *color);
+                names.push(material_name(&promoted));
+            }
+        }
+    }
+
+    names
+}
+
+// the pieces of a board with their squares (a1 = 0)
+fn board_pieces(board: &ChessBoard) -> Vec<(usize, ChessPiece, ChessPieceColor)> {
+    board.squares()
+        .filter_map(|v2| {
+            let tile = board.tile(&v2);
+            Some((board.square_index(&v2), tile.piece()?, tile.color()?))
+        })
+        .collect()
+}
+
+// the tables put white at the bottom of the board
+fn square_v2(square: usize) -> V2 {
+    V2 { x: (square % 8) as u16, y: 7 - (square / 8) as u16 }
+}
+
+// one of the eight ways to turn and mirror the board
+fn transform(square: usize, symmetry: usize) -> usize {
+    let (mut file, mut rank) = (square % 8, square / 8);
+
+    if symmetry & 1 != 0 {
+        file = 7 - file;
+    }
+    if symmetry & 2 != 0 {
+        rank = 7 - rank;
+    }
+    if symmetry & 4 != 0 {
+        (file, rank) = (rank, file);
+    }
+
+    file + 8 * rank
+}
+
+impl Dtm {
+    fn from_value(value: u8) -> Option<Self> {
+        match value {
+            INVALID => None,
+            DRAW => Some(Dtm::Draw),
+            plies if plies % 2 == 1 => Some(Dtm::Win(plies)),
+            plies => Some(Dtm::Loss(plies))
+        }
+    }
+
+    // higher is better for the side that moved into it
+    fn rank_for_mover(&self) -> i32 {
+        match self {
+            Dtm::Loss(plies) => 1000 - *plies as i32, // mate as soon as possible
+            Dtm::Draw => 0,
+            Dtm::Win(plies) => *plies as i32 - 1000 // and hold out as long as possible
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl EndgameTable {
+    // needs the tables for every smaller material the position can turn
+    // into already in `tables`, see EndgameTables::generate
+    pub fn generate(name: &str, tables: &EndgameTables) -> Result<Self, &'static str> {
+        let pieces = parse_material(name)?;
+
+        let mut table = EndgameTable {
+            name: material_name(&pieces),
+            values: vec![],
+            pieces
+        };
+
+        let slots = table.slots();
+        table.values = vec![INVALID; slots];
+
+        // how many children of each position aren't known to be won for the
+        // opponent yet. the position is lost once none are left
+        let mut remaining = vec![0u8; slots];
+
+        // per ply, the positions with a child decided at the ply before, and
+        // whether that child was lost (so the position wins) or won
+        let mut events: Vec<Vec<(usize, bool)>> = vec![];
+        let mut decided = vec![];
+
+        for (index, remaining) in remaining.iter_mut().enumerate() {
+            let board = match table.valid_board(index) {
+                Some(board) => board,
+                None => continue
+            };
+
+            table.values[index] = DRAW;
+
+            let moves = board.legal_moves();
+            let mut children = vec![];
+            let mut leaving = 0;
+
+            for chess_move in &moves {
+                let mut child = board;
+                child.make_move(chess_move);
+
+                if let Some(child_index) = table.index_of(&child) {
+                    children.push(child_index);
+                    continue;
+                }
+
+                // captures and promotions leave the table
+                leaving += 1;
+
+                let (plies, lost) = match tables.probe(&child).ok_or("missing a smaller endgame table")? {
+                    Dtm::Loss(plies) => (plies, true),
+                    Dtm::Win(plies) => (plies, false),
+                    Dtm::Draw => continue
+                };
+
+                let ply = plies as usize + 1;
+                if events.len() <= ply {
+                    events.resize(ply + 1, vec![]);
+                }
+                events[ply].push((index, lost));
+            }
+
+            // a move and its mirror image can reach the same slot, count it once
+            children.sort_unstable();
+            children.dedup();
+            *remaining = leaving + children.len() as u8;
+
+            if moves.is_empty() && board.in_check(board.side_to_move()) {
+                table.values[index] = 0;
+                decided.push(index);
+            }
+        }
+
+        let mut ply = 0;
+
+        loop {
+            for (index, lost) in events.get_mut(ply).map(std::mem::take).unwrap_or_default() {
+                if table.values[index] != DRAW {
+                    continue;
+                }
+
+                if !lost {
+                    remaining[index] -= 1;
+                }
+
+                if lost || remaining[index] == 0 {
+                    table.values[index] = ply as u8;
+                    decided.push(index);
+                }
+            }
+
+            if decided.is_empty() && events.len() <= ply + 1 {
+                break;
+            }
+
+            if ply + 1 >= DRAW as usize {
+                return Err("endgame is too long to store");
+            }
+
+            if events.len() <= ply + 1 {
+                events.resize(ply + 2, vec![]);
+            }
+
+            // positions decided at an even ply are lost for the side to move
+            for index in std::mem::take(&mut decided) {
+                for parent in table.parents(index) {
+                    events[ply + 1].push((parent, ply % 2 == 0));
+                }
+            }
+
+            ply += 1;
+        }
+
+        Ok(table)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn has_pawns(&self) -> bool {
+        self.pieces.iter().any(|(piece, _)| *piece == ChessPiece::Pawn)
+    }
+
+    fn region(&self) -> usize {
+        match self.has_pawns() {
+            true => 32,
+            false => TRIANGLE.len()
+        }
+    }
+
+    fn slots(&self) -> usize {
+        2 * self.region() * 64usize.pow(self.pieces.len() as u32 - 1)
+    }
+
+    // the white king's place in its region, if it is in there
+    fn region_index(&self, square: usize) -> Option<usize> {
+        match self.has_pawns() {
+            true => (square % 8 < 4).then_some(square / 8 * 4 + square % 8),
+            false => TRIANGLE.iter().position(|other| *other == square)
+        }
+    }
+
+    // the slot of a position, after turning it the way the table stores it.
+    // where several ways work (a king on the diagonal) the lowest slot wins
+    fn canonical(&self, squares: &[usize], side: ChessPieceColor) -> usize {
+        let symmetries = match self.has_pawns() {
+            true => 2,
+            false => 8
+        };
+
+        (0..symmetries)
+            .filter_map(|symmetry| {
+                let mut turned: Vec<usize> = squares.iter().map(|square| transform(*square, symmetry)).collect();
+                let king = self.region_index(turned[0])?;
+
+                // identical pieces are interchangeable, keep them in square order
+                let mut start = 0;
+                while start < turned.len() {
+                    let end = (start..turned.len())
+                        .find(|index| self.pieces[*index] != self.pieces[start])
+                        .unwrap_or(turned.len());
+                    turned[start..end].sort_unstable();
+                    start = end;
+                }
+
+                let rest = turned[1..].iter().rev().fold(0, |index, square| index * 64 + square);
+                Some(side.index() + 2 * (king + self.region() * rest))
+            })
+            .min()
+            .unwrap()
+    }
+
+    fn unindex(&self, index: usize) -> (Vec<usize>, ChessPieceColor) {
+        let side = match index % 2 {
+            0 => ChessPieceColor::White,
+            _ => ChessPieceColor::Black
+        };
+
+        let mut rest = index / 2 / self.region();
+        let king = match self.has_pawns() {
+            true => (index / 2 % 32) / 4 * 8 + (index / 2 % 32) % 4,
+            false => TRIANGLE[index / 2 % TRIANGLE.len()]
+        };
+
+        let mut squares = vec![king];
+        for _ in 1..self.pieces.len() {
+            squares.push(rest % 64);
+            rest /= 64;
+        }
+
+        (squares, side)
+    }
+
+    fn board(&self, squares: &[usize], side: ChessPieceColor) -> ChessBoard {
+        let mut board = ChessBoard::from_layout(ChessPieceColor::White, [0; 64]);
+
+        // marked as moved so nothing can castle
+        for (square, (piece, color)) in squares.iter().zip(&self.pieces) {
+            let tile = ChessTile::new(Some(*piece), Some(*color)).unwrap().moved();
+            board.place(&square_v2(*square), tile);
+        }
+
+        if side == ChessPieceColor::Black {
+            board.make_null_move();
+        }
+
+        board
+    }
+
+    // the position of a slot, unless it can't happen or is stored elsewhere
+    fn valid_board(&self, index: usize) -> Option<ChessBoard> {
+        let (squares, side) = self.unindex(index);
+
+        let overlaps = (1..squares.len()).any(|index| squares[..index].contains(&squares[index]));
+        let stranded_pawn = squares.iter().zip(&self.pieces)
+            .any(|(square, (piece, _))| *piece == ChessPiece::Pawn && (square / 8 == 0 || square / 8 == 7));
+
+        if overlaps || stranded_pawn || self.canonical(&squares, side) != index {
+            return None;
+        }
+
+        // the side that just moved can't have left its king in check
+        let board = self.board(&squares, side);
+        match board.in_check(side.opposite()) {
+            true => None,
+            false => Some(board)
+        }
+    }
+
+    // the squares of a board's pieces in table order, with the side to move.
+    // a board with the colors the other way round is flipped over first
+    fn squares_of(&self, board: &ChessBoard) -> Option<(Vec<usize>, ChessPieceColor)> {
+        let castles = [ChessPieceColor::White, ChessPieceColor::Black].iter()
+            .any(|color| board.castling_rights(*color) != (false, false));
+
+        if castles || !board.is_orthodox() {
+            return None;
+        }
+
+        let mut found = board_pieces(board);
+        let mut side = board.side_to_move();
+
+        let pieces: Pieces = found.iter().map(|(_, piece, color)| (*piece, *color)).collect();
+        if material_name(&pieces) != self.name {
+            found = found.into_iter()
+                .map(|(square, piece, color)| (square ^ 56, piece, color.opposite()))
+                .collect();
+            side = side.opposite();
+        }
+
+        let mut squares = vec![];
+        for piece in &self.pieces {
+            let at = found.iter().position(|(_, other, color)| (*other, *color) == *piece)?;
+            squares.push(found.remove(at).0);
+        }
+
+        match found.is_empty() {
+            true => Some((squares, side)),
+            false => None
+        }
+    }
+
+    fn index_of(&self, board: &ChessBoard) -> Option<usize> {
+        let (squares, side) = self.squares_of(board)?;
+        Some(self.canonical(&squares, side))
+    }
+
+    // the slots one move before this one, without captures or promotions.
+    // every piece of the side that just moved is walked back to where it
+    // could have come from
+    fn parents(&self, index: usize) -> Vec<usize> {
+        let (squares, side) = self.unindex(index);
+        let board = self.board(&squares, side);
+        let mover = side.opposite();
+        let mut parents = vec![];
+
+        for (at, (piece, color)) in self.pieces.iter().enumerate() {
+            if *color != mover {
+                continue;
+            }
+
+            let square = squares[at];
+            let empty = |square: usize| !squares.contains(&square);
+
+            // every other piece moves the same way backwards as forwards
+            let sources: Vec<usize> = match piece {
+                ChessPiece::Pawn => {
+                    let back = |steps: i32| match mover {
+                        ChessPieceColor::White => square as i32 - 8 * steps,
+                        ChessPieceColor::Black => square as i32 + 8 * steps
+                    } as usize;
+
+                    let start = match mover {
+                        ChessPieceColor::White => 1,
+                        ChessPieceColor::Black => 6
+                    };
+
+                    let mut sources = vec![];
+                    if (1..7).contains(&(back(1) / 8)) && empty(back(1)) {
+                        sources.push(back(1));
+
+                        if back(2) / 8 == start && empty(back(2)) {
+                            sources.push(back(2));
+                        }
+                    }
+
+                    sources
+                },
+                _ => ChessMove::destinations(&square_v2(square), board).iter()
+                    .map(|v2| board.square_index(v2))
+                    .filter(|square| empty(*square))
+                    .collect()
+            };
+
+            for source in sources {
+                let mut before = squares.clone();
+                before[at] = source;
+
+                let parent = self.canonical(&before, mover);
+                if self.values[parent] != INVALID {
+                    parents.push(parent);
+                }
+            }
+        }
+
+        parents.sort_unstable();
+        parents.dedup();
+        parents
+    }
+
+    // none for a board of some other material, or one that can still castle
+    pub fn probe(&self, board: &ChessBoard) -> Option<Dtm> {
+        Dtm::from_value(self.values[self.index_of(board)?])
+    }
+
+    // the most plies any win in the table takes
+    pub fn longest_mate(&self) -> u8 {
+        self.values.iter()
+            .filter(|value| **value % 2 == 1 && **value < DRAW)
+            .max()
+            .copied()
+            .unwrap_or(0)
+    }
+
+    // a magic number, the material name, then a byte per slot
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(self.name.len() as u8);
+        bytes.extend(self.name.as_bytes());
+        bytes.extend(&self.values);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        if bytes.len() < 5 || bytes[0..4] != MAGIC {
+            return Err("not an endgame table");
+        }
+
+        let end = 5 + bytes[4] as usize;
+        let name = bytes.get(5..end)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or("not an endgame table")?;
+
+        let pieces = parse_material(name)?;
+
+        let table = EndgameTable {
+            name: material_name(&pieces),
+            pieces,
+            values: bytes[end..].to_vec()
+        };
+
+        match table.values.len() == table.slots() {
+            true => Ok(table),
+            false => Err("endgame table is the wrong size")
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), &'static str> {
+        fs::write(path, self.to_bytes()).map_err(|_| "could not write endgame table")
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let bytes = fs::read(path).map_err(|_| "could not read endgame table")?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+#[allow(dead_code)]
+impl EndgameTables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    // the table for a material name, either way round
+    pub fn table(&self, name: &str) -> Option<&EndgameTable> {
+        let pieces = parse_material(name).ok()?;
+        let flipped: Pieces = pieces.iter().map(|(piece, color)| (*piece, color.opposite())).collect();
+        let names = [material_name(&pieces), material_name(&flipped)];
+
+        self.tables.iter().find(|table| names.contains(&table.name))
+    }
+
+    pub fn add(&mut self, table: EndgameTable) {
+        if self.table(&table.name).is_none() {
+            self.tables.push(table);
+        }
+    }
+
+    // builds the table and, first, any smaller ones it needs that are
+    // missing. four piece tables take a while, save them once they're made
+    pub fn generate(&mut self, name: &str) -> Result<(), &'static str> {
+        let pieces = parse_material(name)?;
+
+        if insufficient(&pieces) || self.table(name).is_some() {
+            return Ok(());
+        }
+
+        for smaller in smaller_materials(&pieces) {
+            self.generate(&smaller)?;
+        }
+
+        let table = EndgameTable::generate(name, self)?;
+        self.tables.push(table);
+        Ok(())
+    }
+
+    pub fn probe(&self, board: &ChessBoard) -> Option<Dtm> {
+        if !board.is_orthodox() {
+            return None;
+        }
+
+        let pieces: Pieces = board_pieces(board).iter().map(|(_, piece, color)| (*piece, *color)).collect();
+
+        match insufficient(&pieces) {
+            true => Some(Dtm::Draw),
+            false => self.table(&material_name(&pieces))?.probe(board)
+        }
+    }
+
+    // the move that mates soonest, or failing that holds the draw, or
+    // failing that puts mate off longest. moves into material without a
+    // table are passed over
+    pub fn best_move(&self, board: &ChessBoard) -> Option<ChessMove> {
+        self.probe(board)?;
+
+        board.legal_moves().into_iter()
+            .filter_map(|chess_move| {
+                let mut child = *board;
+                child.make_move(&chess_move);
+                self.probe(&child).map(|dtm| (chess_move, dtm.rank_for_mover()))
+            })
+            .rev() // max_by_key keeps the last of equals, prefer the first
+            .max_by_key(|(_, rank)| *rank)
+            .map(|(chess_move, _)| chess_move)
+    }
+
+    // every table as <name>.etb in the directory
+    pub fn save<P: AsRef<Path>>(&self, directory: P) -> Result<(), &'static str> {
+        for table in &self.tables {
+            table.save(directory.as_ref().join(format!("{}.{}", table.name, EXTENSION)))?;
+        }
+
+        Ok(())
+    }
+
+    // returns how many new tables were found. files that don't load are skipped
+    pub fn add_directory<P: AsRef<Path>>(&mut self, directory: P) -> usize {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(_) => return 0
+        };
+
+        let before = self.len();
+
+        for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+            if path.extension().and_then(|extension| extension.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+
+            if let Ok(table) = EndgameTable::load(&path) {
+                self.add(table);
+            }
+        }
+
+        self.len() - before
+    }
+}
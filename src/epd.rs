@@ -0,0 +1,264 @@
+use std::fs;
+use std::path::Path;
+
+use crate::board::ChessBoard;
+use crate::moves::ChessMove;
+use crate::pieces::ChessPieceColor;
+use crate::search::{Searcher, SearchConfig};
+use crate::time::TimeManager;
+use crate::ordering::MAX_PLY;
+use crate::selfplay::MoveLimit;
+
+// extended position description. the four board fields of a fen followed
+// by operations, each an opcode and its operands ending in a semicolon:
+//
+//   r1b1k2r/ppp2ppp/2n5/8/1b1Pq3/2N5/PP2BPPP/R2QK1NR b KQkq - bm Bxc3+; id "WAC.010";
+//
+// test suites give the best moves (`bm`) or the moves to avoid (`am`) in
+// san, and a position counts as solved when the search picks one of the
+// former and none of the latter
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpdRecord {
+    pub board: ChessBoard,
+    operations: Vec<(String, Vec<String>)>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SuiteResult {
+    pub id: String,
+    pub chosen: Option<ChessMove>,
+    pub score: i32,
+    pub depth: u8,
+    pub solved: bool
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SuiteReport {
+    pub results: Vec<SuiteResult>,
+    pub skipped: usize // positions that aren't testable
+}
+
+// operands are split on spaces, except inside double quotes
+fn split_operands(text: &str) -> Vec<String> {
+    let mut operands = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in text.chars() {
+        match (c, quoted) {
+            ('"', _) => {
+                if quoted {
+                    operands.push(current.clone());
+                    current.clear();
+                }
+                quoted = !quoted;
+            },
+            (c, false) if c.is_whitespace() => if !current.is_empty() {
+                operands.push(current.clone());
+                current.clear();
+            },
+            (c, _) => current.push(c)
+        }
+    }
+
+    if !current.is_empty() {
+        operands.push(current);
+    }
+
+    operands
+}
+
+// semicolons end an operation unless they're inside a string
+fn split_operations(text: &str) -> Vec<&str> {
+    let mut operations = vec![];
+    let mut start = 0;
+    let mut quoted = false;
+
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                operations.push(&text[start..index]);
+                start = index + 1;
+            },
+            _ => ()
+        }
+    }
+
+    operations.push(&text[start..]);
+    operations.into_iter()
+        .map(str::trim)
+        .filter(|operation| !operation.is_empty())
+        .collect()
+}
+
+#[allow(dead_code)]
+impl EpdRecord {
+    pub fn parse(line: &str) -> Result<Self, &'static str> {
+        // the first four fields, however much space is between them, and
+        // the rest is operations
+        let mut rest = line.trim();
+        let mut board_fields = vec![];
+        while board_fields.len() < 4 && !rest.is_empty() {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            board_fields.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+
+        if board_fields.len() < 4 {
+            return Err("epd needs four board fields");
+        }
+
+        let board = ChessBoard::from_fen(ChessPieceColor::White, &board_fields.join(" "))?;
+
+        let operations = split_operations(rest)
+            .into_iter()
+            .map(|operation| {
+                let operands = split_operands(operation);
+                let (opcode, operands) = operands.split_first().ok_or("epd operation needs an opcode")?;
+                Ok((opcode.clone(), operands.to_vec()))
+            })
+            .collect::<Result<_, &'static str>>()?;
+
+        Ok(EpdRecord { board, operations })
+    }
+
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    pub fn set_operation(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(name, _)| name == opcode) {
+            Some((_, old)) => *old = operands,
+            None => self.operations.push((opcode.to_string(), operands))
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id")?.first().map(String::as_str)
+    }
+
+    // the operands as legal moves, in san or as uci moves. none if the
+    // opcode is missing or any of its moves can't be played
+    fn moves(&self, opcode: &str) -> Option<Vec<ChessMove>> {
+        self.operation(opcode)?.iter()
+            .map(|text| self.board.parse_san(text).or_else(|| self.board.parse_uci_move(text)))
+            .collect()
+    }
+
+    pub fn best_moves(&self) -> Option<Vec<ChessMove>> {
+        self.moves("bm")
+    }
+
+    pub fn avoid_moves(&self) -> Option<Vec<ChessMove>> {
+        self.moves("am")
+    }
+
+    // has a bm or an am, all of whose moves are legal here
+    pub fn is_testable(&self) -> bool {
+        let best = self.operation("bm").map(|_| self.best_moves().is_some());
+        let avoid = self.operation("am").map(|_| self.avoid_moves().is_some());
+
+        match (best, avoid) {
+            (None, None) => false,
+            (best, avoid) => best.unwrap_or(true) && avoid.unwrap_or(true)
+        }
+    }
+
+    // one of the best moves if there are any, and none of those to avoid
+    pub fn solved_by(&self, chess_move: &ChessMove) -> bool {
+        let best = self.best_moves().is_none_or(|moves| moves.contains(chess_move));
+        let avoid = self.avoid_moves().is_none_or(|moves| !moves.contains(chess_move));
+
+        self.is_testable() && best && avoid
+    }
+
+    pub fn to_epd(&self) -> String {
+        let fen = self.board.to_fen();
+        let mut text: String = fen.split(' ').take(4).collect::<Vec<_>>().join(" ");
+
+        for (opcode, operands) in &self.operations {
+            text.push(' ');
+            text.push_str(opcode);
+
+            for operand in operands {
+                match operand.contains(char::is_whitespace) || opcode == "id" || opcode == "c0" {
+                    true => text.push_str(&format!(" \"{}\"", operand)),
+                    false => text.push_str(&format!(" {}", operand))
+                }
+            }
+
+            text.push(';');
+        }
+
+        text
+    }
+}
+
+// lines that don't parse are skipped, as are blank lines and # comments
+pub fn parse_suite(text: &str) -> Vec<EpdRecord> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| EpdRecord::parse(line).ok())
+        .collect()
+}
+
+pub fn load_suite<P: AsRef<Path>>(path: P) -> Result<Vec<EpdRecord>, &'static str> {
+    let text = fs::read_to_string(path).map_err(|_| "could not read test suite")?;
+    Ok(parse_suite(&text))
+}
+
+#[allow(dead_code)]
+impl SuiteReport {
+    pub fn solved(&self) -> usize {
+        self.results.iter().filter(|result| result.solved).count()
+    }
+
+    pub fn total(&self) -> usize {
+        self.results.len()
+    }
+}
+
+// searches every position with a fresh table, calling back after each one.
+// a clock limit gives every position the time a game on that clock would
+pub fn run_suite<F: FnMut(&EpdRecord, &SuiteResult)>(
+    records: &[EpdRecord],
+    config: SearchConfig,
+    limit: MoveLimit,
+    mut on_position: F
+) -> SuiteReport {
+    let mut searcher = Searcher::new(config);
+    let mut report = SuiteReport::default();
+
+    for (index, record) in records.iter().enumerate() {
+        if !record.is_testable() {
+            report.skipped += 1;
+            continue;
+        }
+
+        searcher.clear();
+
+        let search = match limit {
+            MoveLimit::Depth(depth) => searcher.search(&record.board, depth),
+            MoveLimit::MoveTime(time) => searcher.search_timed(&record.board, MAX_PLY as u8 / 2, TimeManager::fixed(time)),
+            MoveLimit::Clock(control) => searcher.search_timed(&record.board, MAX_PLY as u8 / 2, TimeManager::new(&control))
+        };
+
+        let result = SuiteResult {
+            id: record.id().map_or_else(|| (index + 1).to_string(), str::to_string),
+            chosen: search.best_move,
+            score: search.score,
+            depth: search.depth,
+            solved: search.best_move.is_some_and(|chess_move| record.solved_by(&chess_move))
+        };
+
+        on_position(record, &result);
+        report.results.push(result);
+    }
+
+    report
+}
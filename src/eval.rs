@@ -0,0 +1,284 @@
+use std::fs;
+use std::path::Path;
+
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::board::ChessBoard;
+use crate::math::V2;
+
+// piece-square tables, written from the point of view of the side moving up
+// the board: the first row is the far edge and the last row is the home rank
+
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0
+];
+
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50
+];
+
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20
+];
+
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0
+];
+
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20
+];
+
+const KING_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20
+];
+
+// the order parameters are kept in, here and in parameter files
+const PIECES: [(ChessPiece, &str); 6] = [
+    (ChessPiece::Pawn, "pawn"), (ChessPiece::Knight, "knight"), (ChessPiece::Bishop, "bishop"),
+    (ChessPiece::Rook, "rook"), (ChessPiece::Queen, "queen"), (ChessPiece::King, "king")
+];
+
+// everything the evaluation adds up, so it can be tuned and read back from a
+// file. the defaults are the piece values and the tables above
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalParams {
+    pub values: [i32; 6], // in PIECES order
+    pub tables: [[i32; 64]; 6]
+}
+
+pub const DEFAULT_PARAMS: EvalParams = EvalParams {
+    values: [
+        ChessPiece::Pawn.value(), ChessPiece::Knight.value(), ChessPiece::Bishop.value(),
+        ChessPiece::Rook.value(), ChessPiece::Queen.value(), ChessPiece::King.value()
+    ],
+    tables: [PAWN_TABLE, KNIGHT_TABLE, BISHOP_TABLE, ROOK_TABLE, QUEEN_TABLE, KING_TABLE]
+};
+
+// the fairy pieces borrow the table of the piece they're most like
+fn piece_index(piece: ChessPiece) -> usize {
+    let piece = match piece {
+        ChessPiece::Archbishop => ChessPiece::Knight,
+        ChessPiece::Chancellor => ChessPiece::Rook,
+        ChessPiece::Amazon => ChessPiece::Queen,
+        piece => piece
+    };
+
+    PIECES.iter().position(|(other, _)| *other == piece).unwrap()
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        DEFAULT_PARAMS
+    }
+}
+
+#[allow(dead_code)]
+impl EvalParams {
+    // the values first, then the tables one after another
+    pub fn to_vec(&self) -> Vec<i32> {
+        self.values.iter()
+            .chain(self.tables.iter().flatten())
+            .copied()
+            .collect()
+    }
+
+    pub fn from_slice(weights: &[i32]) -> Result<Self, &'static str> {
+        if weights.len() != 6 + 6 * 64 {
+            return Err("wrong number of evaluation parameters");
+        }
+
+        let mut params = DEFAULT_PARAMS;
+        params.values.copy_from_slice(&weights[..6]);
+
+        for (table, weights) in params.tables.iter_mut().zip(weights[6..].chunks_exact(64)) {
+            table.copy_from_slice(weights);
+        }
+
+        Ok(params)
+    }
+
+    // a line of piece values, then a line per table, each led by its name.
+    // lines starting with # are comments
+    pub fn to_text(&self) -> String {
+        let line = |name: &str, numbers: &[i32]| {
+            let numbers: Vec<String> = numbers.iter().map(|number| number.to_string()).collect();
+            format!("{} {}\n", name, numbers.join(" "))
+        };
+
+        let mut text = "# evaluation parameters, pawn to king\n".to_string();
+        text.push_str(&line("values", &self.values));
+
+        for ((_, name), table) in PIECES.iter().zip(&self.tables) {
+            text.push_str(&line(name, table));
+        }
+
+        text
+    }
+
+    // lines left out keep their defaults
+    pub fn from_text(text: &str) -> Result<Self, &'static str> {
+        let mut params = DEFAULT_PARAMS;
+
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let mut words = line.split_whitespace();
+            let name = words.next().unwrap();
+            let numbers = words
+                .map(|word| word.parse::<i32>())
+                .collect::<Result<Vec<i32>, _>>()
+                .map_err(|_| "not a number in evaluation parameters")?;
+
+            let target: &mut [i32] = match PIECES.iter().position(|(_, other)| *other == name) {
+                Some(index) => &mut params.tables[index],
+                None if name == "values" => &mut params.values,
+                None => return Err("unknown evaluation parameter")
+            };
+
+            if numbers.len() != target.len() {
+                return Err("wrong number of evaluation parameters");
+            }
+
+            target.copy_from_slice(&numbers);
+        }
+
+        Ok(params)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), &'static str> {
+        fs::write(path, self.to_text()).map_err(|_| "could not write evaluation parameters")
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let text = fs::read_to_string(path).map_err(|_| "could not read evaluation parameters")?;
+        Self::from_text(&text)
+    }
+}
+
+#[allow(dead_code)]
+impl ChessBoard {
+    // material and piece placement in centipawns, from the point of view of
+    // the side to move
+    pub fn evaluate(&self) -> i32 {
+        self.evaluate_with(&DEFAULT_PARAMS)
+    }
+
+    // the table entry for a piece on src, boards of other sizes are
+    // stretched onto the tables' 8x8
+    fn table_square(&self, src: &V2, color: ChessPieceColor) -> usize {
+        // flip the table for pieces moving down the board
+        let row = match self.forward(color) {
+            -1 => src.y,
+            _ => self.ranks() - 1 - src.y
+        };
+
+        (src.x * 8 / self.files() + 8 * (row * 8 / self.ranks())) as usize
+    }
+
+    pub fn evaluate_with(&self, params: &EvalParams) -> i32 {
+        let side = self.side_to_move();
+        let mut score = 0;
+
+        for src in self.squares() {
+            let tile = self.tile(&src);
+
+            let (piece, color) = match (tile.piece(), tile.color()) {
+                (Some(piece), Some(color)) => (piece, color),
+                _ => continue
+            };
+
+            let value = match piece.is_standard() {
+                true => params.values[piece_index(piece)],
+                false => piece.value()
+            };
+            let value = value + params.tables[piece_index(piece)][self.table_square(&src, color)];
+
+            match color == side {
+                true => score += value,
+                false => score -= value
+            }
+        }
+
+        score + self.variant_eval()
+    }
+
+    // the same sum written out for tuning: which parameters (indexed like
+    // to_vec) count for white (+1) and which for black (-1)
+    pub fn eval_terms(&self) -> Vec<(usize, i32)> {
+        let mut terms = vec![];
+
+        for src in self.squares() {
+            let tile = self.tile(&src);
+
+            let (piece, color) = match (tile.piece(), tile.color()) {
+                (Some(piece), Some(color)) => (piece, color),
+                _ => continue
+            };
+
+            let sign = match color {
+                ChessPieceColor::White => 1,
+                ChessPieceColor::Black => -1
+            };
+
+            // a fairy piece's value isn't a parameter, only its table is
+            let index = piece_index(piece);
+            if piece.is_standard() {
+                terms.push((index, sign));
+            }
+            terms.push((6 + 64 * index + self.table_square(&src, color), sign));
+        }
+
+        terms
+    }
+
+    // material of everything but pawns and the king
+    pub fn non_pawn_material(&self, color: ChessPieceColor) -> i32 {
+        self.squares()
+            .map(|v2| self.tile(&v2))
+            .filter(|tile| tile.color() == Some(color))
+            .filter_map(|tile| tile.piece())
+            .filter(|piece| *piece != ChessPiece::Pawn && *piece != ChessPiece::King)
+            .map(|piece| piece.value())
+            .sum()
+    }
+}
@@ -2,11 +2,53 @@ pub mod pieces;
 pub mod board;
 pub mod moves;
 pub mod math;
+pub mod attacks;
+pub mod see;
+pub mod eval;
+pub mod search;
+pub mod zobrist;
+pub mod tt;
+pub mod ordering;
+pub mod time;
+pub mod notation;
+pub mod uci;
+pub mod book;
+pub mod syzygy;
+pub mod endgame;
+pub mod nnue;
+pub mod tuning;
+pub mod pgn;
+pub mod selfplay;
+pub mod epd;
+pub mod render;
+pub mod svg;
+pub mod variant;
+pub mod crazyhouse;
+pub mod packed;
+pub mod database;
+#[cfg(feature = "serde")]
+pub mod serialize;
 
 pub use pieces::{ChessPiece, ChessPieceColor};
 pub use moves::{ChessMove, ChessMoveExt};
 pub use board::{ChessTile, ChessBoard};
 pub use math::V2;
+pub use search::{Searcher, SearchConfig, SearchResult, PvLine, Evaluator};
+pub use time::{TimeControl, TimeManager};
+pub use uci::UciEngine;
+pub use book::{OpeningBook, BookEntry, BookSelection};
+pub use syzygy::{Tablebase, Wdl};
+pub use endgame::{EndgameTable, EndgameTables, Dtm};
+pub use nnue::{Network, Accumulator};
+pub use eval::EvalParams;
+pub use tuning::{Tuner, LabelledPosition};
+pub use pgn::{PgnGame, GameResult};
+pub use render::{RenderOptions, Glyphs};
+pub use svg::{SvgOptions, SvgTheme};
+pub use epd::{EpdRecord, SuiteReport, SuiteResult};
+pub use selfplay::{Engine, MatchRunner, MatchStats, MoveLimit, Adjudication, Sprt, SprtDecision, GameRecord};
+pub use variant::Variant;
+pub use database::{GameDatabase, GameQuery, MoveStats};
 
 #[cfg(test)]
 mod tests {
@@ -16,6 +58,31 @@ mod tests {
     use moves::{ChessMove, ChessMoveExt};
     use board::{ChessTile, ChessBoard};
     use math::V2;
+    use search::{Searcher, SearchConfig, Evaluator, MATE};
+    use ordering::{MoveOrdering, MovePicker, mvv_lva};
+    use time::{TimeControl, TimeManager};
+    use uci::UciEngine;
+    use book::{OpeningBook, BookEntry, BookSelection};
+    use syzygy::{Tablebase, Wdl};
+    use endgame::{EndgameTable, EndgameTables, Dtm};
+    use nnue::{Network, FEATURES};
+    use eval::EvalParams;
+    use tuning::{Tuner, parse_labelled};
+    use notation::START_FEN;
+    use packed::PACKED_SIZE;
+    use database::{GameDatabase, GameQuery, MoveStats, material_signature};
+    use render::{RenderOptions, Glyphs};
+    use svg::{SvgOptions, SvgTheme};
+    use pgn::{PgnGame, GameResult, parse_games};
+    use epd::{EpdRecord, parse_suite, run_suite};
+    use selfplay::{Engine, MatchRunner, MatchStats, MoveLimit, Adjudication, Sprt, SprtDecision};
+    use variant::{Variant, HORDE_FEN, CAPABLANCA_FEN};
+    use crazyhouse::POCKET_PIECES;
+
+    use std::fs;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
 
     const TEST_LAYOUT: [u8; 64] = [
         2, 0, 0, 0, 0, 0, 1, 1,
@@ -28,6 +95,36 @@ mod tests {
         2, 3, 4, 5, 6, 4, 3, 2
     ];
 
+    fn tile(piece: ChessPiece, color: ChessPieceColor) -> ChessTile {
+        ChessTile::new(Some(piece), Some(color)).expect("could not create tile")
+    }
+
+    // an empty board, white at the bottom, with the pieces at (x, y)
+    fn position(pieces: &[(u16, u16, ChessPiece, ChessPieceColor)]) -> ChessBoard {
+        let mut chess_board = ChessBoard::from_layout(ChessPieceColor::White, [0; 64]);
+        for (x, y, piece, color) in pieces {
+            chess_board.place(&V2 { x: *x, y: *y }, tile(*piece, *color));
+        }
+        chess_board
+    }
+
+    // white: king f6, queen g2. black: king h8
+    fn queen_mate_in_one() -> ChessBoard {
+        position(&[
+            (5, 2, ChessPiece::King, ChessPieceColor::White),
+            (6, 6, ChessPiece::Queen, ChessPieceColor::White),
+            (7, 0, ChessPiece::King, ChessPieceColor::Black)
+        ])
+    }
+
+    // made up but repeatable numbers
+    fn xorshift(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
     #[test]
     fn chess_move_test() {
         let src = V2 {x: 0, y: 1}; // move enemy pawn
@@ -260,4 +357,2032 @@ mod tests {
 
         assert_eq!(chess_board.take_turn(&src, &dst), false);
     }
+
+    #[test]
+    fn chess_board_attackers_of() {
+        let chess_board = ChessBoard::new(ChessPieceColor::White);
+
+        // f3 is covered by the e and g pawns and the g knight
+        let attackers = chess_board.attackers_of(&V2 { x: 5, y: 5 }, ChessPieceColor::White);
+
+        assert_eq!(attackers.len(), 3);
+        assert!(attackers.contains(&V2 { x: 4, y: 6 }));
+        assert!(attackers.contains(&V2 { x: 6, y: 6 }));
+        assert!(attackers.contains(&V2 { x: 6, y: 7 }));
+
+        assert!(chess_board.attackers_of(&V2 { x: 5, y: 5 }, ChessPieceColor::Black).is_empty());
+    }
+
+    #[test]
+    fn chess_board_attack_and_defence_maps() {
+        let chess_board = ChessBoard::new(ChessPieceColor::White);
+
+        let attacks = chess_board.attack_map(ChessPieceColor::White);
+        let defence = chess_board.defence_map(ChessPieceColor::White);
+
+        // 22 tiles attacked by white in the opening, counted once per attacker
+        assert_eq!(attacks.iter().filter(|count| **count > 0).count(), 22);
+
+        // the d1 queen is defended by the king only and the a1 rook by nothing
+        assert_eq!(defence[3 + 8 * 7], 1);
+        assert_eq!(defence[8 * 7], 0);
+
+        // enemy tiles never show up as defended
+        assert!(defence[..16].iter().all(|count| *count == 0));
+    }
+
+    #[test]
+    fn chess_board_hanging_pieces() {
+        let mut chess_board = ChessBoard::new(ChessPieceColor::White);
+
+        // a black knight on e3 is attacked by the d2 and f2 pawns
+        let knight = ChessTile::new(Some(ChessPiece::Knight), Some(ChessPieceColor::Black))
+            .expect("could not create tile");
+        chess_board.place(&V2 { x: 4, y: 5 }, knight);
+
+        assert_eq!(chess_board.hanging_pieces(ChessPieceColor::Black), vec![V2 { x: 4, y: 5 }]);
+
+        // and it forks the queen, while the f1 bishop is an even trade
+        assert_eq!(chess_board.hanging_pieces(ChessPieceColor::White), vec![V2 { x: 3, y: 7 }]);
+    }
+
+    #[test]
+    fn chess_board_xray_attackers() {
+        let chess_board = ChessBoard::new(ChessPieceColor::White);
+
+        // the d1 queen looks through the d2 pawn at d4
+        let xrays = chess_board.xray_attackers_of(&V2 { x: 3, y: 4 }, ChessPieceColor::White);
+
+        assert_eq!(xrays, vec![V2 { x: 3, y: 7 }]);
+    }
+
+    #[test]
+    fn chess_board_see_winning_capture() {
+        let mut chess_board = ChessBoard::new(ChessPieceColor::White);
+
+        let knight = ChessTile::new(Some(ChessPiece::Knight), Some(ChessPieceColor::Black))
+            .expect("could not create tile");
+        chess_board.place(&V2 { x: 4, y: 5 }, knight);
+
+        // dxe3 wins the knight outright
+        let capture = ChessMove::new(&V2 { x: 3, y: 6 }, &V2 { x: 4, y: 5 }, chess_board)
+            .expect("capture should be legal");
+
+        assert_eq!(chess_board.see(&capture), 320);
+    }
+
+    #[test]
+    fn chess_board_see_losing_capture() {
+        let mut chess_board = ChessBoard::new(ChessPieceColor::White);
+
+        let pawn = ChessTile::new(Some(ChessPiece::Pawn), Some(ChessPieceColor::Black))
+            .expect("could not create tile");
+        let rook = ChessTile::new(Some(ChessPiece::Rook), Some(ChessPieceColor::White))
+            .expect("could not create tile");
+
+        // a pawn on d5 guarded from c6, attacked by a rook on d3
+        chess_board.place(&V2 { x: 3, y: 3 }, pawn);
+        chess_board.place(&V2 { x: 2, y: 2 }, pawn);
+        chess_board.place(&V2 { x: 3, y: 5 }, rook);
+
+        let capture = ChessMove::new(&V2 { x: 3, y: 5 }, &V2 { x: 3, y: 3 }, chess_board)
+            .expect("capture should be legal");
+
+        assert_eq!(chess_board.see(&capture), -400);
+
+        // a second rook on d2 x-rays through and takes the pawn back
+        chess_board.place(&V2 { x: 3, y: 6 }, rook);
+        assert_eq!(chess_board.see(&capture), -300);
+    }
+
+    #[test]
+    fn chess_board_perft_start_position() {
+        let chess_board = ChessBoard::new(ChessPieceColor::White);
+
+        assert_eq!(chess_board.perft(1), 20);
+        assert_eq!(chess_board.perft(2), 400);
+        assert_eq!(chess_board.perft(3), 8902);
+    }
+
+    #[test]
+    fn chess_board_promotion_and_en_passant() {
+        let mut chess_board = ChessBoard::from_layout(ChessPieceColor::White, [0; 64]);
+
+        chess_board.place(&V2 { x: 4, y: 7 }, tile(ChessPiece::King, ChessPieceColor::White));
+        chess_board.place(&V2 { x: 4, y: 0 }, tile(ChessPiece::King, ChessPieceColor::Black));
+        chess_board.place(&V2 { x: 0, y: 1 }, tile(ChessPiece::Pawn, ChessPieceColor::White));
+
+        // a7-a8 comes in all four flavours
+        let promotions = chess_board.legal_moves().into_iter()
+            .filter(|chess_move| chess_move.promotion().is_some())
+            .count();
+        assert_eq!(promotions, 4);
+
+        chess_board.place(&V2 { x: 3, y: 6 }, tile(ChessPiece::Pawn, ChessPieceColor::White));
+        chess_board.place(&V2 { x: 4, y: 4 }, tile(ChessPiece::Pawn, ChessPieceColor::Black));
+
+        // d2-d4 lets the e4 pawn take on d3
+        assert!(chess_board.take_turn(&V2 { x: 3, y: 6 }, &V2 { x: 3, y: 4 }));
+        assert!(chess_board.take_turn(&V2 { x: 4, y: 4 }, &V2 { x: 3, y: 5 }));
+
+        assert_eq!(chess_board.last_turn().expect("no last move").ext(), ChessMoveExt::EpCapture);
+        assert_eq!(chess_board.tile(&V2 { x: 3, y: 4 }).piece(), None);
+    }
+
+    #[test]
+    fn search_finds_back_rank_mate() {
+        let mut chess_board = ChessBoard::from_layout(ChessPieceColor::White, [0; 64]);
+
+        chess_board.place(&V2 { x: 6, y: 0 }, tile(ChessPiece::King, ChessPieceColor::Black));
+        for x in 5..8 {
+            chess_board.place(&V2 { x, y: 1 }, tile(ChessPiece::Pawn, ChessPieceColor::Black));
+        }
+        chess_board.place(&V2 { x: 6, y: 7 }, tile(ChessPiece::King, ChessPieceColor::White));
+        chess_board.place(&V2 { x: 0, y: 7 }, tile(ChessPiece::Rook, ChessPieceColor::White));
+
+        let result = Searcher::new(SearchConfig::default()).search(&chess_board, 2);
+        let best_move = result.best_move.expect("no move found");
+
+        assert_eq!(best_move.dst(), V2 { x: 0, y: 0 });
+        assert_eq!(result.score, MATE - 1);
+    }
+
+    #[test]
+    fn search_quiescence_avoids_defended_pawns() {
+        let mut chess_board = ChessBoard::new(ChessPieceColor::White);
+
+        // a queen on d4 eyeing pawns on a7, d5 and g7, all of them defended
+        chess_board.clear(&V2 { x: 3, y: 7 });
+        chess_board.place(&V2 { x: 3, y: 4 }, tile(ChessPiece::Queen, ChessPieceColor::White));
+        chess_board.place(&V2 { x: 3, y: 3 }, tile(ChessPiece::Pawn, ChessPieceColor::Black));
+        chess_board.place(&V2 { x: 2, y: 2 }, tile(ChessPiece::Pawn, ChessPieceColor::Black));
+
+        let horizon = SearchConfig { quiescence: false, ..SearchConfig::default() };
+        let greedy = Searcher::new(horizon).search(&chess_board, 1);
+        assert!(greedy.best_move.expect("no move found").is_capture());
+
+        let careful = Searcher::new(SearchConfig::default()).search(&chess_board, 1);
+        assert!(!careful.best_move.expect("no move found").is_capture());
+    }
+
+    fn move_ordering_board() -> ChessBoard {
+        let mut chess_board = ChessBoard::from_layout(ChessPieceColor::White, [0; 64]);
+
+        chess_board.place(&V2 { x: 4, y: 7 }, tile(ChessPiece::King, ChessPieceColor::White));
+        chess_board.place(&V2 { x: 7, y: 0 }, tile(ChessPiece::King, ChessPieceColor::Black));
+
+        // a pawn about to promote, a knight that can take a loose rook and a
+        // queen that can take a defended pawn
+        chess_board.place(&V2 { x: 0, y: 1 }, tile(ChessPiece::Pawn, ChessPieceColor::White));
+        chess_board.place(&V2 { x: 2, y: 5 }, tile(ChessPiece::Knight, ChessPieceColor::White));
+        chess_board.place(&V2 { x: 3, y: 3 }, tile(ChessPiece::Rook, ChessPieceColor::Black));
+        chess_board.place(&V2 { x: 6, y: 4 }, tile(ChessPiece::Queen, ChessPieceColor::White));
+        chess_board.place(&V2 { x: 6, y: 2 }, tile(ChessPiece::Pawn, ChessPieceColor::Black));
+        chess_board.place(&V2 { x: 7, y: 1 }, tile(ChessPiece::Pawn, ChessPieceColor::Black));
+
+        chess_board
+    }
+
+    #[test]
+    fn move_picker_order() {
+        let chess_board = move_ordering_board();
+        let ordering = MoveOrdering::new();
+
+        let table_move = ChessMove::raw(&V2 { x: 4, y: 7 }, &V2 { x: 5, y: 7 }, ChessMoveExt::Quiet as u16);
+        let moves: Vec<ChessMove> = MovePicker::new(&chess_board, chess_board.legal_moves(), &ordering, Some(table_move), 0)
+            .collect();
+
+        assert_eq!(moves.len(), chess_board.legal_moves().len());
+        assert_eq!(moves[0], table_move);
+
+        // knight takes rook, then the queen promotion
+        assert_eq!(moves[1].dst(), V2 { x: 3, y: 3 });
+        assert_eq!(moves[2].promotion(), Some(ChessPiece::Queen));
+
+        // the losing queen capture and the underpromotions come last
+        let last: Vec<ChessMoveExt> = moves[moves.len() - 4..].iter()
+            .map(|chess_move| chess_move.ext())
+            .collect();
+
+        assert_eq!(last, vec![
+            ChessMoveExt::Captures,
+            ChessMoveExt::RookPromotion,
+            ChessMoveExt::BishopPromotion,
+            ChessMoveExt::KnightPromotion
+        ]);
+    }
+
+    #[test]
+    fn move_ordering_killers_and_history() {
+        let chess_board = move_ordering_board();
+        let mut ordering = MoveOrdering::new();
+
+        let quiet = ChessMove::raw(&V2 { x: 2, y: 5 }, &V2 { x: 1, y: 7 }, ChessMoveExt::Quiet as u16);
+        ordering.record_cutoff(&quiet, 3, 4);
+
+        assert_eq!(ordering.killers(3), [Some(quiet), None]);
+        assert_eq!(ordering.killers(2), [None, None]);
+        assert_eq!(ordering.history(&quiet), 16);
+
+        // the killer is the first quiet move tried at its own ply
+        let moves: Vec<ChessMove> = MovePicker::new(&chess_board, chess_board.legal_moves(), &ordering, None, 3)
+            .collect();
+        assert_eq!(moves[2], quiet);
+    }
+
+    #[test]
+    fn move_ordering_mvv_lva() {
+        let chess_board = move_ordering_board();
+
+        let knight_takes_rook = ChessMove::raw(&V2 { x: 2, y: 5 }, &V2 { x: 3, y: 3 }, ChessMoveExt::Captures as u16);
+        let queen_takes_pawn = ChessMove::raw(&V2 { x: 6, y: 4 }, &V2 { x: 6, y: 2 }, ChessMoveExt::Captures as u16);
+
+        assert!(mvv_lva(&chess_board, &knight_takes_rook) > mvv_lva(&chess_board, &queen_takes_pawn));
+    }
+
+    fn mate_in_two_board() -> ChessBoard {
+        let mut chess_board = ChessBoard::from_layout(ChessPieceColor::White, [0; 64]);
+
+        // a rook on e7 can block ra8+ but only by giving itself up
+        chess_board.place(&V2 { x: 7, y: 0 }, tile(ChessPiece::King, ChessPieceColor::Black));
+        chess_board.place(&V2 { x: 4, y: 1 }, tile(ChessPiece::Rook, ChessPieceColor::Black));
+        chess_board.place(&V2 { x: 6, y: 1 }, tile(ChessPiece::Pawn, ChessPieceColor::Black));
+        chess_board.place(&V2 { x: 7, y: 1 }, tile(ChessPiece::Pawn, ChessPieceColor::Black));
+
+        chess_board.place(&V2 { x: 6, y: 7 }, tile(ChessPiece::King, ChessPieceColor::White));
+        for x in 5..8 {
+            chess_board.place(&V2 { x, y: 6 }, tile(ChessPiece::Pawn, ChessPieceColor::White));
+        }
+        chess_board.place(&V2 { x: 0, y: 7 }, tile(ChessPiece::Rook, ChessPieceColor::White));
+
+        chess_board
+    }
+
+    #[test]
+    fn search_mate_in_two_with_every_config() {
+        let chess_board = mate_in_two_board();
+
+        for config in [SearchConfig::minimal(), SearchConfig::default()] {
+            let result = Searcher::new(config).search(&chess_board, 4);
+
+            assert_eq!(result.score, MATE - 3);
+            assert_eq!(result.best_move.expect("no move found").dst(), V2 { x: 0, y: 0 });
+        }
+    }
+
+    #[test]
+    fn search_pruning_saves_nodes() {
+        let chess_board = ChessBoard::new(ChessPieceColor::White);
+
+        let minimal = Searcher::new(SearchConfig::minimal()).search(&chess_board, 4);
+        let pruned = Searcher::new(SearchConfig::default()).search(&chess_board, 4);
+
+        assert!(pruned.nodes < minimal.nodes);
+        assert!(pruned.best_move.is_some());
+    }
+
+    #[test]
+    fn time_manager_allocation() {
+        let sudden_death = TimeManager::new(&TimeControl {
+            remaining: Duration::from_secs(60),
+            increment: Duration::ZERO,
+            moves_to_go: None
+        });
+
+        assert_eq!(sudden_death.soft_limit(), Duration::from_secs(2));
+        assert_eq!(sudden_death.hard_limit(), Duration::from_secs(8));
+
+        // the last move before the time control may use most of the clock,
+        // but never all of it
+        let last_move = TimeManager::new(&TimeControl {
+            remaining: Duration::from_secs(10),
+            increment: Duration::from_secs(1),
+            moves_to_go: Some(1)
+        });
+
+        assert!(last_move.hard_limit() < Duration::from_secs(10));
+        assert!(last_move.soft_limit() <= last_move.hard_limit());
+    }
+
+    #[test]
+    fn time_manager_extends_when_unstable() {
+        let mut clock = TimeManager::new(&TimeControl {
+            remaining: Duration::from_secs(60),
+            increment: Duration::ZERO,
+            moves_to_go: None
+        });
+
+        let base = clock.soft_limit();
+
+        clock.update(false, 50);
+        clock.update(true, -100);
+        assert!(clock.soft_limit() > base);
+        assert!(clock.soft_limit() <= clock.hard_limit());
+
+        // settling down brings it back
+        for _ in 0..8 {
+            clock.update(false, -100);
+        }
+        assert!(clock.soft_limit() <= base);
+    }
+
+    #[test]
+    fn time_manager_ponder_hit() {
+        let clock = TimeManager::fixed(Duration::ZERO).pondering();
+
+        // pondering ignores the clock until the opponent plays the move
+        assert!(!clock.should_stop());
+        assert!(clock.should_start_iteration());
+
+        clock.handle().ponder_hit();
+        assert!(clock.should_stop());
+
+        let handle = TimeManager::fixed(Duration::from_secs(60)).handle();
+        handle.stop();
+        assert!(handle.is_stopped());
+    }
+
+    #[test]
+    fn search_timed_stops_on_the_clock() {
+        let chess_board = ChessBoard::new(ChessPieceColor::White);
+        let start = Instant::now();
+
+        let result = Searcher::new(SearchConfig::default())
+            .search_timed(&chess_board, 64, TimeManager::fixed(Duration::from_millis(50)));
+
+        assert!(result.best_move.is_some());
+        assert!(result.depth < 64);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn search_timed_ponder_hit() {
+        let chess_board = ChessBoard::new(ChessPieceColor::White);
+        let clock = TimeManager::fixed(Duration::from_millis(50)).pondering();
+        let handle = clock.handle();
+
+        // pondering runs well past the clock, until the hit starts it
+        let search = thread::spawn(move || Searcher::new(SearchConfig::default()).search_timed(&chess_board, 64, clock));
+        thread::sleep(Duration::from_millis(300));
+        assert!(!search.is_finished());
+
+        let hit = Instant::now();
+        handle.ponder_hit();
+        let result = search.join().expect("search thread");
+        assert!(result.best_move.is_some());
+        assert!(hit.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn uci_ponder_and_infinite() {
+        let mut engine = UciEngine::new();
+        engine.handle("position startpos moves e2e4");
+
+        // the clock's hard limit passes while pondering without a bestmove
+        assert!(engine.handle("go ponder wtime 1000 btime 1000").is_empty());
+        thread::sleep(Duration::from_millis(300));
+        assert!(engine.poll().is_empty());
+        assert!(engine.is_searching());
+
+        let hit = Instant::now();
+        let mut replies = engine.handle("ponderhit");
+        while replies.is_empty() && hit.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(5));
+            replies = engine.poll();
+        }
+        assert!(replies.last().is_some_and(|reply| reply.starts_with("bestmove ")));
+        assert!(!engine.is_searching());
+
+        // infinite only answers stop
+        assert!(engine.handle("go infinite").is_empty());
+        thread::sleep(Duration::from_millis(100));
+        assert!(engine.poll().is_empty());
+        let replies = engine.handle("stop");
+        assert!(replies.last().is_some_and(|reply| reply.starts_with("bestmove ")));
+        assert!(engine.handle("stop").is_empty());
+
+        // and the same through run, which reads stop while the search goes on
+        let mut output = vec![];
+        UciEngine::new().run(&b"position startpos\ngo infinite\nisready\nstop\n"[..], &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("readyok\n"));
+        assert!(output.lines().last().is_some_and(|line| line.starts_with("bestmove ")));
+    }
+
+    #[test]
+    fn search_lazy_smp() {
+        let chess_board = mate_in_two_board();
+
+        let config = SearchConfig { threads: 4, ..SearchConfig::default() };
+        let result = Searcher::new(config).search(&chess_board, 4);
+
+        assert_eq!(result.score, MATE - 3);
+        assert_eq!(result.best_move.expect("no move found").dst(), V2 { x: 0, y: 0 });
+    }
+
+    #[test]
+    fn search_lazy_smp_helpers_search() {
+        let chess_board = ChessBoard::new(ChessPieceColor::White);
+
+        // the helpers search alongside the main thread, and it gets to use
+        // what they put in the table
+        let single = Searcher::new(SearchConfig::default()).search(&chess_board, 5);
+        assert_eq!(single.helper_nodes, 0);
+
+        let mut searcher = Searcher::new(SearchConfig { threads: 4, ..SearchConfig::default() });
+        let result = searcher.search(&chess_board, 5);
+        assert!(result.helper_nodes > 0);
+        assert!(result.nodes > result.helper_nodes);
+
+        // and what they find goes in the shared table, so it ends up holding
+        // more positions than one thread alone puts there
+        let filled = |searcher: &Searcher| searcher.table().filled();
+        let mut alone = Searcher::new(SearchConfig::default());
+        alone.search(&chess_board, 5);
+        assert!(filled(&searcher) > filled(&alone));
+    }
+
+    #[test]
+    fn search_single_thread_is_deterministic() {
+        let chess_board = ChessBoard::new(ChessPieceColor::White);
+
+        let first = Searcher::new(SearchConfig::default()).search(&chess_board, 3);
+        let second = Searcher::new(SearchConfig::default()).search(&chess_board, 3);
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn notation_squares_follow_orientation() {
+        let white = ChessBoard::new(ChessPieceColor::White);
+        let black = ChessBoard::new(ChessPieceColor::Black);
+
+        assert_eq!(white.square_name(&V2 { x: 4, y: 6 }), "e2");
+        assert_eq!(black.square_name(&V2 { x: 4, y: 6 }), "e7");
+
+        for chess_board in [white, black] {
+            for index in 0..64 {
+                let v2 = V2 { x: index % 8, y: index / 8 };
+                assert_eq!(chess_board.parse_square(&chess_board.square_name(&v2)), Some(v2));
+            }
+        }
+
+        let e4 = white.parse_uci_move("e2e4").expect("e2e4 should be legal");
+        assert_eq!(white.move_to_uci(&e4), "e2e4");
+        assert_eq!(white.parse_uci_move("e2e5"), None);
+    }
+
+    #[test]
+    fn search_multi_pv() {
+        let chess_board = mate_in_two_board();
+
+        let config = SearchConfig { multi_pv: 3, ..SearchConfig::default() };
+        let result = Searcher::new(config).search(&chess_board, 4);
+
+        assert_eq!(result.lines.len(), 3);
+        assert_eq!(result.lines[0].score, MATE - 3);
+        assert_eq!(result.lines[0].moves.len(), 3);
+        assert_eq!(result.best_move, Some(result.lines[0].moves[0]));
+
+        // distinct root moves, best first
+        assert_ne!(result.lines[0].moves[0], result.lines[1].moves[0]);
+        assert_ne!(result.lines[1].moves[0], result.lines[2].moves[0]);
+        assert!(result.lines[0].score >= result.lines[1].score);
+        assert!(result.lines[1].score >= result.lines[2].score);
+    }
+
+    #[test]
+    fn uci_multi_pv_info() {
+        let mut engine = UciEngine::new();
+
+        assert_eq!(engine.handle("uci").last().map(String::as_str), Some("uciok"));
+        assert_eq!(engine.handle("isready"), vec!["readyok"]);
+
+        engine.handle("setoption name MultiPV value 3");
+        engine.handle("position startpos moves e2e4 e7e5");
+        assert_eq!(engine.board().turn(), 2);
+
+        let replies = engine.handle("go depth 2");
+
+        assert_eq!(replies.len(), 4);
+        for (index, reply) in replies[..3].iter().enumerate() {
+            assert!(reply.starts_with("info depth 2 "));
+            assert!(reply.contains(&format!(" multipv {} ", index + 1)));
+            assert!(reply.contains(" pv "));
+        }
+        assert!(replies[3].starts_with("bestmove "));
+    }
+
+    #[test]
+    fn chess_board_castling() {
+        let mut chess_board = ChessBoard::from_layout(ChessPieceColor::White, [0; 64]);
+
+        chess_board.place(&V2 { x: 4, y: 7 }, tile(ChessPiece::King, ChessPieceColor::White));
+        chess_board.place(&V2 { x: 0, y: 7 }, tile(ChessPiece::Rook, ChessPieceColor::White));
+        chess_board.place(&V2 { x: 7, y: 7 }, tile(ChessPiece::Rook, ChessPieceColor::White));
+        chess_board.place(&V2 { x: 4, y: 0 }, tile(ChessPiece::King, ChessPieceColor::Black));
+
+        let castles = chess_board.castling_moves();
+        assert_eq!(castles.len(), 2);
+        assert!(chess_board.legal_moves().contains(&castles[0]));
+
+        let mut king_side = chess_board;
+        king_side.make_move(&chess_board.parse_uci_move("e1g1").expect("castling is legal"));
+        assert_eq!(king_side.tile(&V2 { x: 6, y: 7 }).piece(), Some(ChessPiece::King));
+        assert_eq!(king_side.tile(&V2 { x: 5, y: 7 }).piece(), Some(ChessPiece::Rook));
+        assert_eq!(king_side.tile(&V2 { x: 7, y: 7 }).piece(), None);
+        assert_eq!(king_side.castling_rights(ChessPieceColor::White), (false, false));
+
+        let mut queen_side = chess_board;
+        assert!(queen_side.take_turn(&V2 { x: 4, y: 7 }, &V2 { x: 2, y: 7 }));
+        assert_eq!(queen_side.tile(&V2 { x: 3, y: 7 }).piece(), Some(ChessPiece::Rook));
+        assert_eq!(queen_side.tile(&V2 { x: 0, y: 7 }).piece(), None);
+
+        // a black rook on f8 covers f1, so the king can't pass through it
+        chess_board.place(&V2 { x: 5, y: 0 }, tile(ChessPiece::Rook, ChessPieceColor::Black));
+        assert!(chess_board.parse_uci_move("e1g1").is_none());
+        assert!(chess_board.parse_uci_move("e1c1").is_some());
+    }
+
+    #[test]
+    fn zobrist_matches_polyglot_keys() {
+        let key_after = |moves: &str| {
+            let mut chess_board = ChessBoard::new(ChessPieceColor::White);
+            for text in moves.split_whitespace() {
+                let chess_move = chess_board.parse_uci_move(text).expect("legal move");
+                chess_board.make_move(&chess_move);
+            }
+            chess_board.hash()
+        };
+
+        // the reference keys from the polyglot book format description
+        assert_eq!(key_after(""), 0x463b96181691fc9c);
+        assert_eq!(key_after("e2e4"), 0x823c9b50fd114196);
+        assert_eq!(key_after("e2e4 d7d5"), 0x0756b94461c50fb0);
+        assert_eq!(key_after("e2e4 d7d5 e4e5"), 0x662fafb965db29d4);
+        assert_eq!(key_after("e2e4 d7d5 e4e5 f7f5"), 0x22a48b5a8e47ff78);
+        assert_eq!(key_after("e2e4 d7d5 e4e5 f7f5 e1e2"), 0x652a607ca3f242c1);
+        assert_eq!(key_after("e2e4 d7d5 e4e5 f7f5 e1e2 e8f7"), 0x00fdd303c946bdd9);
+        assert_eq!(key_after("a2a4 b7b5 h2h4 b5b4 c2c4"), 0x3c8123ea7b067637);
+        assert_eq!(key_after("a2a4 b7b5 h2h4 b5b4 c2c4 b4c3 a1a3"), 0x5c3f9b829b279560);
+
+        // the same position seen from the other side of the board
+        let mut flipped = ChessBoard::new(ChessPieceColor::Black);
+        for text in ["e2e4", "d7d5"] {
+            let chess_move = flipped.parse_uci_move(text).expect("legal move");
+            flipped.make_move(&chess_move);
+        }
+        assert_eq!(flipped.hash(), 0x0756b94461c50fb0);
+    }
+
+    #[test]
+    fn opening_book_lookup() {
+        let start = ChessBoard::new(ChessPieceColor::White);
+        let e4 = start.parse_uci_move("e2e4").expect("legal move");
+        let d4 = start.parse_uci_move("d2d4").expect("legal move");
+
+        let mut castled = ChessBoard::new(ChessPieceColor::White);
+        for text in ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "f8c5"] {
+            let chess_move = castled.parse_uci_move(text).expect("legal move");
+            castled.make_move(&chess_move);
+        }
+        let castle = castled.parse_uci_move("e1g1").expect("legal move");
+
+        let book = OpeningBook::new(vec![
+            BookEntry::new(&start, &d4, 1),
+            BookEntry::new(&castled, &castle, 5),
+            BookEntry::new(&start, &e4, 3)
+        ]);
+
+        // castling goes into the file as the king taking its rook, e1h1
+        assert_eq!(book.entries(castled.hash())[0].chess_move, 0x107);
+
+        let mut book = OpeningBook::from_bytes(&book.to_bytes()).expect("valid book").with_seed(7);
+        assert_eq!(book.len(), 3);
+        assert_eq!(book.moves(&start), vec![(d4, 1), (e4, 3)]);
+        assert_eq!(book.pick(&start, BookSelection::Best), Some(e4));
+        assert_eq!(book.pick(&castled, BookSelection::Best), Some(castle));
+
+        for _ in 0..8 {
+            let chess_move = book.pick(&start, BookSelection::Weighted);
+            assert!(chess_move == Some(e4) || chess_move == Some(d4));
+        }
+
+        let mut out_of_book = start;
+        out_of_book.make_move(&start.parse_uci_move("a2a3").expect("legal move"));
+        assert_eq!(book.pick(&out_of_book, BookSelection::Weighted), None);
+        assert!(OpeningBook::from_bytes(&[0; 15]).is_err());
+
+        let mut engine = UciEngine::new();
+        engine.set_book(book, BookSelection::Best);
+        engine.handle("position startpos");
+        assert_eq!(engine.handle("go depth 4"), vec!["bestmove e2e4"]);
+
+        engine.handle("setoption name OwnBook value false");
+        assert!(engine.handle("go depth 1").len() > 1);
+    }
+
+    // tables holding a single value for every position are the smallest
+    // valid files: KQvK is won for white to move and lost for black to move,
+    // every dtz is one move
+    fn syzygy_directory(name: &str) -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join(format!("chess-engine-syzygy-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&directory).expect("temp directory");
+
+        let mut wdl = vec![0x71, 0xe8, 0x23, 0x5d, 0x01, 0x00, 0x66, 0x55, 0xee, 0x00, 0x80, 0x04, 0x80, 0x00];
+        let mut dtz = vec![0xd7, 0x66, 0x0c, 0xa5, 0x00, 0x00, 0x66, 0x55, 0xee, 0x00, 0x80, 0x00];
+        wdl.resize(64, 0);
+        dtz.resize(64, 0);
+
+        fs::write(directory.join("KQvK.rtbw"), wdl).expect("write table");
+        fs::write(directory.join("KQvK.rtbz"), dtz).expect("write table");
+        fs::write(directory.join("KRvK.rtbw"), [0; 64]).expect("write table"); // wrong magic
+        directory
+    }
+
+    #[test]
+    fn syzygy_open() {
+        let directory = syzygy_directory("open");
+        let tablebase = Tablebase::open(&format!("/does/not/exist:{}", directory.display()));
+
+        assert_eq!(tablebase.len(), 2);
+        assert_eq!(tablebase.max_pieces(), 3);
+        assert!(Tablebase::open("/does/not/exist").is_empty());
+
+        fs::remove_dir_all(&directory).expect("remove temp directory");
+    }
+
+    #[test]
+    fn syzygy_root_moves_and_search() {
+        let directory = syzygy_directory("root");
+        let mut tablebase = Tablebase::new();
+        tablebase.add_directory(&directory);
+
+        let won = queen_mate_in_one();
+        assert!(tablebase.covers(&won));
+
+        // only the mate keeps the quickest win
+        let mate = won.parse_uci_move("g2g7").expect("legal move");
+        assert_eq!(tablebase.root_moves(&won), Some(vec![mate]));
+
+        let mut searcher = Searcher::new(SearchConfig::default());
+        searcher.set_tablebase(Some(Arc::new(tablebase)));
+        assert_eq!(searcher.search(&won, 3).best_move, Some(mate));
+        assert!(searcher.tablebase().is_some());
+
+        fs::remove_dir_all(&directory).expect("remove temp directory");
+    }
+
+    #[test]
+    fn syzygy_wdl() {
+        let directory = syzygy_directory("wdl");
+        let mut tablebase = Tablebase::new();
+        tablebase.add_directory(&directory);
+
+        let mut won = queen_mate_in_one();
+        assert_eq!(tablebase.probe_wdl(&won), Some(Wdl::Win));
+        won.make_null_move();
+        assert_eq!(tablebase.probe_wdl(&won), Some(Wdl::Loss));
+
+        // the colors swapped: black king f3 and queen g7, white king h1
+        let mut swapped = position(&[
+            (7, 7, ChessPiece::King, ChessPieceColor::White),
+            (5, 5, ChessPiece::King, ChessPieceColor::Black),
+            (6, 1, ChessPiece::Queen, ChessPieceColor::Black)
+        ]);
+        swapped.make_null_move();
+        assert_eq!(tablebase.probe_wdl(&swapped), Some(Wdl::Win));
+
+        // black can only take the queen, which leaves a draw
+        let mut hanging = position(&[
+            (0, 7, ChessPiece::King, ChessPieceColor::White),
+            (6, 1, ChessPiece::Queen, ChessPieceColor::White),
+            (7, 0, ChessPiece::King, ChessPieceColor::Black)
+        ]);
+        hanging.make_null_move();
+        assert_eq!(tablebase.probe_wdl(&hanging), Some(Wdl::Draw));
+
+        fs::remove_dir_all(&directory).expect("remove temp directory");
+    }
+
+    #[test]
+    fn syzygy_missing_or_broken_tables() {
+        let directory = syzygy_directory("broken");
+        let mut tablebase = Tablebase::new();
+        tablebase.add_directory(&directory);
+
+        // a broken table, or one that isn't there, just fails the probe
+        let rook = position(&[
+            (4, 7, ChessPiece::King, ChessPieceColor::White),
+            (0, 4, ChessPiece::Rook, ChessPieceColor::White),
+            (4, 0, ChessPiece::King, ChessPieceColor::Black)
+        ]);
+        assert_eq!(tablebase.probe_wdl(&rook), None);
+        assert_eq!(tablebase.root_moves(&rook), None);
+
+        let bishop = position(&[
+            (4, 7, ChessPiece::King, ChessPieceColor::White),
+            (0, 4, ChessPiece::Bishop, ChessPieceColor::White),
+            (4, 0, ChessPiece::King, ChessPieceColor::Black)
+        ]);
+        assert_eq!(tablebase.probe_wdl(&bishop), None);
+
+        fs::remove_dir_all(&directory).expect("remove temp directory");
+    }
+
+    #[test]
+    fn syzygy_real_tables() {
+        // the real 3 piece tables, with values checked against our own
+        // retrograde tables and another prober. both orientations have to
+        // agree, and the pawn tables are probed with the pawn on either side
+        let mut tablebase = Tablebase::new();
+        assert_eq!(tablebase.add_directory(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy")), 3);
+
+        let known = [
+            ("8/8/8/8/8/2k5/8/KQ6 b - - 0 1", Wdl::Loss, -14),
+            ("4k3/8/8/8/3K4/8/8/7Q b - - 0 1", Wdl::Loss, -10),
+            ("7k/5K2/8/8/8/8/6Q1/8 w - - 0 1", Wdl::Win, 1),
+            ("k7/8/2K5/8/8/8/8/7R w - - 0 1", Wdl::Win, 3),
+            ("8/8/8/2R5/1K6/8/5k2/8 w - - 0 1", Wdl::Win, 21),
+            ("8/8/3k4/8/8/8/8/R3K3 w - - 0 1", Wdl::Win, 27),
+            ("8/8/3k4/8/8/8/8/R3K3 b - - 0 1", Wdl::Loss, -28),
+            ("8/8/8/4k3/8/8/4P3/4K3 w - - 0 1", Wdl::Draw, 0),
+            ("8/8/8/4k3/8/8/4P3/4K3 b - - 0 1", Wdl::Draw, 0),
+            ("8/3k4/8/8/8/8/4P3/3K4 w - - 0 1", Wdl::Draw, 0),
+            ("8/8/8/8/8/k7/P7/K7 w - - 0 1", Wdl::Draw, 0),
+            ("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", Wdl::Win, 3),
+            ("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1", Wdl::Loss, -4),
+            ("8/1P6/8/8/8/8/k7/6K1 w - - 0 1", Wdl::Win, 1),
+            ("8/8/8/2K5/5kp1/8/8/8 b - - 0 1", Wdl::Win, 1),
+            ("8/5p2/6k1/K7/8/8/8/8 w - - 0 1", Wdl::Loss, -2)
+        ];
+
+        for (fen, wdl, dtz) in known {
+            for color in [ChessPieceColor::White, ChessPieceColor::Black] {
+                let chess_board = ChessBoard::from_fen(color, fen).expect("valid fen");
+                assert_eq!(tablebase.probe_wdl(&chess_board), Some(wdl), "{}", fen);
+                assert_eq!(tablebase.probe_dtz(&chess_board), Some(dtz), "{}", fen);
+            }
+        }
+
+        // both king moves leave mate with Rh8 next
+        let rook = ChessBoard::from_fen(ChessPieceColor::White, "k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+        let moves: Vec<String> = tablebase.root_moves(&rook).expect("covered").iter().map(|chess_move| rook.move_to_uci(chess_move)).collect();
+        assert_eq!(moves, vec!["c6c7", "c6b6"]);
+    }
+
+    // white: king a1, queen d1. black: king e5
+    fn queen_ending() -> ChessBoard {
+        position(&[
+            (0, 7, ChessPiece::King, ChessPieceColor::White),
+            (3, 7, ChessPiece::Queen, ChessPieceColor::White),
+            (4, 3, ChessPiece::King, ChessPieceColor::Black)
+        ])
+    }
+
+    // the same with the colors swapped, black to move
+    fn swapped_queen_ending() -> ChessBoard {
+        let mut chess_board = position(&[
+            (0, 0, ChessPiece::King, ChessPieceColor::Black),
+            (3, 0, ChessPiece::Queen, ChessPieceColor::Black),
+            (4, 4, ChessPiece::King, ChessPieceColor::White)
+        ]);
+        chess_board.make_null_move();
+        chess_board
+    }
+
+    fn queen_tables() -> EndgameTables {
+        let mut tables = EndgameTables::new();
+        tables.generate("KQvK").expect("generate KQvK");
+        tables
+    }
+
+    #[test]
+    fn endgame_table_longest_mate() {
+        // the longest win with a queen is mate in ten
+        let tables = queen_tables();
+        let table = tables.table("KvKQ").expect("KQvK table");
+        assert_eq!(table.name(), "KQvK");
+        assert_eq!(table.longest_mate(), 19);
+    }
+
+    #[test]
+    fn endgame_table_refuses_big_endings() {
+        assert!(EndgameTables::new().generate("KQRvKR").is_err());
+    }
+
+    #[test]
+    fn endgame_table_best_move_mates() {
+        let tables = queen_tables();
+        let mate_in_one = queen_mate_in_one();
+        assert_eq!(tables.probe(&mate_in_one), Some(Dtm::Win(1)));
+        let best = tables.best_move(&mate_in_one).expect("a winning move");
+        assert_eq!(mate_in_one.move_to_uci(&best), "g2g7");
+    }
+
+    #[test]
+    fn endgame_table_mates_when_it_said() {
+        // both sides play the table and the game has to end in mate exactly
+        // when it said
+        let tables = queen_tables();
+        let mut chess_board = queen_ending();
+        let plies = match tables.probe(&chess_board) {
+            Some(Dtm::Win(plies)) => plies,
+            other => panic!("expected a win, got {:?}", other)
+        };
+
+        for _ in 0..plies {
+            let best = tables.best_move(&chess_board).expect("a move");
+            chess_board.make_move(&best);
+        }
+        assert!(chess_board.legal_moves().is_empty());
+        assert!(chess_board.in_check(ChessPieceColor::Black));
+    }
+
+    #[test]
+    fn endgame_table_swapped_colors() {
+        let tables = queen_tables();
+        assert_eq!(tables.probe(&swapped_queen_ending()), tables.probe(&queen_ending()));
+    }
+
+    #[test]
+    fn endgame_table_draws() {
+        let tables = queen_tables();
+
+        // black: king a8. white: queen b6, king d1. black to move is stalemate
+        let mut stalemate = position(&[
+            (0, 0, ChessPiece::King, ChessPieceColor::Black),
+            (1, 2, ChessPiece::Queen, ChessPieceColor::White),
+            (3, 7, ChessPiece::King, ChessPieceColor::White)
+        ]);
+        stalemate.make_null_move();
+        assert_eq!(tables.probe(&stalemate), Some(Dtm::Draw));
+
+        // a bare king each is a draw without any table, a rook ending has none
+        let kings = position(&[
+            (0, 7, ChessPiece::King, ChessPieceColor::White),
+            (4, 3, ChessPiece::King, ChessPieceColor::Black)
+        ]);
+        assert_eq!(tables.probe(&kings), Some(Dtm::Draw));
+
+        let rook = position(&[
+            (0, 7, ChessPiece::King, ChessPieceColor::White),
+            (3, 7, ChessPiece::Rook, ChessPieceColor::White),
+            (4, 3, ChessPiece::King, ChessPieceColor::Black)
+        ]);
+        assert_eq!(tables.probe(&rook), None);
+    }
+
+    #[test]
+    fn endgame_table_save_and_load() {
+        // saved and loaded back the table answers the same
+        let tables = queen_tables();
+        let directory = std::env::temp_dir().join(format!("chess-engine-endgame-{}", std::process::id()));
+        fs::create_dir_all(&directory).expect("temp directory");
+        tables.save(&directory).expect("save tables");
+
+        let mut loaded = EndgameTables::new();
+        assert_eq!(loaded.add_directory(&directory), 1);
+        assert_eq!(loaded.probe(&queen_ending()), tables.probe(&queen_ending()));
+        assert_eq!(loaded.probe(&swapped_queen_ending()), tables.probe(&queen_ending()));
+
+        assert!(EndgameTable::load(directory.join("KRvK.etb")).is_err());
+        assert!(EndgameTable::from_bytes(&[0; 64]).is_err());
+
+        fs::remove_dir_all(&directory).expect("remove temp directory");
+    }
+
+    #[test]
+    fn endgame_table_rook_and_pawn() {
+        // mate in 16 with a rook, and in 28 with a pawn, which has to look up
+        // the tables it promotes into
+        let mut tables = queen_tables();
+        tables.generate("KRvK").expect("generate KRvK");
+        assert_eq!(tables.table("KRvK").expect("KRvK table").longest_mate(), 31);
+        tables.generate("KPvK").expect("generate KPvK");
+        assert_eq!(tables.table("KPvK").expect("KPvK table").longest_mate(), 55);
+    }
+
+    // takes a few minutes, run with --ignored
+    #[test]
+    #[ignore]
+    fn endgame_table_bishop_knight() {
+        let mut tables = EndgameTables::new();
+        tables.generate("KBNvK").expect("generate KBNvK");
+        assert_eq!(tables.table("KBNvK").expect("KBNvK table").longest_mate(), 65);
+    }
+
+    // a small network with made up weights, written the way a trainer would
+    fn network_bytes() -> Vec<u8> {
+        let hidden = 16;
+        let mut state: u64 = 0x9e3779b97f4a7c15;
+        let mut random = || (xorshift(&mut state) % 64) as i16 - 32;
+
+        let mut bytes = b"CENN".to_vec();
+        bytes.extend((hidden as u32).to_le_bytes());
+        for _ in 0..(FEATURES + 1 + 2) * hidden {
+            bytes.extend(random().to_le_bytes());
+        }
+        bytes.extend(25i32.to_le_bytes());
+
+        bytes
+    }
+
+    #[test]
+    fn nnue_network_file() {
+        let bytes = network_bytes();
+        assert_eq!(Network::from_bytes(&bytes).expect("network loads").hidden(), 16);
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Network::from_bytes(b"CENN\x08\x00\x00\x00").is_err());
+    }
+
+    #[test]
+    fn nnue_incremental_updates() {
+        let network = Network::from_bytes(&network_bytes()).expect("network loads");
+        let scalar = network.clone().scalar();
+
+        // updating move by move has to land on the same numbers as building
+        // the accumulator from scratch, through en passant, castling and
+        // promotion, and the simd code has to agree with the plain code
+        let check = |moves: &str, mut chess_board: ChessBoard| {
+            let mut accumulator = network.refresh(&chess_board).expect("orthodox board");
+
+            for text in moves.split_whitespace() {
+                let chess_move = chess_board.parse_uci_move(text).expect("legal move");
+                network.update(&mut accumulator, &chess_board, &chess_move).expect("orthodox move");
+                chess_board.make_move(&chess_move);
+
+                assert_eq!(Some(&accumulator), network.refresh(&chess_board).as_ref());
+                assert_eq!(Some(&accumulator), scalar.refresh(&chess_board).as_ref());
+                assert_eq!(
+                    Some(network.evaluate(&accumulator, chess_board.side_to_move())),
+                    scalar.evaluate_board(&chess_board)
+                );
+            }
+        };
+
+        check("e2e4 g8f6 e4e5 d7d5 e5d6 e7d6 g1f3 f8e7 f1c4 e8g8 e1g1 b8c6 b1c3 c8e6 c4e6 f7e6", ChessBoard::new(ChessPieceColor::White));
+        check("e2e4 e7e5 g1f3 b8c6 f1b5 g8f6 e1g1 f6e4", ChessBoard::new(ChessPieceColor::Black));
+
+        // white: king e1, pawn b7. black: king e8, rook a8
+        let mut promotion = ChessBoard::from_layout(ChessPieceColor::White, [0; 64]);
+        promotion.place(&V2 { x: 4, y: 7 }, tile(ChessPiece::King, ChessPieceColor::White).moved());
+        promotion.place(&V2 { x: 1, y: 1 }, tile(ChessPiece::Pawn, ChessPieceColor::White).moved());
+        promotion.place(&V2 { x: 4, y: 0 }, tile(ChessPiece::King, ChessPieceColor::Black).moved());
+        promotion.place(&V2 { x: 0, y: 0 }, tile(ChessPiece::Rook, ChessPieceColor::Black).moved());
+        check("b7a8q e8d7 a8d8", promotion);
+        check("b7b8n a8b8", promotion);
+    }
+
+    #[test]
+    fn nnue_only_orthodox_boards() {
+        let network = Network::from_bytes(&network_bytes()).expect("network loads");
+
+        // a wider board, or fairy pieces on the usual one, get no answer
+        // rather than made up feature numbers
+        let capablanca = ChessBoard::from_fen_sized(ChessPieceColor::White, CAPABLANCA_FEN).unwrap();
+        assert!(network.refresh(&capablanca).is_none());
+        assert_eq!(network.evaluate_board(&capablanca), None);
+
+        let archbishop = ChessBoard::from_fen_sized(ChessPieceColor::White, "4k3/8/8/8/8/8/8/A3K3 w - - 0 1").unwrap();
+        assert_eq!(network.evaluate_board(&archbishop), None);
+
+        let mut accumulator = network.refresh(&ChessBoard::new(ChessPieceColor::White)).unwrap();
+        let before = accumulator.clone();
+        let chess_move = archbishop.parse_uci_move("a1b3").unwrap();
+        assert!(network.update(&mut accumulator, &archbishop, &chess_move).is_err());
+        assert_eq!(accumulator, before);
+
+        // and the search falls back to the hand written evaluation
+        let config = SearchConfig { evaluator: Evaluator::Nnue, ..SearchConfig::minimal() };
+        let mut searcher = Searcher::new(config);
+        searcher.set_network(Some(Arc::new(network)));
+        assert!(searcher.search(&capablanca, 2).best_move.is_some());
+        assert!(searcher.search(&archbishop, 2).best_move.is_some());
+    }
+
+    #[test]
+    fn nnue_search_and_uci() {
+        let bytes = network_bytes();
+        let network = Network::from_bytes(&bytes).expect("network loads");
+
+        // the search with the network, and switched on over uci
+        let directory = std::env::temp_dir().join(format!("chess-engine-nnue-{}", std::process::id()));
+        fs::create_dir_all(&directory).expect("temp directory");
+        let path = directory.join("test.nnue");
+        fs::write(&path, &bytes).expect("write network");
+
+        let config = SearchConfig { evaluator: Evaluator::Nnue, quiescence: false, ..SearchConfig::minimal() };
+        let mut searcher = Searcher::new(config);
+        searcher.set_network(Some(Arc::new(Network::load(&path).expect("network loads"))));
+
+        let chess_board = ChessBoard::new(ChessPieceColor::White);
+        let expected = chess_board.legal_moves().iter()
+            .map(|chess_move| {
+                let mut child = chess_board;
+                child.make_move(chess_move);
+                -network.evaluate_board(&child).expect("orthodox board")
+            })
+            .max();
+        assert_eq!(Some(searcher.search(&chess_board, 1).score), expected);
+        assert!(searcher.search(&chess_board, 3).best_move.is_some());
+
+        let mut engine = UciEngine::new();
+        assert!(engine.handle("uci").contains(&"option name UseNNUE type check default false".to_string()));
+        engine.handle(&format!("setoption name EvalFile value {}", path.display()));
+        engine.handle("setoption name UseNNUE value true");
+        assert_eq!(engine.config().evaluator, Evaluator::Nnue);
+        assert!(engine.handle("go depth 2").last().expect("a reply").starts_with("bestmove "));
+
+        fs::remove_dir_all(&directory).expect("remove temp directory");
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        let start = ChessBoard::from_fen(ChessPieceColor::White, START_FEN).expect("start position");
+        assert_eq!(start, ChessBoard::new(ChessPieceColor::White));
+        assert_eq!(start.to_fen(), START_FEN);
+
+        // en passant, castling rights and the move number survive the trip,
+        // and the polyglot key matches the one for the same moves played out
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        let chess_board = ChessBoard::from_fen(ChessPieceColor::White, fen).expect("valid fen");
+        assert_eq!(chess_board.to_fen(), fen);
+        assert_eq!(chess_board.hash(), 0x22a48b5a8e47ff78);
+        assert!(chess_board.parse_uci_move("e5f6").is_some());
+
+        let flipped = ChessBoard::from_fen(ChessPieceColor::Black, fen).expect("valid fen");
+        assert_eq!(flipped.to_fen(), fen);
+        assert_eq!(flipped.hash(), chess_board.hash());
+
+        let partial = "4k3/8/8/8/8/8/8/R3K2R b Kq - 0 40";
+        let chess_board = ChessBoard::from_fen(ChessPieceColor::White, partial).expect("valid fen");
+        assert_eq!(chess_board.to_fen(), "4k3/8/8/8/8/8/8/R3K2R b K - 0 40");
+        assert_eq!(chess_board.turn(), 79);
+
+        assert!(ChessBoard::from_fen(ChessPieceColor::White, "8/8/8 w - -").is_err());
+        assert!(ChessBoard::from_fen(ChessPieceColor::White, "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").is_err());
+        assert!(ChessBoard::from_fen(ChessPieceColor::White, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq -").is_err());
+        assert!(ChessBoard::from_fen(ChessPieceColor::White, "4k3/8/8/8/8/8/8/4K3 b - - 0 32769").is_err());
+        assert_eq!(ChessBoard::from_fen(ChessPieceColor::White, "4k3/8/8/8/8/8/8/4K3 w - - 0 32768").unwrap().turn(), 65534);
+    }
+
+    #[test]
+    fn texel_tuning() {
+        let lines = [
+            "4k3/8/8/8/8/8/8/3QK3 w - - 0 1 [1.0]",
+            "3qk3/8/8/8/8/8/8/4K3 b - - c9 \"0-1\";",
+            "4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 1/2-1/2",
+            "4k3/pppppppp/8/8/8/8/PPPPPPP1/4K3 b - - 0 1 [0.5]",
+            "4k3/ppp5/8/8/8/8/PPPPP3/4K3 w - - 0-1",
+            "4k3/8/8/8/8/8/8/4K3 w - - no result"
+        ];
+
+        let positions: Vec<_> = lines.iter().filter_map(|line| parse_labelled(line)).collect();
+        assert_eq!(positions.len(), 5);
+        assert_eq!(positions[1].result, 0.0);
+        assert_eq!(positions[1].board.side_to_move(), ChessPieceColor::Black);
+
+        // the written out terms add up to the evaluation
+        let params = EvalParams::default();
+        let weights = params.to_vec();
+        for position in &positions {
+            let board = position.board;
+            let white: i32 = board.eval_terms().iter().map(|(index, sign)| sign * weights[*index]).sum();
+            let side = match board.side_to_move() {
+                ChessPieceColor::White => white,
+                ChessPieceColor::Black => -white
+            };
+            assert_eq!(side, board.evaluate());
+        }
+
+        let mut tuner = Tuner::new(&positions);
+        let scale = tuner.fit_scale(&params);
+        assert!(scale > 0.0);
+
+        // the last position is lost despite the extra pawns, tuning has to
+        // fit the results better than the defaults do
+        let tuned = tuner.tune(&params, 5);
+        assert!(tuner.loss(&tuned) < tuner.loss(&params));
+        assert_ne!(tuned, params);
+
+        // written out and read back, and used by the search
+        let text = tuned.to_text();
+        assert_eq!(EvalParams::from_text(&text), Ok(tuned.clone()));
+        assert_eq!(EvalParams::from_text("values 1 2 3"), Err("wrong number of evaluation parameters"));
+        assert!(EvalParams::from_text("bishops 1").is_err());
+
+        let mut searcher = Searcher::new(SearchConfig { quiescence: false, ..SearchConfig::minimal() });
+        searcher.set_eval_params(tuned.clone());
+
+        let chess_board = positions[4].board;
+        let expected = chess_board.legal_moves().iter()
+            .map(|chess_move| {
+                let mut child = chess_board;
+                child.make_move(chess_move);
+                -child.evaluate_with(&tuned)
+            })
+            .max();
+        assert_eq!(Some(searcher.search(&chess_board, 1).score), expected);
+    }
+
+    #[test]
+    fn self_play_match() {
+        let start = ChessBoard::new(ChessPieceColor::White);
+        let san: Vec<String> = start.legal_moves().iter().map(|chess_move| start.move_to_san(chess_move)).collect();
+        assert!(san.contains(&"Nf3".to_string()) && san.contains(&"e4".to_string()));
+        assert_eq!(start.parse_san("Nc3"), start.parse_uci_move("b1c3"));
+
+        // both knights reach d2, so the file tells them apart
+        let chess_board = ChessBoard::from_fen(ChessPieceColor::White, "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").expect("valid fen");
+        let knight = chess_board.parse_uci_move("b1d2").unwrap();
+        assert_eq!(chess_board.move_to_san(&knight), "Nbd2");
+        assert_eq!(chess_board.parse_san("Nbd2+"), Some(knight));
+        assert_eq!(chess_board.parse_san("Nd2"), None);
+
+        let chess_board = ChessBoard::from_fen(ChessPieceColor::White, "6k1/5ppp/8/8/8/8/8/R3K2R w KQ - 0 1").expect("valid fen");
+        assert_eq!(chess_board.move_to_san(&chess_board.parse_uci_move("a1a8").unwrap()), "Ra8#");
+        assert_eq!(chess_board.parse_san("0-0-0"), chess_board.parse_uci_move("e1c1"));
+
+        // pgn moves are numbered from where the game started
+        let black_to_move = ChessBoard::from_fen(ChessPieceColor::White, "4k3/8/8/8/8/8/4P3/4K3 b - - 0 7").expect("valid fen");
+        let mut game = PgnGame::new(black_to_move);
+        game.push(black_to_move.parse_san("Kd7").unwrap());
+        game.set_result(GameResult::Draw);
+        let text = game.to_pgn();
+        assert!(text.contains("[Result \"1/2-1/2\"]") && text.contains("[SetUp \"1\"]"));
+        assert!(text.ends_with("\n7... Kd7 1/2-1/2\n"));
+
+        // white mates on the back rank whichever engine plays it, so each
+        // engine wins once
+        let config = SearchConfig { threads: 1, ..SearchConfig::default() };
+        let first = Engine::new("first", config);
+        let second = Engine::new("second", config);
+        let mut runner = MatchRunner::new(first, second, MoveLimit::Depth(3));
+        runner.set_openings(vec![
+            ChessBoard::from_fen(ChessPieceColor::White, "6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1").expect("valid fen")
+        ]);
+        runner.set_adjudication(Adjudication { max_plies: 120, ..Adjudication::default() });
+
+        let mut records = vec![];
+        let stats = runner.run(2, |record, _| records.push(record.clone()));
+        assert_eq!(stats.games(), 2);
+        assert_eq!(records[0].result, GameResult::WhiteWins);
+        assert_eq!(records[1].result, GameResult::WhiteWins);
+        assert_eq!(stats, MatchStats { wins: 1, draws: 0, losses: 1 });
+        assert_eq!(records[1].pgn.tag("White"), Some("second"));
+        assert_eq!(records[0].termination, "checkmate");
+        assert!(records[0].pgn.to_pgn().contains("1. Ra8# 1-0"));
+
+        // statistics
+        let even = MatchStats { wins: 10, draws: 20, losses: 10 };
+        assert!(even.elo().abs() < 1e-9);
+        assert!((even.los() - 0.5).abs() < 1e-9);
+
+        let ahead = MatchStats { wins: 60, draws: 20, losses: 20 };
+        assert!((ahead.elo() - 147.19).abs() < 0.1);
+        assert!(ahead.elo_error() > 0.0 && ahead.elo_error() < ahead.elo());
+        assert!(ahead.los() > 0.99);
+
+        let sprt = Sprt { elo0: 0.0, elo1: 10.0, alpha: 0.05, beta: 0.05 };
+        let (lower, upper) = sprt.bounds();
+        assert!((upper - 2.944).abs() < 1e-3 && (lower + 2.944).abs() < 1e-3);
+        assert_eq!(sprt.decision(&even), SprtDecision::Continue);
+        assert_eq!(sprt.decision(&ahead), SprtDecision::Continue);
+        assert_eq!(sprt.decision(&MatchStats { wins: 300, draws: 100, losses: 100 }), SprtDecision::AcceptH1);
+        assert_eq!(sprt.decision(&MatchStats { wins: 100, draws: 100, losses: 300 }), SprtDecision::AcceptH0);
+    }
+
+    #[test]
+    fn epd_test_suite() {
+        let suite = r#"
+            # win at chess, the first position
+            2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+            4k3/8/2p5/3p4/8/8/8/3QK3 w - - am Qxd5; id "queen trap"; c0 "the pawn is guarded; leave it";
+            4k3/8/8/8/8/8/8/4K3 w - - id "nothing to test";
+            4k3/8/8/8/8/8/8/4K3 w - - bm Qh5; id "illegal";
+            not an epd
+        "#;
+
+        let records = parse_suite(suite);
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].id(), Some("WAC.001"));
+        assert_eq!(records[1].operation("c0"), Some(&["the pawn is guarded; leave it".to_string()][..]));
+        assert!(!records[2].is_testable() && !records[3].is_testable());
+
+        let queen_trap = &records[1];
+        assert_eq!(EpdRecord::parse(&queen_trap.to_epd()).as_ref(), Ok(queen_trap));
+        assert!(EpdRecord::parse("4k3/8/8/8/8/8/8/4K3 w - - bm Kd2; \"").is_err());
+        assert!(!queen_trap.solved_by(&queen_trap.board.parse_san("Qxd5").unwrap()));
+        assert!(queen_trap.solved_by(&queen_trap.board.parse_san("Kf2").unwrap()));
+
+        let mut seen = vec![];
+        let report = run_suite(&records, SearchConfig::default(), MoveLimit::Depth(5), |_, result| seen.push(result.id.clone()));
+        assert_eq!(seen, vec!["WAC.001", "queen trap"]);
+        assert_eq!(report.skipped, 2);
+        assert_eq!((report.solved(), report.total()), (2, 2));
+        assert_eq!(report.results[0].chosen, records[0].board.parse_san("Qg6"));
+    }
+
+    #[test]
+    fn epd_extra_spaces() {
+        // spaces and tabs between the fields are only separators
+        let spaced = EpdRecord::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR  w \tKQkq   -  bm e4;").unwrap();
+        let single = EpdRecord::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e4;").unwrap();
+        assert_eq!(spaced, single);
+        assert_eq!(spaced.operation("bm"), Some(&["e4".to_string()][..]));
+
+        let bare = EpdRecord::parse("4k3/8/8/8/8/8/8/4K3 w  - -").unwrap();
+        assert_eq!(bare.to_epd(), "4k3/8/8/8/8/8/8/4K3 w - -");
+        assert!(EpdRecord::parse("4k3/8/8/8/8/8/8/4K3 w  -").is_err());
+    }
+
+    #[test]
+    fn pgn_games() {
+        let text = r#"
+            [Event "casual"]
+            [White "someone \"quoted\""]
+            [Result "1-0"]
+
+            1. e4 e5 2. Bc4 {the bishop eyes f7} Nc6 (2... Nf6 3. d3) 3. Qh5 $1 Nf6?? 4. Qxf7# 1-0
+
+            [FEN "r3k2r/8/8/8/8/8/8/4K3 b kq - 0 30"]
+
+            30... 0-0-0 31.Kf2 *
+        "#;
+
+        let games = parse_games(ChessPieceColor::White, text).expect("valid pgn");
+        assert_eq!(games.len(), 2);
+
+        let mate = &games[0];
+        assert_eq!(mate.tag("White"), Some("someone \"quoted\""));
+        assert_eq!(mate.result(), GameResult::WhiteWins);
+        assert_eq!(mate.movetext(), "1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7#");
+        assert_eq!(mate.outcome(), Some((GameResult::WhiteWins, "checkmate")));
+
+        // written out and read back, and the same game seen from black's side
+        assert_eq!(PgnGame::parse(ChessPieceColor::White, &mate.to_pgn()).as_ref(), Ok(mate));
+        let flipped = mate.oriented(ChessPieceColor::Black);
+        assert_eq!(flipped.board().to_fen(), mate.board().to_fen());
+        assert_eq!(flipped.movetext(), mate.movetext());
+
+        let castled = &games[1];
+        assert_eq!(castled.movetext(), "30... O-O-O 31. Kf2");
+        assert_eq!(castled.board().to_fen(), "2kr3r/8/8/8/8/8/5K2/8 b - - 0 31");
+
+        let mut undone = castled.clone();
+        assert!(undone.pop().is_some());
+        assert_eq!(undone.moves().len(), 1);
+
+        // knights back and forth repeat the start position a third time
+        let mut shuffle = PgnGame::new(ChessBoard::new(ChessPieceColor::White));
+        for _ in 0..2 {
+            for text in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+                let board = shuffle.board();
+                assert_eq!(shuffle.outcome(), None);
+                shuffle.push(board.parse_san(text).unwrap());
+            }
+        }
+        assert_eq!(shuffle.outcome(), Some((GameResult::Draw, "threefold repetition")));
+
+        assert!(PgnGame::parse(ChessPieceColor::White, "1. e4 e4 *").is_err());
+    }
+
+    #[test]
+    fn board_rendering() {
+        let start = ChessBoard::new(ChessPieceColor::White);
+        let text = start.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], "8  r  n  b  q  k  b  n  r ");
+        assert_eq!(lines[7], "1  R  N  B  Q  K  B  N  R ");
+        assert_eq!(lines[8], "   a  b  c  d  e  f  g  h ");
+
+        // the last move and the king in check are marked
+        let mut chess_board = start;
+        for text in ["e4", "f6", "Qh5+"] {
+            chess_board.make_move(&chess_board.parse_san(text).unwrap());
+        }
+        let text = chess_board.to_string();
+        assert!(text.starts_with("8  r  n  b  q (k) b  n  r \n"));
+        assert!(text.contains("5  .  .  .  .  .  .  . [Q]\n"));
+        assert!(text.contains("1  R  N  B [.] K  B  N  R \n"));
+
+        // seen from black, whichever side the board was set up for
+        let flipped = chess_board.render(&RenderOptions { flipped: true, coordinates: false, ..RenderOptions::default() });
+        let black = ChessBoard::from_fen(ChessPieceColor::Black, &chess_board.to_fen()).unwrap();
+        let from_black = black.render(&RenderOptions { coordinates: false, highlights: false, ..RenderOptions::default() });
+        assert!(flipped.starts_with(" R  N  B  K [.] B  N  R \n"));
+        assert!(from_black.starts_with(" R  N  B  K  .  B  N  R \n"));
+
+        let unicode = start.render(&RenderOptions { glyphs: Glyphs::Unicode, ..RenderOptions::default() });
+        assert!(unicode.starts_with("8  ♜  ♞  ♝  ♛  ♚  ♝  ♞  ♜ \n"));
+        assert!(unicode.contains("1  ♖  ♘  ♗  ♕  ♔  ♗  ♘  ♖ \n"));
+
+        let colored = start.render(&RenderOptions { glyphs: Glyphs::Unicode, colors: true, ..RenderOptions::default() });
+        assert_eq!(colored.matches("\x1b[0m").count(), 64);
+    }
+
+    #[test]
+    fn svg_diagrams() {
+        let mut chess_board = ChessBoard::new(ChessPieceColor::White);
+        chess_board.make_move(&chess_board.parse_san("e4").unwrap());
+
+        let e4 = chess_board.parse_square("e4").unwrap();
+        let d5 = chess_board.parse_square("d5").unwrap();
+
+        let options = SvgOptions { arrows: vec![(e4, d5)], highlights: vec![d5], ..SvgOptions::default() };
+        let svg = chess_board.to_svg(&options);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"400\" height=\"400\""));
+        assert!(svg.ends_with("</svg>\n"));
+
+        // the border, 64 squares, 32 labels and 32 pieces
+        assert_eq!(svg.matches("<rect").count(), 65);
+        assert_eq!(svg.matches("<text").count(), 64);
+        assert_eq!(svg.matches("<line").count(), 1);
+        // e2, e4 and d5 marked
+        assert_eq!(svg.matches("fill=\"#cdd26a\"").count(), 3);
+
+        // a1 is dark, in the bottom left corner, or the top right flipped
+        assert!(svg.contains("<rect x=\"20.00\" y=\"335.00\" width=\"45.00\" height=\"45.00\" fill=\"#b58863\"/>"));
+        let flipped = chess_board.to_svg(&SvgOptions { flipped: true, coordinates: false, last_move: false, size: 240, theme: SvgTheme::blue(), ..SvgOptions::default() });
+        assert_eq!(flipped.matches("<rect").count(), 64);
+        assert!(flipped.contains("<rect x=\"210.00\" y=\"0.00\" width=\"30.00\" height=\"30.00\" fill=\"#8ca2ad\"/>"));
+        assert!(!flipped.contains("#9bc700"));
+
+        // with an odd number of files the colors still alternate along a rank
+        let odd = ChessBoard::from_fen_sized(ChessPieceColor::White, "k8/9/9/9/9/9/9/9/8K w - - 0 1").unwrap();
+        let svg = odd.to_svg(&SvgOptions { coordinates: false, ..SvgOptions::default() });
+        assert_eq!(svg.matches("fill=\"#f0d9b5\"/>").count(), 40);
+    }
+
+    #[test]
+    fn chess960_castling() {
+        // 518 is the standard setup, 0 has both bishops in the corner
+        let standard = ChessBoard::chess960(ChessPieceColor::White, 518).unwrap();
+        assert_eq!(standard.to_fen(), START_FEN);
+        assert!(standard.is_chess960());
+        assert_eq!(ChessBoard::chess960(ChessPieceColor::White, 0).unwrap().to_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        assert!(ChessBoard::chess960(ChessPieceColor::White, 960).is_err());
+
+        let setups: std::collections::HashSet<String> = (0..960)
+            .map(|index| ChessBoard::chess960(ChessPieceColor::Black, index).unwrap().to_fen())
+            .collect();
+        assert_eq!(setups.len(), 960);
+
+        // perft numbers from the usual chess960 suite, with shredder-fen rights
+        let suite = [
+            ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", [21, 528, 12189]),
+            ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", [21, 807, 18002]),
+            ("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", [20, 479, 10471])
+        ];
+
+        for (fen, counts) in suite {
+            for color in [ChessPieceColor::White, ChessPieceColor::Black] {
+                let chess_board = ChessBoard::from_fen(color, fen).expect("valid fen");
+                assert!(chess_board.is_chess960());
+                for (depth, count) in counts.iter().enumerate() {
+                    assert_eq!(chess_board.perft(depth as u8 + 1), *count);
+                }
+            }
+        }
+
+        // castling is the king taking its own rook, even when the king
+        // doesn't move at all
+        let fen = "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1";
+        let chess_board = ChessBoard::from_fen(ChessPieceColor::White, fen).expect("valid fen");
+        assert_eq!(chess_board.to_fen(), "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w KQkq - 0 1");
+        assert_eq!(chess_board.to_shredder_fen(), "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1");
+
+        let castle = chess_board.parse_uci_move("e1g1").expect("castling");
+        assert_eq!(chess_board.move_to_san(&castle), "O-O");
+        assert_eq!(chess_board.parse_san("O-O-O"), chess_board.parse_uci_move("e1b1"));
+
+        let mut castled = chess_board;
+        castled.make_move(&castle);
+        assert_eq!(castled.to_fen(), "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R3RK1 b kq - 0 1");
+        let short = castled.parse_uci_move("e8g8").unwrap();
+        castled.make_move(&short);
+        assert_eq!(castled.to_fen(), "1r3rk1/pppppppp/8/8/8/8/PPPPPPPP/1R3RK1 w - - 0 2");
+
+        // and over uci, once the gui asks for it
+        let mut engine = UciEngine::new();
+        assert!(engine.handle("uci").contains(&"option name UCI_Chess960 type check default false".to_string()));
+        engine.handle("setoption name UCI_Chess960 value true");
+        engine.handle(&format!("position fen {} moves e1g1 e8b8", fen));
+        assert_eq!(engine.board().to_fen(), "2kr2r1/pppppppp/8/8/8/8/PPPPPPPP/1R3RK1 w - - 0 2");
+
+        engine.handle("position startpos moves g1f3 g8f6 g2g3 g7g6 f1g2 f8g7");
+        let castle = engine.board().castling_moves()[0];
+        assert_eq!(engine.board().move_to_uci(&castle), "e1h1");
+
+        engine.handle("setoption name UCI_Chess960 value false");
+        assert!(!engine.board().is_chess960());
+        let castle = engine.board().castling_moves()[0];
+        assert_eq!(engine.board().move_to_uci(&castle), "e1g1");
+    }
+
+    fn variant_board(color: ChessPieceColor, fen: &str, variant: Variant) -> ChessBoard {
+        let mut chess_board = ChessBoard::from_fen(color, fen).expect("valid fen");
+        chess_board.set_variant(variant);
+        chess_board
+    }
+
+    #[test]
+    fn variant_perft() {
+        // reference numbers from other variant engines
+        let suite = [
+            (Variant::Horde, HORDE_FEN, vec![8, 128, 1274, 23310]),
+            (Variant::Atomic, START_FEN, vec![20, 400, 8902]),
+            (Variant::Atomic, "rn2kb1r/1pp1p2p/p2q1pp1/3P4/2P3b1/4PN2/PP3PPP/R2QKB1R b KQkq - 0 1", vec![40, 1238, 45237]),
+            (Variant::Atomic, "rn1qkb1r/p5pp/2p5/3p4/N3P3/5P2/PPP4P/R1BQK3 w Qkq - 0 1", vec![28, 833, 23353]),
+            (Variant::KingOfTheHill, START_FEN, vec![20, 400, 8902]),
+            (Variant::ThreeCheck, START_FEN, vec![20, 400, 8902]),
+            // stepping onto d4 or e4 ends the game, black only answers the
+            // other six king moves
+            (Variant::KingOfTheHill, "4k3/8/8/8/8/4K3/8/8 w - - 0 1", vec![8, 30, 240, 1453, 10616]),
+            (Variant::KingOfTheHill, "8/8/8/2k5/8/3K4/8/8 w - - 0 1", vec![6, 36, 211, 1506, 9277]),
+            // the rook's check is the third one, so black never answers it
+            (Variant::ThreeCheck, "4k3/8/8/8/8/8/8/R3K3 w - - 1+3 0 1", vec![15, 65, 1185, 6519]),
+            // a check left each, kiwipete is 14 short of the usual 97862
+            (Variant::ThreeCheck, "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 1+1 0 1", vec![48, 2039, 97848]),
+            (Variant::ThreeCheck, "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 1+1 0 1", vec![26, 562, 13410, 302770])
+        ];
+
+        for (variant, fen, counts) in suite {
+            for color in [ChessPieceColor::White, ChessPieceColor::Black] {
+                let chess_board = variant_board(color, fen, variant);
+                for (depth, count) in counts.iter().enumerate() {
+                    assert_eq!(chess_board.perft(depth as u8 + 1), *count, "{:?} {}", variant, fen);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn variant_three_check_fen_counts() {
+        // the three-check counts go through fen, either way they're written
+        let three_check = ChessBoard::from_fen(ChessPieceColor::White, "4k3/8/8/8/8/8/8/R3K3 w - - 0 1 +2+0").unwrap();
+        assert_eq!(three_check.variant(), Variant::ThreeCheck);
+        assert_eq!(three_check.to_fen(), "4k3/8/8/8/8/8/8/R3K3 w - - 1+3 0 1");
+
+        let mut checked = three_check;
+        checked.make_move(&checked.parse_uci_move("a1a8").unwrap());
+        assert_eq!(checked.checks_given(ChessPieceColor::White), 3);
+        assert!(checked.legal_moves().is_empty());
+        assert_eq!(checked.variant_outcome(), Some((GameResult::WhiteWins, "three checks")));
+    }
+
+    #[test]
+    fn variant_atomic_explosions() {
+        // atomic captures blow up the neighbourhood but leave pawns standing
+        let mut explosion = variant_board(ChessPieceColor::White, "4k3/8/3q4/3pnb2/8/5N2/8/4K3 w - - 0 1", Variant::Atomic);
+        explosion.make_move(&explosion.parse_uci_move("f3e5").unwrap());
+        assert_eq!(explosion.to_fen(), "4k3/8/8/3p4/8/8/8/4K3 b - - 0 1");
+
+        // the king can't capture, and taking next to the enemy king wins
+        let atomic = variant_board(ChessPieceColor::White, "4k3/4q3/8/8/8/8/4Q3/4K3 w - - 0 1", Variant::Atomic);
+        assert!(atomic.parse_uci_move("e2e7").is_some());
+        let mut exploded = atomic;
+        exploded.make_move(&atomic.parse_uci_move("e2e7").unwrap());
+        assert_eq!(exploded.variant_outcome(), Some((GameResult::WhiteWins, "king exploded")));
+        let touching = variant_board(ChessPieceColor::White, "8/8/8/8/8/8/3kq3/3K4 w - - 0 1", Variant::Atomic);
+        assert!(!touching.in_check(ChessPieceColor::White));
+        assert!(touching.parse_uci_move("d1e2").is_none());
+    }
+
+    #[test]
+    fn variant_horde_rules() {
+        // the horde loses once every white piece is gone
+        let horde = variant_board(ChessPieceColor::White, "4k3/8/8/8/8/8/8/8 w - - 0 1", Variant::Horde);
+        assert_eq!(horde.variant_outcome(), Some((GameResult::BlackWins, "horde destroyed")));
+        let first_rank = variant_board(ChessPieceColor::Black, "4k3/8/8/8/8/8/8/P7 w - - 0 1", Variant::Horde);
+        assert!(first_rank.parse_uci_move("a1a3").is_some());
+    }
+
+    #[test]
+    fn variant_king_of_the_hill_search() {
+        // the search goes for the hill
+        let hill = variant_board(ChessPieceColor::White, "4k3/8/8/8/8/4K3/8/8 w - - 0 1", Variant::KingOfTheHill);
+        let result = Searcher::new(SearchConfig::default()).search(&hill, 3);
+        assert_eq!(result.score, MATE - 1);
+        assert!(["e3d4", "e3e4"].contains(&hill.move_to_uci(&result.best_move.unwrap()).as_str()));
+    }
+
+    #[test]
+    fn variant_pgn_tag() {
+        // pgn keeps the variant in its tag
+        let mut game = PgnGame::new(ChessBoard::new_variant(ChessPieceColor::White, Variant::Horde));
+        game.push(game.board().parse_san("b6").unwrap());
+        let text = game.to_pgn();
+        assert!(text.contains("[Variant \"Horde\"]"));
+        let read = PgnGame::parse(ChessPieceColor::Black, &text).unwrap();
+        assert_eq!(read.board().variant(), Variant::Horde);
+        assert_eq!(read.board().to_fen(), game.board().to_fen());
+    }
+
+    #[test]
+    fn variant_uci_option() {
+        // uci picks the variant from UCI_Variant
+        let mut engine = UciEngine::new();
+        assert!(engine.handle("uci").iter().any(|line| line.starts_with("option name UCI_Variant") && line.contains("var 3check")));
+        engine.handle("setoption name UCI_Variant value atomic");
+        engine.handle("position startpos moves g1f3 d7d5 f3e5 e7e6 e5f7");
+        assert_eq!(engine.board().variant_outcome(), Some((GameResult::WhiteWins, "king exploded")));
+    }
+
+    #[test]
+    fn board_geometry() {
+        let capablanca = ChessBoard::from_fen_sized(ChessPieceColor::White, CAPABLANCA_FEN).expect("valid fen");
+        assert_eq!((capablanca.files(), capablanca.ranks()), (10, 8));
+        assert!(!capablanca.is_orthodox());
+        assert!(ChessBoard::from_fen(ChessPieceColor::White, CAPABLANCA_FEN).is_err());
+        assert_eq!(capablanca.to_fen(), CAPABLANCA_FEN);
+        assert_eq!(capablanca.parse_square("j1"), Some(V2 { x: 9, y: 7 }));
+        assert_eq!(capablanca.parse_square("k1"), None);
+
+        for color in [ChessPieceColor::White, ChessPieceColor::Black] {
+            let chess_board = ChessBoard::from_fen_sized(color, CAPABLANCA_FEN).unwrap();
+            for (depth, count) in [28, 784, 25228].iter().enumerate() {
+                assert_eq!(chess_board.perft(depth as u8 + 1), *count);
+            }
+        }
+
+        // castling puts the king on the i or c file
+        let castles = ChessBoard::from_fen_sized(ChessPieceColor::White, "r4k3r/10/10/10/10/10/10/R4K3R w KQkq - 0 1").unwrap();
+        let mut castled = castles;
+        castled.make_move(&castles.parse_san("O-O").unwrap());
+        assert_eq!(castled.to_fen(), "r4k3r/10/10/10/10/10/10/R6RK1 b kq - 0 1");
+
+        // fairy pieces combine the knight's leaps with a rider's lines
+        for (fen, moves) in [("8/8/8/8/3A4/8/8/k6K w - - 0 1", 21), ("8/8/8/8/3C4/8/8/k6K w - - 0 1", 22), ("8/8/8/8/3M4/8/8/k6K w - - 0 1", 35)] {
+            let chess_board = ChessBoard::from_fen(ChessPieceColor::White, fen).unwrap();
+            let src = chess_board.parse_square("d4").unwrap();
+            assert_eq!(chess_board.legal_moves().iter().filter(|chess_move| chess_move.src() == src).count(), moves, "{}", fen);
+        }
+
+        let checked = ChessBoard::from_fen(ChessPieceColor::White, "4k3/8/3A4/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(checked.in_check(ChessPieceColor::Black));
+        assert_eq!(checked.move_to_san(&checked.legal_moves()[0]).len(), 3);
+
+        // tall boards have two digit ranks
+        let tall = ChessBoard::from_fen_sized(ChessPieceColor::White, "k9/10/10/10/10/10/10/10/P9/9K w - - 0 1").unwrap();
+        assert_eq!(tall.square_name(&tall.parse_square("a10").unwrap()), "a10");
+        assert!(tall.parse_uci_move("a2a4").is_some());
+        assert!(tall.render(&RenderOptions::default()).starts_with("10  k"));
+
+        // and short ones have no room for a double step
+        assert!(ChessBoard::from_fen_sized(ChessPieceColor::White, "k7/K7 w - a1 0 1").is_err());
+        assert!(ChessBoard::from_fen_sized(ChessPieceColor::White, "k7/8/K7 b - a2 0 1").is_err());
+    }
+
+    #[test]
+    fn fairy_promotions() {
+        // a pawn can become a fairy piece the board has, here a chancellor
+        let chess_board = ChessBoard::from_fen_sized(ChessPieceColor::White, "4k4c/1P8/10/10/10/10/10/4K5 w - - 0 1").unwrap();
+        let src = chess_board.parse_square("b7").unwrap();
+        assert_eq!(chess_board.legal_moves().iter().filter(|chess_move| chess_move.src() == src).count(), 5);
+        assert!(chess_board.parse_uci_move("b7b8a").is_none());
+
+        let promotion = chess_board.parse_uci_move("b7b8c").unwrap();
+        assert_eq!(promotion.promotion(), Some(ChessPiece::Chancellor));
+        assert_eq!(promotion.ext(), ChessMoveExt::QueenPromotion);
+        assert_eq!(chess_board.move_to_san(&promotion), "b8=C+");
+        assert_eq!(chess_board.parse_san("b8=C"), Some(promotion));
+        assert!(promotion.0 < 1 << 24);
+
+        let mut promoted = chess_board;
+        promoted.make_move(&promotion);
+        assert_eq!(promoted.to_fen(), "1C2k4c/10/10/10/10/10/10/4K5 b - - 0 1");
+
+        // without one on the board it's the usual four
+        let orthodox = ChessBoard::from_fen(ChessPieceColor::White, "4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(orthodox.legal_moves().iter().filter(|chess_move| chess_move.promotion().is_some()).count(), 4);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trips() {
+        // json uses names and notation
+        assert_eq!(serde_json::to_string(&ChessPiece::Chancellor).unwrap(), "\"chancellor\"");
+        assert_eq!(serde_json::to_string(&ChessPieceColor::Black).unwrap(), "\"black\"");
+        assert_eq!(serde_json::to_string(&V2 { x: 4, y: 6 }).unwrap(), "[4,6]");
+
+        let mut chess_board = ChessBoard::new(ChessPieceColor::Black);
+        let e4 = chess_board.parse_uci_move("e2e4").unwrap();
+        assert_eq!(serde_json::to_string(&e4).unwrap(), "{\"from\":[4,1],\"to\":[4,3],\"kind\":\"double_pawn_push\"}");
+        assert_eq!(serde_json::from_str::<ChessMove>(&serde_json::to_string(&e4).unwrap()).unwrap(), e4);
+        let wide = ChessBoard::from_fen_sized(ChessPieceColor::White, "4k4c/1P8/10/10/10/10/10/4K5 w - - 0 1").unwrap();
+        let chancellor = wide.parse_uci_move("b7b8c").unwrap();
+        let json = serde_json::to_string(&chancellor).unwrap();
+        assert_eq!(json, "{\"from\":[1,1],\"to\":[1,0],\"kind\":\"queen_promotion\",\"piece\":\"chancellor\"}");
+        assert_eq!(serde_json::from_str::<ChessMove>(&json).unwrap(), chancellor);
+        assert_eq!(postcard::from_bytes::<ChessMove>(&postcard::to_allocvec(&chancellor).unwrap()).unwrap(), chancellor);
+
+        chess_board.make_move(&e4);
+
+        let json = serde_json::to_string(&chess_board).unwrap();
+        assert!(json.contains("\"fen\":\"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1\""));
+        let read = serde_json::from_str::<ChessBoard>(&json).unwrap();
+        assert_eq!((read.to_fen(), read.hash()), (chess_board.to_fen(), chess_board.hash()));
+
+        let tile = chess_board.tile(&e4.dst());
+        assert_eq!(serde_json::to_string(&tile).unwrap(), "{\"piece\":\"pawn\",\"color\":\"white\",\"moved\":true,\"promoted\":false}");
+        assert_eq!(serde_json::from_str::<ChessTile>("null").unwrap(), ChessTile::new(None, None).unwrap());
+
+        // a bare fen is a position with white at the bottom
+        let atomic = serde_json::from_str::<ChessBoard>(&format!("{{\"fen\":\"{}\",\"variant\":\"atomic\"}}", START_FEN)).unwrap();
+        assert_eq!(atomic.variant(), Variant::Atomic);
+        assert_eq!(serde_json::from_str::<ChessBoard>(&format!("\"{}\"", START_FEN)).unwrap(), ChessBoard::new(ChessPieceColor::White));
+        assert!(serde_json::from_str::<ChessBoard>("\"8/8 w\"").is_err());
+
+        // binary forms keep every bit, even what fen leaves out
+        let zh = ChessBoard::new_variant(ChessPieceColor::White, Variant::Crazyhouse);
+        let mut played = zh;
+        for uci in ["e2e4", "d7d5", "e4d5", "d8d5", "g1f3"] {
+            played.make_move(&played.parse_uci_move(uci).unwrap());
+        }
+
+        let capablanca = ChessBoard::from_fen_sized(ChessPieceColor::Black, CAPABLANCA_FEN).unwrap();
+        for chess_board in [played, capablanca, ChessBoard::chess960(ChessPieceColor::White, 0).unwrap()] {
+            let bytes = postcard::to_allocvec(&chess_board).unwrap();
+            assert_eq!(postcard::from_bytes::<ChessBoard>(&bytes).unwrap(), chess_board);
+
+            let json = serde_json::to_string(&chess_board).unwrap();
+            let read = serde_json::from_str::<ChessBoard>(&json).unwrap();
+            assert_eq!((read.to_fen(), read.hash(), read.variant()), (chess_board.to_fen(), chess_board.hash(), chess_board.variant()));
+        }
+
+        for chess_move in played.legal_moves() {
+            let bytes = postcard::to_allocvec(&chess_move).unwrap();
+            assert_eq!(postcard::from_bytes::<ChessMove>(&bytes).unwrap(), chess_move);
+            assert_eq!(serde_json::from_str::<ChessMove>(&serde_json::to_string(&chess_move).unwrap()).unwrap(), chess_move);
+        }
+
+        // games come back move for move, with their tags in order
+        let mut game = PgnGame::new(zh);
+        game.set_tag("White", "Ann");
+        game.set_tag("Annotator", "Bea");
+        for uci in ["e2e4", "d7d5", "e4d5", "d8d5", "g1f3", "d5d8", "P@e5"] {
+            game.push(game.board().parse_uci_move(uci).unwrap());
+        }
+
+        let json = serde_json::to_string(&game).unwrap();
+        assert!(json.contains("\"moves\":[\"e2e4\",\"d7d5\",\"e4d5\",\"d8d5\",\"g1f3\",\"d5d8\",\"P@e5\"]"));
+        assert_eq!(serde_json::from_str::<PgnGame>(&json).unwrap(), game);
+        assert_eq!(postcard::from_bytes::<PgnGame>(&postcard::to_allocvec(&game).unwrap()).unwrap(), game);
+
+        let illegal = json.replace("g1f3", "g1g3");
+        assert!(serde_json::from_str::<PgnGame>(&illegal).is_err());
+    }
+
+    #[test]
+    fn crazyhouse_drops() {
+        // kings and a full pocket each: every piece on the 62 empty tiles,
+        // pawns on the 48 between the back ranks, and five king moves
+        let pockets = ChessBoard::from_fen(ChessPieceColor::White, "2k5/8/8/8/8/8/8/4K3[QRBNPqrbnp] w - - 0 1").unwrap();
+        assert_eq!(pockets.variant(), Variant::Crazyhouse);
+        assert_eq!(pockets.perft(1), 4 * 62 + 48 + 5);
+        assert_eq!(pockets.perft(2), 75353);
+
+        // the ninth rank form reads the same
+        let ninth = ChessBoard::from_fen(ChessPieceColor::Black, "2k5/8/8/8/8/8/8/4K3/QRBNPqrbnp w - - 0 1").unwrap();
+        assert_eq!(ninth.to_fen(), pockets.to_fen());
+        assert!(ninth.parse_uci_move("P@e8").is_none());
+
+        let mut game = PgnGame::new(ChessBoard::new_variant(ChessPieceColor::White, Variant::Crazyhouse));
+        for san in ["e4", "d5", "exd5", "Qxd5", "@e6", "fxe6", "Nc3", "Qa5", "Nf3", "P@e4"] {
+            let board = game.board();
+            let chess_move = board.parse_san(san).unwrap_or_else(|| panic!("{} should be legal", san));
+            game.push(chess_move);
+        }
+
+        let board = game.board();
+        assert_eq!(board.to_fen(), "rnb1kbnr/ppp1p1pp/4p3/q7/4p3/2N2N2/PPPP1PPP/R1BQKB1R[p] w KQkq - 0 6");
+        assert_eq!(board.pocket(ChessPieceColor::Black, ChessPiece::Pawn), 1);
+        assert_eq!(board.pocket(ChessPieceColor::White, ChessPiece::Pawn), 0);
+        assert!(game.movetext().contains("3. P@e6 fxe6"));
+
+        let knight = board.parse_uci_move("f3e5").unwrap();
+        assert_eq!(board.move_to_san(&knight), "Ne5");
+        let text = game.to_pgn();
+        assert!(text.contains("[Variant \"Crazyhouse\"]") && !text.contains("[FEN"));
+        assert_eq!(PgnGame::parse(ChessPieceColor::Black, &text).unwrap().board().to_fen(), board.to_fen());
+
+        // a promoted piece is marked, and goes back into the pocket as a pawn
+        let mut promotion = ChessBoard::from_fen(ChessPieceColor::White, "r3k3/1Pn5/8/8/8/8/8/4K3[] w - - 0 1").unwrap();
+        promotion.make_move(&promotion.parse_san("bxa8=Q+").unwrap());
+        assert_eq!(promotion.to_fen(), "Q~3k3/2n5/8/8/8/8/8/4K3[R] b - - 0 1");
+        assert_eq!(ChessBoard::from_fen(ChessPieceColor::Black, &promotion.to_fen()).unwrap().to_fen(), promotion.to_fen());
+        promotion.make_move(&promotion.parse_san("Nxa8").unwrap());
+        assert_eq!(promotion.to_fen(), "n3k3/8/8/8/8/8/8/4K3[Rp] w - - 0 2");
+
+        // a drop can block a check, and the search knows about them
+        let check = ChessBoard::from_fen(ChessPieceColor::White, "4r2k/8/8/8/8/8/8/4K3[N] w - - 0 1").unwrap();
+        let blocks = check.legal_moves().into_iter().filter(|chess_move| chess_move.is_drop()).count();
+        assert_eq!(blocks, 6);
+        let result = Searcher::new(SearchConfig::default()).search(&check, 2);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn crazyhouse_fairy_pieces() {
+        // only the five pocket pieces can be in a pocket
+        let pocketed = "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR[A] w KQkq - 0 1";
+        assert!(ChessBoard::from_fen_sized(ChessPieceColor::White, pocketed).is_err());
+
+        // and a captured archbishop is gone rather than pocketed
+        let mut chess_board = ChessBoard::from_fen_sized(ChessPieceColor::White, "4k3/8/8/8/8/8/a7/R3K3[] w - - 0 1").unwrap();
+        assert_eq!(chess_board.variant(), Variant::Crazyhouse);
+        chess_board.make_move(&chess_board.parse_uci_move("a1a2").unwrap());
+        assert!(POCKET_PIECES.iter().all(|piece| chess_board.pocket(ChessPieceColor::White, *piece) == 0));
+        assert_eq!(chess_board.pocket(ChessPieceColor::White, ChessPiece::Archbishop), 0);
+        assert_eq!(chess_board.to_fen(), "4k3/8/8/8/8/8/R7/4K3[] b - - 0 1");
+    }
+
+    #[test]
+    fn packed_position_either_way_up() {
+        // a position packs the same whichever way up the board is
+        let start = ChessBoard::new(ChessPieceColor::White).pack().unwrap();
+        assert!(start.iter().any(|byte| *byte != 0));
+        assert_eq!(ChessBoard::new(ChessPieceColor::Black).pack().unwrap(), start);
+        assert_eq!(ChessBoard::unpack(ChessPieceColor::Black, &start).unwrap(), ChessBoard::new(ChessPieceColor::Black));
+    }
+
+    #[test]
+    fn packed_random_games() {
+        let mut state: u64 = 0x2545f4914f6cdd1d;
+        let mut random = move || xorshift(&mut state);
+
+        // random games from a few starts, every position on the way has to
+        // come back with the same fen and hash and the game move for move
+        let mut starts = vec![ChessBoard::new(ChessPieceColor::White), ChessBoard::chess960(ChessPieceColor::Black, 100).unwrap()];
+        for variant in [Variant::KingOfTheHill, Variant::ThreeCheck, Variant::Atomic] {
+            starts.push(ChessBoard::new_variant(ChessPieceColor::White, variant));
+        }
+
+        for start in starts {
+            for _ in 0..8 {
+                let mut chess_board = start;
+                let mut moves = vec![];
+
+                for _ in 0..80 {
+                    let packed = chess_board.pack().unwrap();
+                    let unpacked = ChessBoard::unpack(chess_board.player_color(), &packed).unwrap();
+                    assert_eq!(unpacked.to_fen(), chess_board.to_fen());
+                    assert_eq!((unpacked.hash(), unpacked.variant()), (chess_board.hash(), chess_board.variant()));
+                    assert_eq!(unpacked.legal_moves(), chess_board.legal_moves());
+
+                    let legal = chess_board.legal_moves();
+                    if legal.is_empty() {
+                        break;
+                    }
+
+                    let chess_move = legal[random() as usize % legal.len()];
+                    chess_board.make_move(&chess_move);
+                    moves.push(chess_move);
+                }
+
+                let bytes = start.pack_game(&moves).unwrap();
+                assert_eq!(bytes.len(), PACKED_SIZE + moves.len());
+                assert_eq!(ChessBoard::unpack_game(start.player_color(), &bytes).unwrap(), (start, moves.clone()));
+
+                // and the other way up, the same moves by name
+                let (mut flipped, other) = ChessBoard::unpack_game(start.player_color().opposite(), &bytes).unwrap();
+                let mut chess_board = start;
+                for (chess_move, flipped_move) in moves.iter().zip(&other) {
+                    assert_eq!(flipped.move_to_uci(flipped_move), chess_board.move_to_uci(chess_move));
+                    flipped.make_move(flipped_move);
+                    chess_board.make_move(chess_move);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packed_moves_two_byte_index() {
+        // more than 256 moves to choose from takes two bytes for the index
+        let zh = ChessBoard::from_fen(ChessPieceColor::White, "4k3/8/8/8/8/8/8/4K3[QRBNP] w - - 0 1").unwrap();
+        let drop = zh.parse_uci_move("P@e4").unwrap();
+        assert_eq!(zh.unpack_moves(&zh.pack_moves(&[drop]).unwrap()).unwrap(), vec![drop]);
+        assert_eq!(zh.pack_moves(&[drop]).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn packed_refuses_what_it_cant_hold() {
+        // short input, moves that aren't legal, and positions without room
+        // in 32 bytes: a horde, a wide board, or pieces in hand
+        let start = ChessBoard::new(ChessPieceColor::White).pack().unwrap();
+        assert!(ChessBoard::unpack(ChessPieceColor::White, &start[..31]).is_err());
+        assert!(ChessBoard::new(ChessPieceColor::White).pack_moves(&[ChessMove::raw(&V2 { x: 4, y: 6 }, &V2 { x: 4, y: 2 }, 0)]).is_err());
+        assert!(ChessBoard::from_fen(ChessPieceColor::White, HORDE_FEN).unwrap().pack().is_err());
+        assert!(ChessBoard::from_fen_sized(ChessPieceColor::White, CAPABLANCA_FEN).unwrap().pack().is_err());
+        assert!(ChessBoard::from_fen(ChessPieceColor::White, "4k3/8/8/8/8/8/8/4K3[QRBNP] w - - 0 1").unwrap().pack().is_err());
+    }
+
+    #[test]
+    fn packed_random_bytes() {
+        let mut state: u64 = 0x2545f4914f6cdd1d;
+        let mut random = move || xorshift(&mut state);
+
+        // random bytes either unpack or don't, and what unpacks packs again
+        for _ in 0..20000 {
+            let mut bytes = [0u8; PACKED_SIZE];
+            for chunk in bytes[..28].chunks_mut(8) {
+                chunk.copy_from_slice(&random().to_le_bytes()[..chunk.len()]);
+            }
+            // mostly fewer than 32 pieces and a state that reads, so the
+            // pieces get a look in
+            bytes[..8].copy_from_slice(&(random() & random()).to_le_bytes());
+            bytes[24] = (random() % 5) as u8 | (random() as u8 & 1) << 4;
+            bytes[25] = (random() % 9) as u8 | (random() as u8) << 4;
+
+            let Ok(chess_board) = ChessBoard::unpack(ChessPieceColor::White, &bytes) else {
+                continue;
+            };
+
+            let repacked = chess_board.pack().unwrap();
+            assert_eq!(ChessBoard::unpack(ChessPieceColor::White, &repacked).unwrap().to_fen(), chess_board.to_fen());
+        }
+
+        for _ in 0..2000 {
+            let bytes: Vec<u8> = (0..random() % 16).map(|_| random() as u8).collect();
+            let _ = ChessBoard::new(ChessPieceColor::White).unpack_moves(&bytes);
+        }
+    }
+
+    const DATABASE_PGN: &str = r#"
+[White "Ann"]
+[Black "Bea"]
+[ECO "C42"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nf6 3. Nxe5 d6 4. Nf3 Nxe4 1-0
+
+[White "Bea"]
+[Black "Ann"]
+[Result "1/2-1/2"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 1/2-1/2
+
+[White "Ann"]
+[Black "Cat"]
+[Result "0-1"]
+
+1. Nf3 e5 2. e4 Nc6 0-1
+
+[White "Cat"]
+[Black "Ann"]
+[Result "*"]
+
+1. d4 d5 *
+
+[White "Ann"]
+[Black "Bea"]
+[FEN "4k3/8/8/8/8/8/4P3/R3K3 w Q - 0 1"]
+[SetUp "1"]
+[Result "1-0"]
+
+1. e4 Kd7 2. e5 Ke6 3. Ra6+ Kxe5 1-0
+"#;
+
+
+    fn database() -> GameDatabase {
+        let mut database = GameDatabase::new(ChessPieceColor::White);
+        assert_eq!(database.import_pgn(DATABASE_PGN).unwrap(), 5);
+        database
+    }
+
+    // after 1. e4 e5 2. Nf3
+    fn after_nf3() -> ChessBoard {
+        let mut after = ChessBoard::new(ChessPieceColor::White);
+        for uci in ["e2e4", "e7e5", "g1f3"] {
+            after.make_move(&after.parse_uci_move(uci).unwrap());
+        }
+        after
+    }
+
+    #[test]
+    fn database_import() {
+        let mut database = database();
+        assert!(database.import_pgn("1. e4 e5 2. Ke3 *").is_err());
+        assert_eq!(database.len(), 5);
+    }
+
+    #[test]
+    fn database_games_reaching() {
+        // 1. e4 e5 2. Nf3 and 1. Nf3 e5 2. e4 are the same position
+        let database = database();
+        assert_eq!(database.games_reaching(&after_nf3()), vec![0, 1, 2]);
+        assert_eq!(database.games_reaching(&ChessBoard::new(ChessPieceColor::White)), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn database_move_stats() {
+        let database = database();
+        let after = after_nf3();
+
+        // what black played from there and how it went, from either side
+        let stats = database.move_stats(&after);
+        let nc6 = MoveStats { chess_move: after.parse_uci_move("b8c6").unwrap(), games: 2, white_wins: 0, draws: 1, black_wins: 1 };
+        let nf6 = MoveStats { chess_move: after.parse_uci_move("g8f6").unwrap(), games: 1, white_wins: 1, draws: 0, black_wins: 0 };
+        assert_eq!(stats, vec![nc6, nf6]);
+        assert_eq!(stats[0].score(ChessPieceColor::Black), Some(0.75));
+
+        let flipped = ChessBoard::from_fen(ChessPieceColor::Black, &after.to_fen()).unwrap();
+        let stats = database.move_stats(&flipped);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(flipped.move_to_uci(&stats[0].chess_move), "b8c6");
+
+        let start = database.move_stats(&ChessBoard::new(ChessPieceColor::White));
+        assert_eq!(start.iter().map(|stats| stats.games).collect::<Vec<_>>(), vec![2, 1, 1]);
+        assert_eq!(start[2].black_wins + start[2].white_wins + start[2].draws, 0);
+    }
+
+    #[test]
+    fn database_search() {
+        let database = database();
+        let after = after_nf3();
+
+        // games where ann had white and reached the position
+        let query = GameQuery { position: Some(after), tags: vec![("White".to_string(), "Ann".to_string())], ..Default::default() };
+        assert_eq!(database.search(&query), vec![0, 2]);
+        let query = GameQuery { result: Some(GameResult::WhiteWins), ..query };
+        assert_eq!(database.search(&query), vec![0]);
+        let query = GameQuery { tags: vec![("ECO".to_string(), "C42".to_string())], ..Default::default() };
+        assert_eq!(database.search(&query), vec![0]);
+    }
+
+    #[test]
+    fn database_material() {
+        let database = database();
+
+        // material seen at any point, letters in any order
+        assert_eq!(material_signature(&database.game(4).unwrap().board()), "KRvK");
+        assert_eq!(database.games_with_material("KRvK"), vec![4]);
+        assert_eq!(database.games_with_material("KRPvK"), vec![4]);
+        assert_eq!(database.games_with_material("KPRvK"), vec![4]);
+        assert!(database.games_with_material("KXvK").is_empty());
+        let query = GameQuery { material: Some("KRvK".to_string()), position: Some(after_nf3()), ..Default::default() };
+        assert!(database.search(&query).is_empty());
+    }
+
+    #[test]
+    fn database_save_and_load() {
+        // saved and loaded the other way up, the games and the index come back
+        let database = database();
+        let after = after_nf3();
+        let directory = std::env::temp_dir().join(format!("chess-engine-database-{}", std::process::id()));
+        fs::create_dir_all(&directory).expect("create temp directory");
+        let path = directory.join("games.cegd");
+        database.save(&path).unwrap();
+
+        let loaded = GameDatabase::load(ChessPieceColor::Black, &path).unwrap();
+        assert_eq!(loaded.len(), database.len());
+        for (game, other) in loaded.games().iter().zip(database.games()) {
+            assert_eq!(game.to_pgn(), other.to_pgn());
+        }
+        assert_eq!(loaded.games_reaching(&after), vec![0, 1, 2]);
+        assert_eq!(loaded.move_stats(&after), database.move_stats(&after));
+        assert_eq!(loaded.games_with_material("KRvK"), vec![4]);
+
+        let reloaded = GameDatabase::from_bytes(ChessPieceColor::White, &loaded.to_bytes()).unwrap();
+        assert_eq!(reloaded.to_bytes(), database.to_bytes());
+
+        fs::remove_dir_all(&directory).expect("remove temp directory");
+    }
+
+    #[test]
+    fn database_keeps_variants_as_fen() {
+        let mut database = database();
+
+        // variants that don't pack are kept as fen
+        let mut zh = PgnGame::new(ChessBoard::new_variant(ChessPieceColor::White, Variant::Crazyhouse));
+        for uci in ["e2e4", "d7d5", "e4d5", "d8d5", "P@e4"] {
+            zh.push(zh.board().parse_uci_move(uci).unwrap());
+        }
+        database.add(zh.clone()).unwrap();
+        let loaded = GameDatabase::from_bytes(ChessPieceColor::White, &database.to_bytes()).unwrap();
+        assert_eq!(loaded.game(5), Some(&zh));
+        assert_eq!(loaded.games_reaching(&zh.board()), vec![5]);
+    }
+
+    #[test]
+    fn database_rejects_bad_games_and_bytes() {
+        let mut database = database();
+
+        // a game with a move that isn't legal isn't taken
+        let mut illegal = PgnGame::new(ChessBoard::new(ChessPieceColor::White));
+        illegal.push(ChessMove::raw(&V2 { x: 4, y: 6 }, &V2 { x: 4, y: 2 }, 0));
+        assert!(database.add(illegal).is_err());
+        assert_eq!(database.len(), 5);
+
+        let bytes = database.to_bytes();
+        assert!(GameDatabase::from_bytes(ChessPieceColor::White, &bytes[..bytes.len() - 1]).is_err());
+        let mut bad_index = bytes.clone();
+        let last = bad_index.len() - 4;
+        bad_index[last..].copy_from_slice(&5u32.to_le_bytes());
+        assert!(GameDatabase::from_bytes(ChessPieceColor::White, &bad_index).is_err());
+        assert!(GameDatabase::from_bytes(ChessPieceColor::White, b"CETB\x00\x00\x00\x00").is_err());
+    }
 }
@@ -1,20 +1,30 @@
 use crate::pieces::{ChessPiece};
 use crate::board::{ChessBoard, ChessTile};
-use crate::math::{search_grid_plus, search_grid_diag, V2};
+use crate::math::{search_ray, V2};
+use crate::variant::Variant;
 
 #[allow(dead_code)]
 #[derive(Copy, Clone, Debug, PartialEq)]
-pub struct ChessMove (pub u16);
+pub struct ChessMove (pub u32);
 
 #[allow(dead_code)]
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ChessMoveExt {
-    Quiet,
-    DoublePawnPush,
-    KingCastle,
-    QueenCastle,
-    Captures,
-    EpCapture
+    Quiet = 0,
+    DoublePawnPush = 1,
+    KingCastle = 2,
+    QueenCastle = 3,
+    Captures = 4,
+    EpCapture = 5,
+    Drop = 6,
+    KnightPromotion = 8,
+    BishopPromotion = 9,
+    RookPromotion = 10,
+    QueenPromotion = 11,
+    KnightPromoCapture = 12,
+    BishopPromoCapture = 13,
+    RookPromoCapture = 14,
+    QueenPromoCapture = 15
 }
 
 #[allow(dead_code)]
@@ -25,127 +35,99 @@ impl ChessMove {
         // the weird pawn rules
 
         let tile: ChessTile = board.tile(src);
-        
-        let direction: i8 = match Some(board.player_color()) == tile.color() {
-            true => -1,
-            false => 1,
-        };
 
-        let one_step = i8::try_from(src.y).unwrap() + direction;
-
-        let mut moves = match tile.has_moved() {
-            true => vec![V2 { x: src.x, y: u16::try_from(one_step).unwrap() }],
-            false => {
-                let two_step = i8::try_from(src.y).unwrap() + direction * 2;
-                vec![
-                    V2 { x: src.x, y: u16::try_from(one_step).unwrap() },
-                    V2 { x: src.x, y: u16::try_from(two_step).unwrap() } 
-                ]
-            }
+        let color = match tile.color() {
+            Some(color) => color,
+            None => return vec![]
         };
 
-        // exclude spots where there are pieces already
-        // get the furthest spot first and remove it 
-        for _ in 0..2 {
-            if board.tile(moves.last().unwrap()).color() != None {
-                moves.pop();
-            }
-
-            if moves.len() == 0 {
-                break;
-            }
-        }
+        let direction = board.forward(color);
+        let mut moves = vec![];
 
-        let horizontal = [
-            i8::try_from(src.x).unwrap() - 1, 
-            i8::try_from(src.x).unwrap() + 1
-        ];
+        // pushes only go onto empty tiles, and the double step needs both
+        // tiles clear and the pawn still on its starting rank
+        if let Some(one_step) = board.offset(src, 0, direction) {
+            if board.tile(&one_step).piece().is_none() {
+                moves.push(one_step);
 
-        // find diagonals where the peice can attack
-        for x in horizontal {
-            if x >= 0 && x < 8 {
-                let v2 = V2 { 
-                    x: u16::try_from(x).unwrap(),
-                    y: u16::try_from(one_step).unwrap()
+                let start_rank = match direction {
+                    -1 => board.ranks() - 2,
+                    _ => 1
                 };
 
-                let color = board.tile(&v2).color();
+                // horde pawns on the first rank get a double step as well
+                let horde = board.variant() == Variant::Horde && src.y == board.home_row(color);
 
-                if color != None && tile.color() != color {
-                    moves.push(v2);
+                if let Some(two_step) = board.offset(src, 0, direction * 2) {
+                    if (src.y == start_rank || horde) && board.tile(&two_step).piece().is_none() {
+                        moves.push(two_step);
+                    }
                 }
             }
         }
 
-        moves
-    }
+        // find diagonals where the peice can attack, either an enemy piece or
+        // the tile an enemy pawn just skipped over
+        for x in [-1, 1] {
+            if let Some(v2) = board.offset(src, x, direction) {
+                let target = board.tile(&v2).color();
+                let victim = board.tile(&V2 { x: v2.x, y: src.y });
 
-    fn knight_moves(src: &V2, board: ChessBoard) -> Vec<V2> {
-        
-        let tile_color = board.tile(src).color();
+                let en_passant = board.en_passant() == Some(v2)
+                    && victim.piece() == Some(ChessPiece::Pawn)
+                    && victim.color() != tile.color();
 
-        let moves = vec![
-            V2::get_offset(src, -1,  2), V2::get_offset(src, 1,  2),
-            V2::get_offset(src, -2,  1), V2::get_offset(src, 2,  1),
-            V2::get_offset(src, -1, -2), V2::get_offset(src, 1, -2),
-            V2::get_offset(src, -2, -1), V2::get_offset(src, 2, -1)
-        ];
-
-        moves.iter()
-            .filter(|valid_move| {
-                match valid_move {
-                    Some(valid_move) => board.tile(valid_move).color() != tile_color,
-                    None => false
+                if (target.is_some() && target != tile.color()) || en_passant {
+                    moves.push(v2);
                 }
-            })
-            .map(|valid_move| valid_move.unwrap())
-            .collect()
-    }
+            }
+        }
 
-    fn queen_moves(src: &V2, board: ChessBoard) -> Vec<V2> {
-        // just merge the plus and diagonal search
-        search_grid_plus(src, board).into_iter()
-            .chain(search_grid_diag(src, board).into_iter())
-            .collect()
+        moves
     }
 
-    fn king_moves(src: &V2, board: ChessBoard) -> Vec<V2> {
+    // any piece but the pawn, from its leaps and rides. an atomic king
+    // would blow itself up capturing, so it only steps onto empty tiles
+    fn piece_moves(src: &V2, piece: ChessPiece, board: ChessBoard) -> Vec<V2> {
         let tile_color = board.tile(src).color();
+        let movement = piece.movement();
+        let captures = piece != ChessPiece::King || board.variant() != Variant::Atomic;
         let mut moves = vec![];
 
-        for y in src.y - 1..src.y + 1 {
-            for x in src.x - 1..src.x + 1 {
-                let dst = V2::from_i8(x as i8, y as i8);
-                if dst.is_some() && dst != Some(*src)  {
-                    let valid_dst = dst.unwrap();
-                    if board.tile(&valid_dst).color() != tile_color {
-                        moves.push(dst.unwrap());
-                    }
+        for (x, y) in movement.leaps {
+            if let Some(dst) = board.offset(src, *x, *y) {
+                let target = board.tile(&dst).color();
+                if target != tile_color && (captures || target.is_none()) {
+                    moves.push(dst);
                 }
             }
         }
 
+        for direction in movement.rides {
+            moves.extend(search_ray(src, *direction, &board).into_iter()
+                .filter(|dst| board.tile(dst).color() != tile_color));
+        }
+
         moves
     }
 
-    fn illegal_move(src: &V2, dst: &V2, board: ChessBoard) -> bool {
-        // check for each type of piece
-        let valid_moves = match board.tile(src).piece() {
+    pub(crate) fn destinations(src: &V2, board: ChessBoard) -> Vec<V2> {
+        match board.tile(src).piece() {
             Some(ChessPiece::Pawn) => Self::pawn_moves(src, board),
-            Some(ChessPiece::Rook) => search_grid_plus(src, board),
-            Some(ChessPiece::Knight) => Self::knight_moves(src, board),
-            Some(ChessPiece::Bishop) => search_grid_diag(src, board),
-            Some(ChessPiece::Queen) => Self::queen_moves(src, board),
-            Some(ChessPiece::King) => Self::king_moves(src, board),
+            Some(piece) => Self::piece_moves(src, piece, board),
             None => vec![] // cant move an empty space
-        };
+        }
+    }
+
+    fn illegal_move(src: &V2, dst: &V2, board: ChessBoard) -> bool {
+        let valid_moves = Self::destinations(src, board);
 
         // if the coordinate is included, it's valid.
         // invert the valid signal to return the correct value
         !valid_moves.iter().any(|valid_move| valid_move == dst)
     }
 
-    fn get_ext(src: &V2, dst: &V2, board: ChessBoard) -> ChessMoveExt {
+    pub(crate) fn get_ext(src: &V2, dst: &V2, board: ChessBoard) -> ChessMoveExt {
         let tiles = board.tile_pair(src, dst);
 
         // check to see if the pawn has been moved two spots in any y direction
@@ -154,20 +136,51 @@ impl ChessMove {
         let src_y = i16::try_from(src.y).unwrap();
         let dst_y = i16::try_from(dst.y).unwrap();
 
+        let is_pawn = tiles.0.piece() == Some(ChessPiece::Pawn);
+        let captures = tiles.1.piece().is_some() && tiles.0.color() != tiles.1.color();
+
         // if a pawn moves twice
-        if tiles.0.piece() == Some(ChessPiece::Pawn) && (src_y - dst_y).abs() == 2 {
+        if is_pawn && (src_y - dst_y).abs() == 2 {
             return ChessMoveExt::DoublePawnPush
         }
 
+        // a pawn reaching the far edge promotes, to a queen unless the caller
+        // picks something else
+        if is_pawn && (dst.y == 0 || dst.y == board.ranks() - 1) {
+            return ChessMoveExt::promotion(ChessPiece::Queen, captures)
+        }
+
+        // the king stepping two files is castling, the rook comes along in
+        // make_move
+        if tiles.0.piece() == Some(ChessPiece::King) && dst.x == src.x + 2 {
+            return ChessMoveExt::KingCastle
+        }
+
+        if tiles.0.piece() == Some(ChessPiece::King) && src.x == dst.x + 2 {
+            return ChessMoveExt::QueenCastle
+        }
+
         // if a peice gets capped
-        if tiles.1.piece() != None && tiles.0.color() != tiles.1.color() {
+        if captures {
             return ChessMoveExt::Captures
         }
 
+        // a pawn moving sideways onto an empty tile took en passant
+        if is_pawn && src.x != dst.x {
+            return ChessMoveExt::EpCapture
+        }
+
         ChessMoveExt::Quiet
     }
 
     pub fn new(src: &V2, dst: &V2, board: ChessBoard) -> Result<Self, &'static str> {
+        let castle = board.castling_moves().into_iter()
+            .find(|castle| castle.src() == *src && castle.dst() == *dst);
+
+        if let Some(castle) = castle {
+            return Ok(castle)
+        }
+
         if Self::illegal_move(src, dst, board) {
             return Err("illegal move")
         }
@@ -177,20 +190,66 @@ impl ChessMove {
         Ok(Self::raw(src, dst, ext))
     }
 
+    // a crazyhouse drop. there's no source tile, the piece is kept where
+    // its file would be
+    pub fn drop(piece: ChessPiece, dst: &V2) -> Self {
+        Self::raw(&V2 { x: piece as u16, y: 0 }, dst, ChessMoveExt::Drop as u16)
+    }
+
+    pub fn dropped(&self) -> Option<ChessPiece> {
+        match self.ext() {
+            ChessMoveExt::Drop => ChessPiece::from((self.0 & 0xf) as u8).ok().flatten(),
+            _ => None
+        }
+    }
+
+    pub fn is_drop(&self) -> bool {
+        self.ext() == ChessMoveExt::Drop
+    }
+
+    // a pawn promoting. the kind only has room for the four usual pieces, a
+    // fairy piece is a queen promotion with the piece in the four bits above
+    pub fn promote(src: &V2, dst: &V2, piece: ChessPiece, captures: bool) -> Self {
+        let ext = ChessMoveExt::promotion(piece, captures) as u16;
+        match piece.is_standard() {
+            true => Self::raw(src, dst, ext),
+            false => Self::raw(src, dst, ext | (piece as u16) << 4)
+        }
+    }
+
+    // four bits for each coordinate, enough for a board up to 16x16
     pub fn raw(src: &V2, dst: &V2, ext: u16) -> Self {
-        Self(src.x | (src.y << 3) | (dst.x << 6) | (dst.y << 9) | (ext << 12))
+        let (src, dst, ext) = (src.x as u32 | (src.y as u32) << 4, dst.x as u32 | (dst.y as u32) << 4, ext as u32);
+        Self(src | (dst << 8) | (ext << 16))
     }
 
     pub fn ext(&self) -> ChessMoveExt { 
-        ChessMoveExt::from((self.0 >> 12) & 0x15)
+        ChessMoveExt::from((self.0 >> 16) as u16 & 0xf)
     }
 
+    pub fn is_capture(&self) -> bool {
+        self.ext().is_capture()
+    }
+
+    pub fn promotion(&self) -> Option<ChessPiece> {
+        match (self.ext().is_promotion(), self.0 >> 20 & 0xf) {
+            (true, 0) => self.ext().promotion_piece(),
+            (true, fairy) => ChessPiece::from(fairy as u8).ok().flatten(),
+            (false, _) => None
+        }
+    }
+
+    // a drop answers with its destination, so anything looking for the
+    // piece that moved finds an empty tile rather than the wrong piece
     pub fn src(&self) -> V2 {
-        V2 { x: self.0 & 0x7, y: self.0 >> 3 & 0x7 }
+        match self.is_drop() {
+            true => self.dst(),
+            false => V2 { x: (self.0 & 0xf) as u16, y: (self.0 >> 4 & 0xf) as u16 }
+        }
     }
 
     pub fn dst(&self) -> V2 {
-        V2 { x: self.0 >> 6 & 0x7, y: self.0 >> 9 & 0x7 }
+        V2 { x: (self.0 >> 8 & 0xf) as u16, y: (self.0 >> 12 & 0xf) as u16 }
     }
 }
 
@@ -203,7 +262,180 @@ impl ChessMoveExt {
             3 => ChessMoveExt::QueenCastle,
             4 => ChessMoveExt::Captures,
             5 => ChessMoveExt::EpCapture,
+            6 => ChessMoveExt::Drop,
+            8 => ChessMoveExt::KnightPromotion,
+            9 => ChessMoveExt::BishopPromotion,
+            10 => ChessMoveExt::RookPromotion,
+            11 => ChessMoveExt::QueenPromotion,
+            12 => ChessMoveExt::KnightPromoCapture,
+            13 => ChessMoveExt::BishopPromoCapture,
+            14 => ChessMoveExt::RookPromoCapture,
+            15 => ChessMoveExt::QueenPromoCapture,
             _ => ChessMoveExt::Quiet
         }
     }
-}
\ No newline at end of file
+
+    pub fn promotion(piece: ChessPiece, captures: bool) -> ChessMoveExt {
+        let ext = match piece {
+            ChessPiece::Knight => 8,
+            ChessPiece::Bishop => 9,
+            ChessPiece::Rook => 10,
+            _ => 11
+        };
+
+        match captures {
+            true => ChessMoveExt::from(ext + 4),
+            false => ChessMoveExt::from(ext)
+        }
+    }
+
+    pub fn is_capture(&self) -> bool {
+        // the capture flag is bit 2, shared by plain, en passant and
+        // promotion captures. drops borrowed a free value that has it set
+        (*self as u16) & 4 != 0 && *self != ChessMoveExt::Drop
+    }
+
+    pub fn is_promotion(&self) -> bool {
+        (*self as u16) & 8 != 0
+    }
+
+    pub fn promotion_piece(&self) -> Option<ChessPiece> {
+        match self.is_promotion() {
+            true => match (*self as u16) & 3 {
+                0 => Some(ChessPiece::Knight),
+                1 => Some(ChessPiece::Bishop),
+                2 => Some(ChessPiece::Rook),
+                _ => Some(ChessPiece::Queen)
+            },
+            false => None
+        }
+    }
+}
+
+const PROMOTIONS: [ChessPiece; 4] = [
+    ChessPiece::Queen, ChessPiece::Knight, ChessPiece::Rook, ChessPiece::Bishop
+];
+
+// a pawn may also become one of these, on a board that has one
+const FAIRY_PROMOTIONS: [ChessPiece; 2] = [ChessPiece::Archbishop, ChessPiece::Chancellor];
+
+#[allow(dead_code)]
+impl ChessBoard {
+    // what a pawn can promote to here
+    fn promotions(&self) -> Vec<ChessPiece> {
+        let on_board = |piece: &ChessPiece| self.squares().any(|v2| self.tile(&v2).piece() == Some(*piece));
+        PROMOTIONS.into_iter()
+            .chain(FAIRY_PROMOTIONS.into_iter().filter(on_board))
+            .collect()
+    }
+
+    // every move for the side to move that follows the piece rules, including
+    // ones that leave the king in check
+    pub fn pseudo_legal_moves(&self) -> Vec<ChessMove> {
+        let side = Some(self.side_to_move());
+        let mut moves = vec![];
+
+        for src in self.squares() {
+            if self.tile(&src).color() != side {
+                continue;
+            }
+
+            for dst in ChessMove::destinations(&src, *self) {
+                let ext = ChessMove::get_ext(&src, &dst, *self);
+
+                match ext.is_promotion() {
+                    true => for piece in self.promotions() {
+                        moves.push(ChessMove::promote(&src, &dst, piece, ext.is_capture()));
+                    },
+                    false => moves.push(ChessMove::raw(&src, &dst, ext as u16))
+                }
+            }
+        }
+
+        moves.extend(self.castling_moves());
+
+        if self.variant() == Variant::Crazyhouse {
+            moves.extend(self.drop_moves());
+        }
+
+        moves
+    }
+
+    // castling needs the tiles between the king and the rook empty, and the
+    // king may not castle out of, through or into check. in chess960 every
+    // tile either piece crosses has to be empty apart from the two of them,
+    // and the move goes to the rook's tile
+    pub fn castling_moves(&self) -> Vec<ChessMove> {
+        let side = self.side_to_move();
+        let row = self.home_row(side);
+        let (king_side, queen_side) = self.castling_rooks(side);
+
+        let king = match (0..self.files()).map(|x| V2 { x, y: row }).find(|v2| self.tile(v2).piece() == Some(ChessPiece::King)) {
+            Some(king) => king,
+            None => return vec![]
+        };
+
+        let mut moves = vec![];
+
+        for (rook, king_side, ext) in [(king_side, true, ChessMoveExt::KingCastle), (queen_side, false, ChessMoveExt::QueenCastle)] {
+            let Some(rook) = rook else {
+                continue;
+            };
+
+            let (king_to, rook_to, _) = self.castled_files(king_side);
+
+            let low = king.x.min(rook.x).min(king_to).min(rook_to);
+            let high = king.x.max(rook.x).max(king_to).max(rook_to);
+
+            let empty = (low..=high)
+                .filter(|x| *x != king.x && *x != rook.x)
+                .all(|x| self.tile(&V2 { x, y: row }).piece().is_none());
+
+            let safe = (king.x.min(king_to)..=king.x.max(king_to))
+                .all(|x| !self.is_attacked(&V2 { x, y: row }, side.opposite()));
+
+            if empty && safe {
+                let dst = match self.is_chess960() {
+                    true => rook,
+                    false => V2 { x: king_to, y: row }
+                };
+                moves.push(ChessMove::raw(&king, &dst, ext as u16));
+            }
+        }
+
+        moves
+    }
+
+    // none once the game is over by the variant's rules
+    pub fn legal_moves(&self) -> Vec<ChessMove> {
+        let side = self.side_to_move();
+
+        if self.variant_outcome().is_some() {
+            return vec![];
+        }
+
+        self.pseudo_legal_moves().into_iter()
+            .filter(|chess_move| {
+                let mut board = *self;
+                board.make_move(chess_move);
+                board.king_safe_after_move(side)
+            })
+            .collect()
+    }
+
+    // counts the leaf nodes of the legal move tree, the standard way to check
+    // a move generator against known numbers
+    pub fn perft(&self, depth: u8) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        self.legal_moves().iter()
+            .map(|chess_move| {
+                let mut board = *self;
+                board.make_move(chess_move);
+                board.perft(depth - 1)
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,395 @@
+use std::fs;
+use std::path::Path;
+
+use crate::board::ChessBoard;
+use crate::moves::{ChessMove, ChessMoveExt};
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::math::V2;
+use crate::variant::Variant;
+
+// efficiently updatable neural network evaluation. the input is halfkp: for
+// each side, every piece but the kings on every square, relative to where
+// that side's own king stands. the two sides' hidden layers (accumulators)
+// are the sum of the weight rows of their active features, so a move only
+// adds and subtracts the few rows it changes instead of starting over. a
+// side's king moving changes all of its features, that side is rebuilt
+//
+// the weights file, all little endian:
+//   "CENN", hidden size (u32)
+//   feature weights, FEATURES rows of hidden i16
+//   feature biases, hidden i16
+//   output weights, hidden i16 for the side to move then hidden for the other
+//   output bias (i32)
+
+const MAGIC: [u8; 4] = *b"CENN";
+
+// king square, then five piece kinds of two colors on 64 squares
+pub const FEATURES: usize = 64 * 10 * 64;
+
+// hidden values are clipped to 0..=ACTIVATION_MAX before the output layer,
+// whose weights are scaled by OUTPUT_SCALE. the result comes out in
+// centipawns after multiplying by EVAL_SCALE
+const ACTIVATION_MAX: i16 = 255;
+const OUTPUT_SCALE: i32 = 64;
+const EVAL_SCALE: i32 = 400;
+
+// simd works on 16 hidden values at a time
+const LANES: usize = 16;
+
+#[derive(Clone, Debug)]
+pub struct Network {
+    hidden: usize,
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+    simd: bool
+}
+
+// the hidden layer for white's and for black's point of view
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accumulator {
+    sides: [Vec<i16>; 2]
+}
+
+fn piece_index(piece: ChessPiece) -> usize {
+    match piece {
+        ChessPiece::Pawn => 0,
+        ChessPiece::Knight => 1,
+        ChessPiece::Bishop => 2,
+        ChessPiece::Rook => 3,
+        ChessPiece::Queen => 4,
+        ChessPiece::King => unreachable!("kings aren't features"),
+        _ => unreachable!("the public calls turn away boards with other pieces")
+    }
+}
+
+// black sees the board upside down, so both sides' features look the same
+fn feature(perspective: ChessPieceColor, king: usize, piece: ChessPiece, color: ChessPieceColor, square: usize) -> usize {
+    let orient = |square: usize| match perspective {
+        ChessPieceColor::White => square,
+        ChessPieceColor::Black => square ^ 56
+    };
+
+    let kind = 2 * piece_index(piece) + (color != perspective) as usize;
+    (orient(king) * 10 + kind) * 64 + orient(square)
+}
+
+fn simd_available() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+fn i16_le(bytes: &[u8]) -> Vec<i16> {
+    bytes.chunks_exact(2)
+        .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]))
+        .collect()
+}
+
+fn add_row_scalar(values: &mut [i16], row: &[i16]) {
+    for (value, weight) in values.iter_mut().zip(row) {
+        *value = value.wrapping_add(*weight);
+    }
+}
+
+fn sub_row_scalar(values: &mut [i16], row: &[i16]) {
+    for (value, weight) in values.iter_mut().zip(row) {
+        *value = value.wrapping_sub(*weight);
+    }
+}
+
+fn output_scalar(values: &[i16], weights: &[i16]) -> i32 {
+    values.iter()
+        .zip(weights)
+        .map(|(value, weight)| (*value).clamp(0, ACTIVATION_MAX) as i32 * *weight as i32)
+        .sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    use super::{ACTIVATION_MAX, LANES};
+
+    // safety: the caller checked for avx2, and both slices hold a whole
+    // number of LANES values
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn add_row(values: &mut [i16], row: &[i16]) {
+        for (values, row) in values.chunks_exact_mut(LANES).zip(row.chunks_exact(LANES)) {
+            let sum = _mm256_add_epi16(
+                _mm256_loadu_si256(values.as_ptr() as *const __m256i),
+                _mm256_loadu_si256(row.as_ptr() as *const __m256i)
+            );
+            _mm256_storeu_si256(values.as_mut_ptr() as *mut __m256i, sum);
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn sub_row(values: &mut [i16], row: &[i16]) {
+        for (values, row) in values.chunks_exact_mut(LANES).zip(row.chunks_exact(LANES)) {
+            let difference = _mm256_sub_epi16(
+                _mm256_loadu_si256(values.as_ptr() as *const __m256i),
+                _mm256_loadu_si256(row.as_ptr() as *const __m256i)
+            );
+            _mm256_storeu_si256(values.as_mut_ptr() as *mut __m256i, difference);
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn output(values: &[i16], weights: &[i16]) -> i32 {
+        let zero = _mm256_setzero_si256();
+        let max = _mm256_set1_epi16(ACTIVATION_MAX);
+        let mut sum = _mm256_setzero_si256();
+
+        for (values, weights) in values.chunks_exact(LANES).zip(weights.chunks_exact(LANES)) {
+            let clipped = _mm256_min_epi16(_mm256_max_epi16(_mm256_loadu_si256(values.as_ptr() as *const __m256i), zero), max);
+            let products = _mm256_madd_epi16(clipped, _mm256_loadu_si256(weights.as_ptr() as *const __m256i));
+            sum = _mm256_add_epi32(sum, products);
+        }
+
+        let mut lanes = [0i32; 8];
+        _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+        lanes.iter().sum()
+    }
+}
+
+#[allow(dead_code)]
+impl Network {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        if bytes.len() < 8 || bytes[0..4] != MAGIC {
+            return Err("not a network file");
+        }
+
+        let hidden = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        if hidden == 0 || !hidden.is_multiple_of(LANES) {
+            return Err("network hidden size must be a multiple of 16");
+        }
+
+        let weights = 2 * FEATURES * hidden;
+        let biases = 2 * hidden;
+        let outputs = 2 * 2 * hidden;
+
+        if bytes.len() != 8 + weights + biases + outputs + 4 {
+            return Err("network file is the wrong size");
+        }
+
+        let (weight_bytes, rest) = bytes[8..].split_at(weights);
+        let (bias_bytes, rest) = rest.split_at(biases);
+        let (output_bytes, rest) = rest.split_at(outputs);
+
+        Ok(Network {
+            hidden,
+            feature_weights: i16_le(weight_bytes),
+            feature_biases: i16_le(bias_bytes),
+            output_weights: i16_le(output_bytes),
+            output_bias: i32::from_le_bytes(rest.try_into().unwrap()),
+            simd: simd_available()
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let bytes = fs::read(path).map_err(|_| "could not read network file")?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn hidden(&self) -> usize {
+        self.hidden
+    }
+
+    // whether the avx2 code is used, it is whenever the cpu has it
+    pub fn simd(&self) -> bool {
+        self.simd
+    }
+
+    // the plain code, on any cpu. gives the same numbers, only slower
+    pub fn scalar(self) -> Self {
+        Network { simd: false, ..self }
+    }
+
+    fn row(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden]
+    }
+
+    fn add_row(&self, values: &mut [i16], feature: usize) {
+        #[cfg(target_arch = "x86_64")]
+        if self.simd {
+            return unsafe { avx2::add_row(values, self.row(feature)) };
+        }
+
+        add_row_scalar(values, self.row(feature));
+    }
+
+    fn sub_row(&self, values: &mut [i16], feature: usize) {
+        #[cfg(target_arch = "x86_64")]
+        if self.simd {
+            return unsafe { avx2::sub_row(values, self.row(feature)) };
+        }
+
+        sub_row_scalar(values, self.row(feature));
+    }
+
+    fn output(&self, values: &[i16], weights: &[i16]) -> i32 {
+        #[cfg(target_arch = "x86_64")]
+        if self.simd {
+            return unsafe { avx2::output(values, weights) };
+        }
+
+        output_scalar(values, weights)
+    }
+
+    // one side's hidden layer from scratch
+    fn refresh_side(&self, accumulator: &mut Accumulator, board: &ChessBoard, perspective: ChessPieceColor) {
+        let values = &mut accumulator.sides[perspective.index()];
+        values.clone_from(&self.feature_biases);
+
+        let king = match board.king_square(perspective) {
+            Some(king) => board.square_index(&king),
+            None => return
+        };
+
+        for v2 in board.squares() {
+            let tile = board.tile(&v2);
+
+            if let (Some(piece), Some(color)) = (tile.piece(), tile.color()) {
+                if piece != ChessPiece::King {
+                    self.add_row(values, feature(perspective, king, piece, color, board.square_index(&v2)));
+                }
+            }
+        }
+    }
+
+    // none for a board the network doesn't know, anything but an orthodox one
+    pub fn refresh(&self, board: &ChessBoard) -> Option<Accumulator> {
+        match board.is_orthodox() {
+            true => Some(self.build(board)),
+            false => None
+        }
+    }
+
+    fn build(&self, board: &ChessBoard) -> Accumulator {
+        let mut accumulator = Accumulator {
+            sides: [vec![0; self.hidden], vec![0; self.hidden]]
+        };
+
+        for perspective in [ChessPieceColor::White, ChessPieceColor::Black] {
+            self.refresh_side(&mut accumulator, board, perspective);
+        }
+
+        accumulator
+    }
+
+    // brings the accumulator of `board` up to date with the move about to be
+    // played on it. unmaking the move is going back to the copy from before.
+    // the accumulator is left alone if the board isn't orthodox either side
+    // of the move
+    pub fn update(&self, accumulator: &mut Accumulator, board: &ChessBoard, chess_move: &ChessMove) -> Result<(), &'static str> {
+        let mover = board.side_to_move();
+        let (src, dst) = (chess_move.src(), chess_move.dst());
+
+        let mut after = *board;
+        after.make_move(chess_move);
+
+        if !board.is_orthodox() || !after.is_orthodox() {
+            return Err("the network only knows orthodox boards");
+        }
+
+        // a drop has no tile it came from, it's simpler to start over
+        if chess_move.is_drop() {
+            *accumulator = self.build(&after);
+            return Ok(());
+        }
+
+        let piece = match board.tile(&src).piece() {
+            Some(piece) => piece,
+            None => return Ok(())
+        };
+
+        let mut removed = vec![];
+        let mut added = vec![];
+
+        match chess_move.ext() {
+            ChessMoveExt::EpCapture => removed.push((ChessPiece::Pawn, mover.opposite(), V2 { x: dst.x, y: src.y })),
+            ext if ext.is_capture() => if let Some(captured) = board.tile(&dst).piece() {
+                removed.push((captured, mover.opposite(), dst));
+            },
+            _ => ()
+        }
+
+        match piece {
+            ChessPiece::King => {
+                let rook = match chess_move.ext() {
+                    ChessMoveExt::KingCastle => Some((7, 5)),
+                    ChessMoveExt::QueenCastle => Some((0, 3)),
+                    _ => None
+                };
+
+                // in chess960 the move goes to the rook's tile
+                if let Some((from, to)) = rook {
+                    let from = match board.is_chess960() {
+                        true => dst,
+                        false => V2 { x: from, y: src.y }
+                    };
+                    removed.push((ChessPiece::Rook, mover, from));
+                    added.push((ChessPiece::Rook, mover, V2 { x: to, y: src.y }));
+                }
+            },
+            _ => {
+                removed.push((piece, mover, src));
+                added.push((chess_move.promotion().unwrap_or(piece), mover, dst));
+            }
+        }
+
+        // an atomic capture can clear a whole neighbourhood
+        let exploded = board.variant() == Variant::Atomic && chess_move.is_capture();
+
+        for perspective in [ChessPieceColor::White, ChessPieceColor::Black] {
+            if exploded || (piece == ChessPiece::King && perspective == mover) {
+                self.refresh_side(accumulator, &after, perspective);
+                continue;
+            }
+
+            let king = match after.king_square(perspective) {
+                Some(king) => after.square_index(&king),
+                None => continue
+            };
+
+            let values = &mut accumulator.sides[perspective.index()];
+            let index = |(piece, color, v2): &(ChessPiece, ChessPieceColor, V2)| {
+                feature(perspective, king, *piece, *color, board.square_index(v2))
+            };
+
+            for change in &removed {
+                self.sub_row(values, index(change));
+            }
+
+            for change in &added {
+                self.add_row(values, index(change));
+            }
+        }
+
+        Ok(())
+    }
+
+    // centipawns from the point of view of the side to move
+    pub fn evaluate(&self, accumulator: &Accumulator, side: ChessPieceColor) -> i32 {
+        let us = &accumulator.sides[side.index()];
+        let them = &accumulator.sides[side.opposite().index()];
+        let (our_weights, their_weights) = self.output_weights.split_at(self.hidden);
+
+        let sum = self.output(us, our_weights) + self.output(them, their_weights) + self.output_bias;
+        sum * EVAL_SCALE / (ACTIVATION_MAX as i32 * OUTPUT_SCALE)
+    }
+
+    // the whole network on a board, without keeping anything. none for a
+    // board that isn't orthodox
+    pub fn evaluate_board(&self, board: &ChessBoard) -> Option<i32> {
+        self.refresh(board).map(|accumulator| self.evaluate(&accumulator, board.side_to_move()))
+    }
+}
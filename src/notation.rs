@@ -0,0 +1,554 @@
+use crate::board::{ChessBoard, ChessTile};
+use crate::moves::{ChessMove, ChessMoveExt};
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::math::V2;
+use crate::variant::Variant;
+use crate::crazyhouse::POCKET_PIECES;
+
+// algebraic square names, long algebraic (uci) moves and fen. files always
+// run a to h (or as far as the board goes) from left to right, ranks are
+// counted from white's side, so which row is rank 1 depends on who sits at
+// the bottom of the board. the fairy pieces are written a, c and m
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+pub(crate) fn piece_letter(piece: ChessPiece) -> char {
+    match piece {
+        ChessPiece::Pawn => 'p',
+        ChessPiece::Knight => 'n',
+        ChessPiece::Bishop => 'b',
+        ChessPiece::Rook => 'r',
+        ChessPiece::Queen => 'q',
+        ChessPiece::King => 'k',
+        ChessPiece::Archbishop => 'a',
+        ChessPiece::Chancellor => 'c',
+        ChessPiece::Amazon => 'm'
+    }
+}
+
+pub(crate) fn letter_piece(letter: char) -> Option<ChessPiece> {
+    match letter.to_ascii_lowercase() {
+        'p' => Some(ChessPiece::Pawn),
+        'n' => Some(ChessPiece::Knight),
+        'b' => Some(ChessPiece::Bishop),
+        'r' => Some(ChessPiece::Rook),
+        'q' => Some(ChessPiece::Queen),
+        'k' => Some(ChessPiece::King),
+        'a' => Some(ChessPiece::Archbishop),
+        'c' => Some(ChessPiece::Chancellor),
+        'm' => Some(ChessPiece::Amazon),
+        _ => None
+    }
+}
+
+fn file_letter(x: u16) -> char {
+    (b'a' + x as u8) as char
+}
+
+// how many files a rank of fen placement covers, empties can take two digits
+fn rank_width(rank: &str) -> Result<u16, &'static str> {
+    let mut width = 0;
+    let mut empty = String::new();
+
+    for letter in rank.chars().chain([' ']) {
+        if letter.is_ascii_digit() {
+            empty.push(letter);
+            continue;
+        }
+
+        if !empty.is_empty() {
+            width += empty.parse::<u16>().map_err(|_| "not a fen rank")?;
+            empty.clear();
+        }
+
+        if letter != '~' && letter != ' ' {
+            width += 1;
+        }
+    }
+
+    Ok(width)
+}
+
+// checks given by white and black, from either three-check field
+fn parse_checks(field: &str) -> Option<(u8, u8)> {
+    let (given, field) = match field.strip_prefix('+') {
+        Some(field) => (true, field),
+        None => (false, field)
+    };
+
+    let (white, black) = field.split_once('+')?;
+    let (white, black) = (white.parse::<u8>().ok()?.min(3), black.parse::<u8>().ok()?.min(3));
+
+    match given {
+        true => Some((white, black)),
+        false => Some((3 - white, 3 - black))
+    }
+}
+
+#[allow(dead_code)]
+impl ChessBoard {
+    pub fn square_name(&self, v2: &V2) -> String {
+        let rank = match self.player_color() {
+            ChessPieceColor::White => self.ranks() - v2.y,
+            ChessPieceColor::Black => v2.y + 1
+        };
+
+        format!("{}{}", file_letter(v2.x), rank)
+    }
+
+    pub fn parse_square(&self, name: &str) -> Option<V2> {
+        let file = name.bytes().next().filter(u8::is_ascii_lowercase)?;
+        let rank = name.get(1..).filter(|rank| rank.bytes().all(|b| b.is_ascii_digit()))?;
+
+        let x = (file - b'a') as u16;
+        let rank = rank.parse::<u16>().ok().filter(|rank| (1..=self.ranks()).contains(rank))?;
+        if x >= self.files() {
+            return None;
+        }
+
+        let y = match self.player_color() {
+            ChessPieceColor::White => self.ranks() - rank,
+            ChessPieceColor::Black => rank - 1
+        };
+
+        Some(V2 { x, y })
+    }
+
+    pub fn move_to_uci(&self, chess_move: &ChessMove) -> String {
+        // drops are written like N@f3 in both notations
+        if let Some(piece) = chess_move.dropped() {
+            return format!("{}@{}", piece_letter(piece).to_ascii_uppercase(), self.square_name(&chess_move.dst()));
+        }
+
+        let promotion = match chess_move.promotion() {
+            Some(piece) => piece_letter(piece).to_string(),
+            None => String::new()
+        };
+
+        format!(
+            "{}{}{}",
+            self.square_name(&chess_move.src()),
+            self.square_name(&chess_move.dst()),
+            promotion
+        )
+    }
+
+    // the legal move written as e2e4 or e7e8q, if there is one
+    pub fn parse_uci_move(&self, text: &str) -> Option<ChessMove> {
+        self.legal_moves().into_iter()
+            .find(|chess_move| self.move_to_uci(chess_move) == text)
+    }
+
+    // standard algebraic notation, like Nbd7, exd6, O-O or e8=Q+
+    pub fn move_to_san(&self, chess_move: &ChessMove) -> String {
+        self.san(chess_move, &self.legal_moves())
+    }
+
+    // `moves` are the legal moves, for telling apart pieces of one kind that
+    // can reach the same square
+    fn san(&self, chess_move: &ChessMove, moves: &[ChessMove]) -> String {
+        let (src, dst) = (chess_move.src(), chess_move.dst());
+
+        let mut text = match (chess_move.ext(), self.tile(&src).piece()) {
+            (ChessMoveExt::KingCastle, _) => "O-O".to_string(),
+            (ChessMoveExt::QueenCastle, _) => "O-O-O".to_string(),
+            (ChessMoveExt::Drop, _) => self.move_to_uci(chess_move),
+            (_, piece) => {
+                let mut text = String::new();
+                let from = self.square_name(&src);
+
+                match piece {
+                    Some(ChessPiece::Pawn) | None => if chess_move.is_capture() {
+                        text.push_str(&from[..1]);
+                    },
+                    Some(piece) => {
+                        text.push(piece_letter(piece).to_ascii_uppercase());
+
+                        let rivals: Vec<V2> = moves.iter()
+                            .filter(|other| other.dst() == dst && other.src() != src)
+                            .filter(|other| self.tile(&other.src()).piece() == Some(piece))
+                            .map(|other| other.src())
+                            .collect();
+
+                        // the file if that's enough, then the rank, then both
+                        if !rivals.is_empty() {
+                            match (rivals.iter().all(|v2| v2.x != src.x), rivals.iter().all(|v2| v2.y != src.y)) {
+                                (true, _) => text.push_str(&from[..1]),
+                                (false, true) => text.push_str(&from[1..]),
+                                (false, false) => text.push_str(&from)
+                            }
+                        }
+                    }
+                }
+
+                if chess_move.is_capture() {
+                    text.push('x');
+                }
+
+                text.push_str(&self.square_name(&dst));
+
+                if let Some(promotion) = chess_move.promotion() {
+                    text.push('=');
+                    text.push(piece_letter(promotion).to_ascii_uppercase());
+                }
+
+                text
+            }
+        };
+
+        let mut child = *self;
+        child.make_move(chess_move);
+
+        if child.in_check(child.side_to_move()) {
+            text.push(match child.legal_moves().is_empty() {
+                true => '#',
+                false => '+'
+            });
+        }
+
+        text
+    }
+
+    // the legal move written in san. check marks, annotations and the = of
+    // a promotion may be left off, castling may be written with zeros and
+    // a pawn drop without its P
+    pub fn parse_san(&self, text: &str) -> Option<ChessMove> {
+        let plain = |text: &str| text.replace('0', "O")
+            .chars()
+            .filter(|c| !"+#!?=".contains(*c))
+            .collect::<String>();
+
+        let wanted = match text.starts_with('@') {
+            true => plain(&format!("P{}", text)),
+            false => plain(text)
+        };
+        if wanted.is_empty() {
+            return None;
+        }
+
+        let moves = self.legal_moves();
+        moves.iter()
+            .find(|chess_move| plain(&self.san(chess_move, &moves)) == wanted)
+            .copied()
+    }
+
+    // a position from forsyth-edwards notation. the move counters may be
+    // left off, as in epd. the board doesn't keep a halfmove clock, so that
+    // field is read past. the board has to be 8x8, so a cut off fen isn't
+    // taken for a smaller board
+    pub fn from_fen(player_color: ChessPieceColor, fen: &str) -> Result<Self, &'static str> {
+        let board = Self::from_fen_sized(player_color, fen)?;
+        if (board.files(), board.ranks()) != (8, 8) {
+            return Err("a fen board is 8x8");
+        }
+
+        Ok(board)
+    }
+
+    // the same, with the board as big as the placement says
+    pub fn from_fen_sized(player_color: ChessPieceColor, fen: &str) -> Result<Self, &'static str> {
+        let mut fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 {
+            return Err("not a fen string");
+        }
+
+        // three-check positions add the checks each side has left, like
+        // 3+3 after the en passant square, or the checks given, like +0+0
+        // at the end
+        let checks = match fields.iter().skip(4).position(|field| field.contains('+')) {
+            Some(index) => Some(parse_checks(fields.remove(index + 4)).ok_or("not a fen check count")?),
+            None => None
+        };
+
+        // crazyhouse adds the pockets, in brackets after the board or as a
+        // ninth rank, and marks promoted pieces with a ~. a ninth rank as
+        // wide as the others is taken for a board that tall
+        let (placement, pocket) = match fields[0].split_once('[') {
+            Some((placement, pocket)) => (placement, Some(pocket.trim_end_matches(']'))),
+            None => (fields[0], None)
+        };
+
+        let mut ranks: Vec<&str> = placement.split('/').collect();
+        let files = rank_width(ranks[0])?;
+
+        let pocket = match (pocket, ranks.len()) {
+            (None, 9) if !ranks[8].contains(|c: char| c.is_ascii_digit()) && rank_width(ranks[8])? != files => ranks.pop(),
+            (pocket, _) => pocket
+        };
+
+        // the board is as big as the placement says
+        let mut board = ChessBoard::empty(player_color, files, ranks.len() as u16)?;
+
+        for (index, rank) in ranks.iter().enumerate() {
+            if rank_width(rank)? != files {
+                return Err("fen ranks need the same number of files");
+            }
+
+            let mut file = 0;
+            let mut last = None;
+            let mut empty = String::new();
+
+            for letter in rank.chars() {
+                if letter.is_ascii_digit() {
+                    empty.push(letter);
+                    continue;
+                }
+
+                if !empty.is_empty() {
+                    file += empty.parse::<u16>().map_err(|_| "not a fen rank")?;
+                    empty.clear();
+                }
+
+                if letter == '~' {
+                    let v2 = last.ok_or("a fen ~ follows a piece")?;
+                    board.place(&v2, board.tile(&v2).promoted());
+                    continue;
+                }
+
+                let piece = letter_piece(letter).ok_or("not a fen piece")?;
+                let color = match letter.is_ascii_uppercase() {
+                    true => ChessPieceColor::White,
+                    false => ChessPieceColor::Black
+                };
+
+                let square = format!("{}{}", file_letter(file), board.ranks() - index as u16);
+                let v2 = board.parse_square(&square).ok_or("not a fen rank")?;
+
+                // kings and rooks count as moved until the castling field
+                // says otherwise
+                let tile = ChessTile::new(Some(piece), Some(color))?;
+                board.place(&v2, match piece {
+                    ChessPiece::King | ChessPiece::Rook => tile.moved(),
+                    _ => tile
+                });
+                last = Some(v2);
+                file += 1;
+            }
+        }
+
+        let black = match fields[1] {
+            "w" => false,
+            "b" => true,
+            _ => return Err("fen side to move is w or b")
+        };
+
+        // a right keeps the king and that rook unmoved. besides KQkq, x-fen
+        // and shredder-fen name the rook's file, and any right that isn't
+        // the standard king and corner rook makes the game chess960
+        let mut chess960 = false;
+
+        let last_file = file_letter(board.files() - 1);
+
+        for letter in fields[2].chars().filter(|letter| *letter != '-') {
+            let (color, rank) = match letter.is_ascii_uppercase() {
+                true => (ChessPieceColor::White, 1),
+                false => (ChessPieceColor::Black, board.ranks())
+            };
+
+            let find = |files: Vec<char>, piece: ChessPiece| files.into_iter()
+                .map(|file| board.parse_square(&format!("{}{}", file, rank)).unwrap())
+                .find(|v2| board.tile(v2).piece() == Some(piece) && board.tile(v2).color() == Some(color));
+
+            // rights without their king or rook are dropped
+            let Some(king) = find(('a'..=last_file).collect(), ChessPiece::King) else {
+                continue;
+            };
+            let king_file = file_letter(king.x);
+
+            let rook = match letter.to_ascii_lowercase() {
+                'k' => find(('a'..=last_file).rev().filter(|file| *file > king_file).collect(), ChessPiece::Rook),
+                'q' => find(('a'..=last_file).filter(|file| *file < king_file).collect(), ChessPiece::Rook),
+                file if ('a'..=last_file).contains(&file) => {
+                    chess960 = true;
+                    find(vec![file], ChessPiece::Rook)
+                },
+                _ => return Err("not a fen castling right")
+            };
+
+            let Some(rook) = rook else {
+                continue;
+            };
+            chess960 |= king.x != board.files() / 2 || (rook.x != 0 && rook.x != board.files() - 1);
+
+            for v2 in [king, rook] {
+                let tile = board.tile(&v2);
+                board.place(&v2, ChessTile::new(tile.piece(), tile.color())?);
+            }
+        }
+
+        board.set_chess960(chess960);
+
+        // the square skipped over stands for the double step just played
+        if fields[3] != "-" {
+            let skipped = board.parse_square(fields[3]).ok_or("not a fen en passant square")?;
+            let (from, to) = match black {
+                true => (2, 4),
+                false => (board.ranks() - 1, board.ranks().checked_sub(3).ok_or("not a fen en passant square")?)
+            };
+
+            // boards too short for a double step have no square for it
+            let file = &fields[3][..1];
+            let src = board.parse_square(&format!("{}{}", file, from)).ok_or("not a fen en passant square")?;
+            let dst = board.parse_square(&format!("{}{}", file, to)).ok_or("not a fen en passant square")?;
+
+            if board.tile(&dst).piece() == Some(ChessPiece::Pawn) && skipped.x == dst.x {
+                board.set_last_move(Some(ChessMove::raw(&src, &dst, ChessMoveExt::DoublePawnPush as u16)));
+            }
+        }
+
+        let full_moves = match fields.get(5) {
+            Some(field) => field.parse::<u16>().map_err(|_| "not a fen move number")?.max(1),
+            None => 1
+        };
+
+        let turn = (full_moves - 1).checked_mul(2)
+            .and_then(|turn| turn.checked_add(black as u16))
+            .ok_or("not a fen move number")?;
+        board.set_turn(turn);
+
+        if let Some(pocket) = pocket {
+            board.set_variant(Variant::Crazyhouse);
+
+            for letter in pocket.chars().filter(|letter| *letter != '-') {
+                let piece = letter_piece(letter).filter(|piece| POCKET_PIECES.contains(piece)).ok_or("not a fen pocket piece")?;
+                let color = match letter.is_ascii_uppercase() {
+                    true => ChessPieceColor::White,
+                    false => ChessPieceColor::Black
+                };
+                board.set_pocket(color, piece, board.pocket(color, piece) + 1);
+            }
+        }
+
+        if let Some((white, black)) = checks {
+            board.set_variant(Variant::ThreeCheck);
+            board.set_checks_given(ChessPieceColor::White, white);
+            board.set_checks_given(ChessPieceColor::Black, black);
+        }
+
+        Ok(board)
+    }
+
+    // standard castling rights come out as KQkq, chess960 ones as x-fen, which
+    // only names the rook's file when KQkq would be ambiguous
+    pub fn to_fen(&self) -> String {
+        self.fen(false)
+    }
+
+    // chess960 castling rights always as the rooks' files, like HAha
+    pub fn to_shredder_fen(&self) -> String {
+        self.fen(self.is_chess960())
+    }
+
+    fn castling_letter(&self, rook: &V2, side: char, color: ChessPieceColor, shredder: bool) -> char {
+        // another unmoved rook further out on the same side
+        let outer = (0..self.files())
+            .map(|x| V2 { x, y: rook.y })
+            .filter(|v2| match side {
+                'k' => v2.x > rook.x,
+                _ => v2.x < rook.x
+            })
+            .any(|v2| self.tile(&v2).piece() == Some(ChessPiece::Rook) && self.tile(&v2).color() == Some(color));
+
+        let letter = match shredder || outer {
+            true => file_letter(rook.x),
+            false => side
+        };
+
+        match color {
+            ChessPieceColor::White => letter.to_ascii_uppercase(),
+            ChessPieceColor::Black => letter
+        }
+    }
+
+    fn fen(&self, shredder: bool) -> String {
+        let mut ranks = vec![];
+
+        for rank in (1..=self.ranks()).rev() {
+            let mut text = String::new();
+            let mut empty = 0;
+
+            for file in 0..self.files() {
+                let v2 = self.parse_square(&format!("{}{}", file_letter(file), rank)).unwrap();
+                let tile = self.tile(&v2);
+
+                let (piece, color) = match (tile.piece(), tile.color()) {
+                    (Some(piece), Some(color)) => (piece, color),
+                    _ => {
+                        empty += 1;
+                        continue;
+                    }
+                };
+
+                if empty > 0 {
+                    text.push_str(&empty.to_string());
+                    empty = 0;
+                }
+
+                text.push(match color {
+                    ChessPieceColor::White => piece_letter(piece).to_ascii_uppercase(),
+                    ChessPieceColor::Black => piece_letter(piece)
+                });
+
+                if tile.is_promoted() {
+                    text.push('~');
+                }
+            }
+
+            if empty > 0 {
+                text.push_str(&empty.to_string());
+            }
+
+            ranks.push(text);
+        }
+
+        let mut placement = ranks.join("/");
+        if self.variant() == Variant::Crazyhouse {
+            placement.push('[');
+            for color in [ChessPieceColor::White, ChessPieceColor::Black] {
+                for piece in POCKET_PIECES {
+                    let letter = match color {
+                        ChessPieceColor::White => piece_letter(piece).to_ascii_uppercase(),
+                        ChessPieceColor::Black => piece_letter(piece)
+                    };
+                    placement.extend((0..self.pocket(color, piece)).map(|_| letter));
+                }
+            }
+            placement.push(']');
+        }
+
+        let side = match self.side_to_move() {
+            ChessPieceColor::White => "w",
+            ChessPieceColor::Black => "b"
+        };
+
+        let castling: String = [ChessPieceColor::White, ChessPieceColor::Black].into_iter()
+            .flat_map(|color| {
+                let (king_side, queen_side) = self.castling_rooks(color);
+                [(king_side, 'k', color), (queen_side, 'q', color)]
+            })
+            .filter_map(|(rook, letter, color)| rook.map(|rook| self.castling_letter(&rook, letter, color, shredder)))
+            .collect();
+
+        let checks = match self.variant() {
+            Variant::ThreeCheck => format!(
+                " {}+{}",
+                3u8.saturating_sub(self.checks_given(ChessPieceColor::White)),
+                3u8.saturating_sub(self.checks_given(ChessPieceColor::Black))
+            ),
+            _ => String::new()
+        };
+
+        format!(
+            "{} {} {} {}{} 0 {}",
+            placement,
+            side,
+            match castling.is_empty() {
+                true => "-".to_string(),
+                false => castling
+            },
+            self.en_passant().map_or("-".to_string(), |v2| self.square_name(&v2)),
+            checks,
+            self.turn() / 2 + 1
+        )
+    }
+}
@@ -0,0 +1,169 @@
+use crate::board::{ChessBoard, MAX_FILES, MAX_RANKS};
+use crate::moves::ChessMove;
+use crate::pieces::ChessPiece;
+use crate::math::V2;
+
+// move ordering. alpha-beta cuts off sooner the earlier the best move is
+// tried, so moves are scored and handed out best first:
+//
+//   1. the move the transposition table remembers
+//   2. captures that don't lose material, most valuable victim first and
+//      least valuable attacker breaking ties (mvv-lva)
+//   3. queen promotions
+//   4. killer moves, quiet moves that caused a cutoff at the same ply
+//   5. other quiet moves by their history score
+//   6. losing captures and underpromotions
+
+pub const MAX_PLY: usize = 128;
+
+const TABLE_MOVE: i32 = 1_000_000;
+const GOOD_CAPTURE: i32 = 200_000;
+const PROMOTION: i32 = 150_000;
+const KILLER: i32 = 100_000;
+const HISTORY_LIMIT: i32 = 50_000;
+const BAD_CAPTURE: i32 = -100_000;
+
+// room for the biggest board
+const SQUARES: usize = (MAX_FILES * MAX_RANKS) as usize;
+
+fn rank(piece: ChessPiece) -> i32 {
+    match piece {
+        ChessPiece::Pawn => 1,
+        ChessPiece::Knight => 2,
+        ChessPiece::Bishop => 3,
+        ChessPiece::Rook => 4,
+        ChessPiece::Archbishop => 5,
+        ChessPiece::Chancellor => 6,
+        ChessPiece::Queen => 7,
+        ChessPiece::Amazon => 8,
+        ChessPiece::King => 9
+    }
+}
+
+fn index(v2: &V2) -> usize {
+    (v2.x + MAX_FILES * v2.y) as usize
+}
+
+// most valuable victim, least valuable attacker
+pub fn mvv_lva(board: &ChessBoard, chess_move: &ChessMove) -> i32 {
+    let attacker = board.tile(&chess_move.src()).piece().map_or(0, rank);
+
+    // en passant leaves the destination empty, the victim is always a pawn
+    let victim = board.tile(&chess_move.dst()).piece().map_or(1, rank);
+
+    victim * 10 - attacker
+}
+
+#[derive(Clone, Debug)]
+pub struct MoveOrdering {
+    killers: Vec<[Option<ChessMove>; 2]>,
+    history: Vec<[i32; SQUARES]> // indexed by source tile then destination tile
+}
+
+#[allow(dead_code)]
+impl MoveOrdering {
+    pub fn new() -> Self {
+        MoveOrdering {
+            killers: vec![[None; 2]; MAX_PLY],
+            history: vec![[0; SQUARES]; SQUARES]
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn killers(&self, ply: usize) -> [Option<ChessMove>; 2] {
+        self.killers[ply.min(MAX_PLY - 1)]
+    }
+
+    pub fn history(&self, chess_move: &ChessMove) -> i32 {
+        self.history[index(&chess_move.src())][index(&chess_move.dst())]
+    }
+
+    // a quiet move refuted the opponent's last move. remember it for this ply
+    // and give it credit in the history table, more for deeper cutoffs
+    pub fn record_cutoff(&mut self, chess_move: &ChessMove, ply: usize, depth: u8) {
+        let killers = &mut self.killers[ply.min(MAX_PLY - 1)];
+        if killers[0] != Some(*chess_move) {
+            killers[1] = killers[0];
+            killers[0] = Some(*chess_move);
+        }
+
+        let entry = &mut self.history[index(&chess_move.src())][index(&chess_move.dst())];
+        *entry += depth as i32 * depth as i32;
+
+        // halve everything once a score gets too big so old cutoffs fade out
+        if *entry >= HISTORY_LIMIT {
+            self.history.iter_mut()
+                .flat_map(|row| row.iter_mut())
+                .for_each(|score| *score /= 2);
+        }
+    }
+
+    pub fn score(&self, board: &ChessBoard, chess_move: &ChessMove, table_move: Option<ChessMove>, ply: usize) -> i32 {
+        if table_move == Some(*chess_move) {
+            return TABLE_MOVE;
+        }
+
+        if let Some(piece) = chess_move.promotion() {
+            let capture = match chess_move.is_capture() {
+                true => mvv_lva(board, chess_move),
+                false => 0
+            };
+
+            return match piece {
+                ChessPiece::Queen => PROMOTION + capture,
+                _ => BAD_CAPTURE - 1000 + rank(piece)
+            };
+        }
+
+        if chess_move.is_capture() {
+            return match board.see(chess_move) >= 0 {
+                true => GOOD_CAPTURE + mvv_lva(board, chess_move),
+                false => BAD_CAPTURE + mvv_lva(board, chess_move)
+            };
+        }
+
+        match self.killers(ply) {
+            [Some(killer), _] if killer == *chess_move => KILLER,
+            [_, Some(killer)] if killer == *chess_move => KILLER - 1,
+            _ => self.history(chess_move)
+        }
+    }
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// hands out moves best first. scores are worked out up front, but the list is
+// only sorted as far as it gets consumed, which is usually not far at a cutoff
+pub struct MovePicker {
+    moves: Vec<(i32, ChessMove)>
+}
+
+impl MovePicker {
+    pub fn new(board: &ChessBoard, moves: Vec<ChessMove>, ordering: &MoveOrdering, table_move: Option<ChessMove>, ply: usize) -> Self {
+        MovePicker {
+            moves: moves.into_iter()
+                .map(|chess_move| (ordering.score(board, &chess_move, table_move, ply), chess_move))
+                .collect()
+        }
+    }
+}
+
+impl Iterator for MovePicker {
+    type Item = ChessMove;
+
+    fn next(&mut self) -> Option<ChessMove> {
+        let best = self.moves.iter()
+            .enumerate()
+            .max_by_key(|(index, (score, _))| (*score, -(*index as i32)))
+            .map(|(index, _)| index)?;
+
+        Some(self.moves.remove(best).1)
+    }
+}
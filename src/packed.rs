@@ -0,0 +1,278 @@
+use crate::board::{ChessBoard, ChessTile};
+use crate::moves::{ChessMove, ChessMoveExt};
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::math::V2;
+use crate::variant::Variant;
+
+// packed positions, a fixed 32 bytes each for storing a lot of them:
+//
+//   0..8    which squares are occupied, bit 0 for a1 up to bit 63 for h8
+//   8..24   a nibble for each occupied square in that order, low nibble
+//           first. white pawn, knight, bishop, rook, queen and king are 0
+//           to 5, black's are 6 to 11, and 12 and 13 are white and black
+//           rooks that can still castle
+//   24      the variant, and bit 4 for chess960
+//   25      the en passant file plus one, or 0, then the checks white and
+//           black have given two bits each
+//   26..28  the turn
+//   28..32  zero
+//
+// squares count from a1 whichever color is at the bottom, so a position
+// packs the same either way up and the player is given back when it's
+// unpacked. it fits an 8x8 board of the usual pieces with up to 32 of them,
+// so not crazyhouse with its pockets or horde before it's lost some pawns.
+// like fen, only the kings and rooks remember whether they've moved
+//
+// the moves of a game are packed as where they come in the legal moves of
+// the position they're played from, a byte each unless there are more than
+// 256 to choose from. the legal moves are put in order by their squares
+// counted from a1, so the game unpacks the same either way up too
+
+pub const PACKED_SIZE: usize = 32;
+
+const PIECES: usize = 32;
+const STATE: usize = 8 + PIECES / 2;
+const CASTLING_ROOK: u8 = 12;
+
+fn nibble(piece: ChessPiece, color: ChessPieceColor) -> u8 {
+    let kind = ChessPiece::ALL.iter().position(|other| *other == piece).unwrap() as u8;
+
+    match color {
+        ChessPieceColor::White => kind,
+        ChessPieceColor::Black => kind + 6
+    }
+}
+
+fn index_width(legal: usize) -> usize {
+    match legal > 256 {
+        true => 2,
+        false => 1
+    }
+}
+
+#[allow(dead_code)]
+impl ChessBoard {
+    // the tile square_index counts to
+    fn square_at(&self, index: usize) -> V2 {
+        let (x, row) = (index as u16 % self.files(), index as u16 / self.files());
+
+        let y = match self.player_color() {
+            ChessPieceColor::White => self.ranks() - 1 - row,
+            ChessPieceColor::Black => row
+        };
+
+        V2 { x, y }
+    }
+
+    // drops after the moves, by piece, then by the squares and the kind
+    fn ordered_moves(&self) -> Vec<ChessMove> {
+        let mut legal = self.legal_moves();
+        legal.sort_by_key(|chess_move| match chess_move.dropped() {
+            Some(piece) => (1, piece as usize, self.square_index(&chess_move.dst()), 0),
+            None => (0, self.square_index(&chess_move.src()), self.square_index(&chess_move.dst()), chess_move.ext() as u16)
+        });
+        legal
+    }
+
+    pub fn pack(&self) -> Result<[u8; PACKED_SIZE], &'static str> {
+        if !self.is_orthodox() {
+            return Err("only 8x8 boards of the usual pieces pack");
+        }
+        if self.variant() == Variant::Crazyhouse {
+            return Err("crazyhouse pockets don't fit a packed position");
+        }
+
+        let castling = [ChessPieceColor::White, ChessPieceColor::Black]
+            .map(|color| self.castling_rooks(color))
+            .into_iter()
+            .flat_map(|(king_side, queen_side)| [king_side, queen_side])
+            .flatten()
+            .collect::<Vec<V2>>();
+
+        let mut bytes = [0; PACKED_SIZE];
+        let mut occupied = 0u64;
+        let mut count = 0;
+
+        for index in 0..64 {
+            let v2 = self.square_at(index);
+            let tile = self.tile(&v2);
+
+            let (Some(piece), Some(color)) = (tile.piece(), tile.color()) else {
+                continue;
+            };
+
+            if count == PIECES {
+                return Err("a packed position holds up to 32 pieces");
+            }
+
+            let code = match castling.contains(&v2) {
+                true => CASTLING_ROOK + (color == ChessPieceColor::Black) as u8,
+                false => nibble(piece, color)
+            };
+
+            occupied |= 1 << index;
+            bytes[8 + count / 2] |= code << (4 * (count % 2));
+            count += 1;
+        }
+
+        bytes[..8].copy_from_slice(&occupied.to_le_bytes());
+
+        let variant = Variant::ALL.iter().position(|variant| *variant == self.variant()).unwrap() as u8;
+        bytes[STATE] = variant | (self.is_chess960() as u8) << 4;
+
+        let en_passant = self.en_passant().map_or(0, |v2| v2.x as u8 + 1);
+        let checks = [ChessPieceColor::White, ChessPieceColor::Black].map(|color| self.checks_given(color).min(3));
+        bytes[STATE + 1] = en_passant | checks[0] << 4 | checks[1] << 6;
+
+        bytes[STATE + 2..STATE + 4].copy_from_slice(&self.turn().to_le_bytes());
+
+        Ok(bytes)
+    }
+
+    pub fn unpack(player_color: ChessPieceColor, bytes: &[u8]) -> Result<Self, &'static str> {
+        if bytes.len() != PACKED_SIZE {
+            return Err("a packed position is 32 bytes");
+        }
+        if bytes[STATE + 4..].iter().any(|byte| *byte != 0) {
+            return Err("not a packed position");
+        }
+
+        let occupied = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        if occupied.count_ones() as usize > PIECES {
+            return Err("a packed position holds up to 32 pieces");
+        }
+
+        let mut board = ChessBoard::empty(player_color, 8, 8)?;
+        let mut castling = vec![];
+
+        for (count, index) in (0..64).filter(|index| occupied >> index & 1 == 1).enumerate() {
+            let v2 = board.square_at(index);
+            let code = bytes[8 + count / 2] >> (4 * (count % 2)) & 0xf;
+
+            // kings and rooks count as moved until a castling rook says
+            // otherwise, the same as fen
+            let (piece, color) = match code {
+                0..=11 => (ChessPiece::ALL[code as usize % 6], match code < 6 {
+                    true => ChessPieceColor::White,
+                    false => ChessPieceColor::Black
+                }),
+                CASTLING_ROOK => (ChessPiece::Rook, ChessPieceColor::White),
+                13 => (ChessPiece::Rook, ChessPieceColor::Black),
+                _ => return Err("not a packed piece")
+            };
+
+            let tile = ChessTile::new(Some(piece), Some(color))?;
+            board.place(&v2, match piece {
+                ChessPiece::King | ChessPiece::Rook => tile.moved(),
+                _ => tile
+            });
+
+            if code >= CASTLING_ROOK {
+                castling.push((v2, color));
+            }
+        }
+
+        for (rook, color) in castling {
+            let row = board.home_row(color);
+            if rook.y != row {
+                return Err("a castling rook has to be on its home row");
+            }
+
+            board.place(&rook, ChessTile::new(Some(ChessPiece::Rook), Some(color))?);
+
+            let king = (0..8)
+                .map(|x| V2 { x, y: row })
+                .find(|v2| board.tile(v2).piece() == Some(ChessPiece::King) && board.tile(v2).color() == Some(color))
+                .ok_or("a castling rook needs its king")?;
+            board.place(&king, ChessTile::new(Some(ChessPiece::King), Some(color))?);
+        }
+
+        board.set_variant(*Variant::ALL.get(bytes[STATE] as usize & 0xf).ok_or("not a variant")?);
+        board.set_chess960(bytes[STATE] >> 4 & 1 == 1);
+        if bytes[STATE] >> 5 != 0 || board.variant() == Variant::Crazyhouse {
+            return Err("not a packed position");
+        }
+
+        board.set_checks_given(ChessPieceColor::White, bytes[STATE + 1] >> 4 & 3);
+        board.set_checks_given(ChessPieceColor::Black, bytes[STATE + 1] >> 6);
+        board.set_turn(u16::from_le_bytes([bytes[STATE + 2], bytes[STATE + 3]]));
+
+        // the double step just played, from the side that isn't to move
+        match bytes[STATE + 1] & 0xf {
+            0 => {},
+            file @ 1..=8 => {
+                let mover = board.side_to_move().opposite();
+                let (from, to) = match mover == player_color {
+                    true => (6, 4),
+                    false => (1, 3)
+                };
+
+                let src = V2 { x: file as u16 - 1, y: from };
+                let dst = V2 { x: file as u16 - 1, y: to };
+                board.set_last_move(Some(ChessMove::raw(&src, &dst, ChessMoveExt::DoublePawnPush as u16)));
+            },
+            _ => return Err("not a packed en passant file")
+        }
+
+        Ok(board)
+    }
+
+    // the moves have to be legal, each from the position the last one left
+    pub fn pack_moves(&self, moves: &[ChessMove]) -> Result<Vec<u8>, &'static str> {
+        let mut board = *self;
+        let mut bytes = vec![];
+
+        for chess_move in moves {
+            let legal = board.ordered_moves();
+            let index = legal.iter().position(|other| other == chess_move).ok_or("not a legal move")?;
+
+            bytes.extend(&(index as u16).to_le_bytes()[..index_width(legal.len())]);
+            board.make_move(chess_move);
+        }
+
+        Ok(bytes)
+    }
+
+    pub fn unpack_moves(&self, bytes: &[u8]) -> Result<Vec<ChessMove>, &'static str> {
+        let mut board = *self;
+        let mut moves = vec![];
+        let mut rest = bytes;
+
+        while !rest.is_empty() {
+            let legal = board.ordered_moves();
+            let width = index_width(legal.len());
+            if rest.len() < width {
+                return Err("the packed moves end part way through one");
+            }
+
+            let index = match width {
+                1 => rest[0] as usize,
+                _ => u16::from_le_bytes([rest[0], rest[1]]) as usize
+            };
+
+            let chess_move = *legal.get(index).ok_or("not a legal move")?;
+            board.make_move(&chess_move);
+            moves.push(chess_move);
+            rest = &rest[width..];
+        }
+
+        Ok(moves)
+    }
+
+    // the packed start position followed by the packed moves
+    pub fn pack_game(&self, moves: &[ChessMove]) -> Result<Vec<u8>, &'static str> {
+        let mut bytes = self.pack()?.to_vec();
+        bytes.extend(self.pack_moves(moves)?);
+        Ok(bytes)
+    }
+
+    pub fn unpack_game(player_color: ChessPieceColor, bytes: &[u8]) -> Result<(Self, Vec<ChessMove>), &'static str> {
+        if bytes.len() < PACKED_SIZE {
+            return Err("a packed game starts with a packed position");
+        }
+
+        let board = Self::unpack(player_color, &bytes[..PACKED_SIZE])?;
+        let moves = board.unpack_moves(&bytes[PACKED_SIZE..])?;
+        Ok((board, moves))
+    }
+}
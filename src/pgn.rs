@@ -0,0 +1,461 @@
+use crate::board::ChessBoard;
+use crate::moves::ChessMove;
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::variant::Variant;
+
+// portable game notation. tags first, the seven tag roster in its usual
+// order, then the moves in san wrapped to 80 columns and the result.
+// reading skips comments, variations and annotation glyphs, only the main
+// line is kept
+
+const LINE_WIDTH: usize = 80;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GameResult { WhiteWins, BlackWins, Draw, Unfinished }
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnGame {
+    tags: Vec<(String, String)>,
+    start: ChessBoard,
+    moves: Vec<ChessMove>,
+    result: GameResult
+}
+
+enum Token {
+    Tag(String, String),
+    Word(String)
+}
+
+// bare kings, or a king and a single minor piece against a bare king. a
+// king on its own can still walk up the hill, the horde has no king, and
+// in crazyhouse pieces come back
+pub fn insufficient_material(board: &ChessBoard) -> bool {
+    if matches!(board.variant(), Variant::KingOfTheHill | Variant::Horde | Variant::Crazyhouse) {
+        return false;
+    }
+
+    let mut extra = vec![];
+
+    for v2 in board.squares() {
+        match board.tile(&v2).piece() {
+            Some(ChessPiece::King) | None => (),
+            Some(piece) => extra.push(piece)
+        }
+    }
+
+    extra.len() <= 1 && extra.iter().all(|piece| matches!(piece, ChessPiece::Knight | ChessPiece::Bishop))
+}
+
+// `[Name "value"]` with backslash escapes in the value
+fn parse_tag(text: &str) -> Option<(String, String)> {
+    let (name, rest) = text.trim().split_once(char::is_whitespace)?;
+    let rest = rest.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut value = String::new();
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.extend(chars.next()),
+            c => value.push(c)
+        }
+    }
+
+    Some((name.to_string(), value))
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    let mut depth = 0; // inside how many variations
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => for c in chars.by_ref() {
+                if c == '}' {
+                    break;
+                }
+            },
+            ';' => for c in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            },
+            '(' => depth += 1,
+            ')' => depth = (depth - 1).max(0),
+            '[' if depth == 0 => {
+                let mut tag = String::new();
+                let mut quoted = false;
+                let mut escaped = false;
+
+                for c in chars.by_ref() {
+                    match (c, quoted, escaped) {
+                        (']', false, _) => break,
+                        ('"', _, false) => quoted = !quoted,
+                        _ => ()
+                    }
+                    escaped = c == '\\' && !escaped;
+                    tag.push(c);
+                }
+
+                if let Some((name, value)) = parse_tag(&tag) {
+                    tokens.push(Token::Tag(name, value));
+                }
+            },
+            c if c.is_whitespace() => (),
+            c => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "[]{}();".contains(next) {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+
+                if depth == 0 && !word.starts_with('$') {
+                    tokens.push(Token::Word(word));
+                }
+            }
+        }
+    }
+
+    tokens
+}
+
+// every game in the text. a game with an illegal move or a bad setup ends
+// the reading with an error
+pub fn parse_games(player_color: ChessPieceColor, text: &str) -> Result<Vec<PgnGame>, &'static str> {
+    let mut games = vec![];
+    let mut tags = vec![];
+    let mut words = vec![];
+
+    for token in tokenize(text) {
+        match token {
+            Token::Tag(name, value) => {
+                // tags after moves start the next game
+                if !words.is_empty() {
+                    games.push(PgnGame::from_tokens(player_color, &tags, &words)?);
+                    tags.clear();
+                    words.clear();
+                }
+                tags.push((name, value));
+            },
+            Token::Word(word) => {
+                let ends_game = GameResult::parse(&word).is_some();
+                words.push(word);
+
+                if ends_game {
+                    games.push(PgnGame::from_tokens(player_color, &tags, &words)?);
+                    tags.clear();
+                    words.clear();
+                }
+            }
+        }
+    }
+
+    if !tags.is_empty() || !words.is_empty() {
+        games.push(PgnGame::from_tokens(player_color, &tags, &words)?);
+    }
+
+    Ok(games)
+}
+
+#[allow(dead_code)]
+impl GameResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unfinished => "*"
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unfinished),
+            _ => None
+        }
+    }
+
+    pub fn win_for(color: ChessPieceColor) -> Self {
+        match color {
+            ChessPieceColor::White => GameResult::WhiteWins,
+            ChessPieceColor::Black => GameResult::BlackWins
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl PgnGame {
+    pub fn new(start: ChessBoard) -> Self {
+        let tags = [
+            ("Event", "?"), ("Site", "?"), ("Date", "????.??.??"), ("Round", "?"),
+            ("White", "?"), ("Black", "?"), ("Result", "*")
+        ];
+
+        PgnGame {
+            tags: tags.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            start,
+            moves: vec![],
+            result: GameResult::Unfinished
+        }
+    }
+
+    // replaces the tag if it's already there
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string()))
+        }
+    }
+
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // the first game in the text
+    pub fn parse(player_color: ChessPieceColor, text: &str) -> Result<Self, &'static str> {
+        parse_games(player_color, text)?
+            .into_iter()
+            .next()
+            .ok_or("no game in pgn")
+    }
+
+    fn from_tokens(player_color: ChessPieceColor, tags: &[(String, String)], words: &[String]) -> Result<Self, &'static str> {
+        let variant = tags.iter().find(|(name, _)| name == "Variant");
+        let rules = variant.and_then(|(_, value)| Variant::parse(value));
+
+        let mut start = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) => ChessBoard::from_fen_sized(player_color, fen)?,
+            None => ChessBoard::new_variant(player_color, rules.unwrap_or(Variant::Standard))
+        };
+
+        if let Some(rules) = rules {
+            start.set_variant(rules);
+        }
+
+        // chess960 setups can look standard, the variant tag tells them apart
+        if variant.is_some_and(|(_, value)| value.contains("960") || value.to_lowercase().contains("fischer")) {
+            start.set_chess960(true);
+        }
+
+        let mut game = PgnGame::new(start);
+        for (name, value) in tags {
+            game.set_tag(name, value);
+        }
+
+        let mut board = start;
+        for word in words {
+            if let Some(result) = GameResult::parse(word) {
+                game.set_result(result);
+                break;
+            }
+
+            // move numbers, on their own or stuck to the move
+            let text = match word.rfind('.') {
+                Some(dot) if word[..dot].chars().all(|c| c.is_ascii_digit() || c == '.') => &word[dot + 1..],
+                _ => word.as_str()
+            };
+            if text.is_empty() {
+                continue;
+            }
+
+            let chess_move = board.parse_san(text).ok_or("illegal move in pgn")?;
+            board.make_move(&chess_move);
+            game.moves.push(chess_move);
+        }
+
+        Ok(game)
+    }
+
+    pub fn start(&self) -> &ChessBoard {
+        &self.start
+    }
+
+    pub fn moves(&self) -> &[ChessMove] {
+        &self.moves
+    }
+
+    // moves are assumed legal, they aren't checked until the game is written
+    pub fn push(&mut self, chess_move: ChessMove) {
+        self.moves.push(chess_move);
+    }
+
+    pub fn pop(&mut self) -> Option<ChessMove> {
+        self.moves.pop()
+    }
+
+    // the same game with the other color, or the same one, at the bottom
+    pub fn oriented(&self, player_color: ChessPieceColor) -> Self {
+        let mut game = self.clone();
+        game.start = ChessBoard::from_fen_sized(player_color, &self.start.to_fen()).unwrap_or(self.start);
+        game.start.set_chess960(self.start.is_chess960());
+        game.start.set_variant(self.start.variant());
+        game.moves.clear();
+
+        let (mut from, mut to) = (self.start, game.start);
+        for chess_move in &self.moves {
+            let text = from.move_to_uci(chess_move);
+            let Some(moved) = to.parse_uci_move(&text) else {
+                break;
+            };
+
+            from.make_move(chess_move);
+            to.make_move(&moved);
+            game.moves.push(moved);
+        }
+
+        game
+    }
+
+    // how the game ended by the rules, if it has: mate, stalemate, threefold
+    // repetition, the fifty move rule or insufficient material. the
+    // counters start again from the start position
+    pub fn outcome(&self) -> Option<(GameResult, &'static str)> {
+        let mut board = self.start;
+        let mut history = vec![board.hash()];
+        let mut halfmove_clock = 0;
+
+        for chess_move in &self.moves {
+            let reversible = !chess_move.is_capture() && !chess_move.is_drop() && board.tile(&chess_move.src()).piece() != Some(ChessPiece::Pawn);
+            halfmove_clock = match reversible {
+                true => halfmove_clock + 1,
+                false => 0
+            };
+
+            board.make_move(chess_move);
+            history.push(board.hash());
+        }
+
+        if let Some(outcome) = board.variant_outcome() {
+            return Some(outcome);
+        }
+
+        let mover = board.side_to_move();
+        if board.legal_moves().is_empty() {
+            return match board.in_check(mover) {
+                true => Some((GameResult::win_for(mover.opposite()), "checkmate")),
+                false => Some((GameResult::Draw, "stalemate"))
+            };
+        }
+
+        if history.iter().filter(|hash| **hash == board.hash()).count() >= 3 {
+            return Some((GameResult::Draw, "threefold repetition"));
+        }
+
+        if halfmove_clock >= 100 {
+            return Some((GameResult::Draw, "fifty move rule"));
+        }
+
+        if insufficient_material(&board) {
+            return Some((GameResult::Draw, "insufficient material"));
+        }
+
+        None
+    }
+
+    pub fn result(&self) -> GameResult {
+        self.result
+    }
+
+    pub fn set_result(&mut self, result: GameResult) {
+        self.result = result;
+        self.set_tag("Result", result.as_str());
+    }
+
+    // the position after every move has been played
+    pub fn board(&self) -> ChessBoard {
+        let mut board = self.start;
+        for chess_move in &self.moves {
+            board.make_move(chess_move);
+        }
+        board
+    }
+
+    // move numbers and moves in san
+    fn move_tokens(&self) -> Vec<String> {
+        let mut tokens = vec![];
+        let mut board = self.start;
+
+        for (index, chess_move) in self.moves.iter().enumerate() {
+            let number = board.turn() / 2 + 1;
+
+            match board.side_to_move() {
+                ChessPieceColor::White => tokens.push(format!("{}.", number)),
+                ChessPieceColor::Black if index == 0 => tokens.push(format!("{}...", number)),
+                ChessPieceColor::Black => ()
+            }
+
+            tokens.push(board.move_to_san(chess_move));
+            board.make_move(chess_move);
+        }
+
+        tokens
+    }
+
+    // the moves on one line, like `1. e4 e5 2. Nf3`
+    pub fn movetext(&self) -> String {
+        self.move_tokens().join(" ")
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut text = String::new();
+
+        let fen = self.start.to_fen();
+        let custom_start = fen != self.start.variant().start_fen();
+
+        for (name, value) in &self.tags {
+            if custom_start && (name == "SetUp" || name == "FEN") {
+                continue;
+            }
+            text.push_str(&format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")));
+        }
+
+        if self.tag("Variant").is_none() {
+            match self.start.variant() {
+                Variant::Standard if self.start.is_chess960() => text.push_str("[Variant \"Chess960\"]\n"),
+                Variant::Standard => (),
+                variant => text.push_str(&format!("[Variant \"{}\"]\n", variant.name()))
+            }
+        }
+
+        if custom_start {
+            text.push_str("[SetUp \"1\"]\n");
+            text.push_str(&format!("[FEN \"{}\"]\n", fen));
+        }
+
+        text.push('\n');
+
+        // tokens first, then wrapped
+        let mut tokens = self.move_tokens();
+        tokens.push(self.result.as_str().to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                text.push_str(&line);
+                text.push('\n');
+                line.clear();
+            }
+
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+
+        text.push_str(&line);
+        text.push('\n');
+        text
+    }
+}
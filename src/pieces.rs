@@ -1,7 +1,27 @@
+use crate::math::{KNIGHT_OFFSETS, KING_OFFSETS, PLUS_DIRECTIONS, DIAG_DIRECTIONS};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ChessPiece {
+    Pawn = 1, Rook = 2, Knight = 3, Bishop = 4, Queen = 5, King = 6,
+    Archbishop = 7, Chancellor = 8, Amazon = 9
+}
+
+// how a piece gets around, apart from the pawn's own rules and castling. a
+// leaper jumps straight to each of its offsets, a rider keeps going along
+// each of its directions until something is in the way. a new piece only
+// needs its entry here, the move generator and the attack queries read them
 #[derive(Copy, Clone, Debug, PartialEq)]
-pub enum ChessPiece { Pawn = 1, Rook = 2, Knight = 3, Bishop = 4, Queen = 5, King = 6 }
+pub struct Movement {
+    pub leaps: &'static [(i8, i8)],
+    pub rides: &'static [(i8, i8)]
+}
 
 impl ChessPiece {
+    pub const ALL: [ChessPiece; 9] = [
+        Self::Pawn, Self::Knight, Self::Bishop, Self::Rook, Self::Queen, Self::King,
+        Self::Archbishop, Self::Chancellor, Self::Amazon
+    ];
+
     pub fn from(value: u8) -> Result<Option<Self>, &'static str> {
         match value {
             0 => Ok(None),
@@ -11,10 +31,67 @@ impl ChessPiece {
             4 => Ok(Some(Self::Bishop)),
             5 => Ok(Some(Self::Queen)),
             6 => Ok(Some(Self::King)),
+            7 => Ok(Some(Self::Archbishop)),
+            8 => Ok(Some(Self::Chancellor)),
+            9 => Ok(Some(Self::Amazon)),
             _ => Err("not a valid piece")
         }
     }
+
+    // material value in centipawns. the king is priced so that no exchange
+    // is ever worth giving it up
+    pub const fn value(&self) -> i32 {
+        match self {
+            Self::Pawn => 100,
+            Self::Knight => 320,
+            Self::Bishop => 330,
+            Self::Rook => 500,
+            Self::Queen => 900,
+            Self::King => 20000,
+            Self::Archbishop => 850, // bishop and knight
+            Self::Chancellor => 880, // rook and knight
+            Self::Amazon => 1250 // queen and knight
+        }
+    }
+
+    pub const fn movement(&self) -> Movement {
+        let (leaps, rides): (&[_], &[_]) = match self {
+            Self::Pawn => (&[], &[]),
+            Self::Knight => (&KNIGHT_OFFSETS, &[]),
+            Self::Bishop => (&[], &DIAG_DIRECTIONS),
+            Self::Rook => (&[], &PLUS_DIRECTIONS),
+            Self::Queen => (&[], &KING_OFFSETS),
+            Self::King => (&KING_OFFSETS, &[]),
+            Self::Archbishop => (&KNIGHT_OFFSETS, &DIAG_DIRECTIONS),
+            Self::Chancellor => (&KNIGHT_OFFSETS, &PLUS_DIRECTIONS),
+            Self::Amazon => (&KNIGHT_OFFSETS, &KING_OFFSETS)
+        };
+
+        Movement { leaps, rides }
+    }
+
+    // one of the six pieces of ordinary chess
+    pub fn is_standard(&self) -> bool {
+        !matches!(self, Self::Archbishop | Self::Chancellor | Self::Amazon)
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
-pub enum ChessPieceColor { Black, White }
\ No newline at end of file
+pub enum ChessPieceColor { Black, White }
+
+impl ChessPieceColor {
+    pub fn opposite(&self) -> Self {
+        match self {
+            Self::White => Self::Black,
+            Self::Black => Self::White
+        }
+    }
+
+    // white 0 and black 1, for anything kept per side
+    pub fn index(&self) -> usize {
+        match self {
+            Self::White => 0,
+            Self::Black => 1
+        }
+    }
+}
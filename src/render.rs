@@ -0,0 +1,186 @@
+use std::fmt;
+
+use crate::board::{ChessBoard, ChessTile};
+use crate::pieces::{ChessPiece, ChessPieceColor};
+
+// drawing the board as text. ascii uses the fen letters, unicode the chess
+// glyphs, and with colors on the squares get ansi backgrounds. the side
+// the board is seen from sits at the bottom with its a file on its left,
+// the way it would across a real board
+
+const RESET: &str = "\x1b[0m";
+const LIGHT_SQUARE: &str = "\x1b[48;5;223m";
+const DARK_SQUARE: &str = "\x1b[48;5;137m";
+const LAST_MOVE: &str = "\x1b[48;5;143m";
+const CHECK: &str = "\x1b[48;5;167m";
+const WHITE_PIECE: &str = "\x1b[1;38;5;231m";
+const BLACK_PIECE: &str = "\x1b[1;38;5;232m";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Glyphs { Ascii, Unicode }
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RenderOptions {
+    pub glyphs: Glyphs,
+    pub colors: bool, // ansi square backgrounds and piece colors
+    pub coordinates: bool, // rank and file labels
+    pub flipped: bool, // seen from the other side than the player's
+    pub highlights: bool // the last move and a king in check
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Highlight { None, LastMove, Check }
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            glyphs: Glyphs::Ascii,
+            colors: false,
+            coordinates: true,
+            flipped: false,
+            highlights: true
+        }
+    }
+}
+
+fn glyph(tile: ChessTile, glyphs: Glyphs, colors: bool) -> char {
+    let (Some(piece), Some(color)) = (tile.piece(), tile.color()) else {
+        return match (glyphs, colors) {
+            (_, true) => ' ',
+            (Glyphs::Ascii, false) => '.',
+            (Glyphs::Unicode, false) => '·'
+        };
+    };
+
+    // colored pieces are told apart by their color, so they all get the
+    // solid glyphs, which are easier to see
+    let white = color == ChessPieceColor::White && !(colors && glyphs == Glyphs::Unicode);
+
+    // there are no glyphs for the fairy pieces, they keep their letters
+    match (glyphs, piece, white) {
+        (Glyphs::Ascii, piece, _) | (Glyphs::Unicode, piece @ (ChessPiece::Archbishop | ChessPiece::Chancellor | ChessPiece::Amazon), _) => {
+            let letter = match piece {
+                ChessPiece::Pawn => 'p',
+                ChessPiece::Knight => 'n',
+                ChessPiece::Bishop => 'b',
+                ChessPiece::Rook => 'r',
+                ChessPiece::Queen => 'q',
+                ChessPiece::King => 'k',
+                ChessPiece::Archbishop => 'a',
+                ChessPiece::Chancellor => 'c',
+                ChessPiece::Amazon => 'm'
+            };
+
+            match color {
+                ChessPieceColor::White => letter.to_ascii_uppercase(),
+                ChessPieceColor::Black => letter
+            }
+        },
+        (Glyphs::Unicode, ChessPiece::King, true) => '♔',
+        (Glyphs::Unicode, ChessPiece::Queen, true) => '♕',
+        (Glyphs::Unicode, ChessPiece::Rook, true) => '♖',
+        (Glyphs::Unicode, ChessPiece::Bishop, true) => '♗',
+        (Glyphs::Unicode, ChessPiece::Knight, true) => '♘',
+        (Glyphs::Unicode, ChessPiece::Pawn, true) => '♙',
+        (Glyphs::Unicode, ChessPiece::King, false) => '♚',
+        (Glyphs::Unicode, ChessPiece::Queen, false) => '♛',
+        (Glyphs::Unicode, ChessPiece::Rook, false) => '♜',
+        (Glyphs::Unicode, ChessPiece::Bishop, false) => '♝',
+        (Glyphs::Unicode, ChessPiece::Knight, false) => '♞',
+        (Glyphs::Unicode, ChessPiece::Pawn, false) => '♟'
+    }
+}
+
+#[allow(dead_code)]
+impl ChessBoard {
+    pub fn render(&self, options: &RenderOptions) -> String {
+        let viewer = match options.flipped {
+            true => self.player_color().opposite(),
+            false => self.player_color()
+        };
+
+        // files and ranks as the viewer sees them, left to right and top
+        // to bottom
+        let last_file = (b'a' + self.files() as u8 - 1) as char;
+        let (files, ranks): (Vec<char>, Vec<u16>) = match viewer {
+            ChessPieceColor::White => (('a'..=last_file).collect(), (1..=self.ranks()).rev().collect()),
+            ChessPieceColor::Black => (('a'..=last_file).rev().collect(), (1..=self.ranks()).collect())
+        };
+        let label = self.ranks().to_string().len();
+
+        let last_move = self.last_turn()
+            .filter(|_| options.highlights)
+            .map(|chess_move| [chess_move.src(), chess_move.dst()]);
+
+        let mover = self.side_to_move();
+        let in_check = options.highlights && self.in_check(mover);
+
+        let mut text = String::new();
+
+        for rank in &ranks {
+            if options.coordinates {
+                text.push_str(&format!("{:>label$} ", rank));
+            }
+
+            for file in &files {
+                let v2 = self.parse_square(&format!("{}{}", file, rank)).unwrap();
+                let tile = self.tile(&v2);
+
+                let highlight = match (last_move, tile.piece(), tile.color()) {
+                    (_, Some(ChessPiece::King), Some(color)) if in_check && color == mover => Highlight::Check,
+                    (Some(squares), _, _) if squares.contains(&v2) => Highlight::LastMove,
+                    _ => Highlight::None
+                };
+
+                let symbol = glyph(tile, options.glyphs, options.colors);
+
+                if !options.colors {
+                    let (open, close) = match highlight {
+                        Highlight::None => (' ', ' '),
+                        Highlight::LastMove => ('[', ']'),
+                        Highlight::Check => ('(', ')')
+                    };
+                    text.push_str(&format!("{}{}{}", open, symbol, close));
+                    continue;
+                }
+
+                // a1 is dark
+                let index = self.square_index(&v2);
+                let files = self.files() as usize;
+                let background = match (highlight, (index % files + index / files) % 2) {
+                    (Highlight::Check, _) => CHECK,
+                    (Highlight::LastMove, _) => LAST_MOVE,
+                    (Highlight::None, 0) => DARK_SQUARE,
+                    (Highlight::None, _) => LIGHT_SQUARE
+                };
+
+                let foreground = match tile.color() {
+                    Some(ChessPieceColor::White) => WHITE_PIECE,
+                    _ => BLACK_PIECE
+                };
+
+                text.push_str(&format!("{}{} {} {}", background, foreground, symbol, RESET));
+            }
+
+            text.push('\n');
+        }
+
+        if options.coordinates {
+            text.push_str(&" ".repeat(label + 1));
+            for file in &files {
+                text.push_str(&format!(" {} ", file));
+            }
+            text.push('\n');
+        }
+
+        text
+    }
+}
+
+// the plain ascii board with labels
+impl fmt::Display for ChessBoard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(&RenderOptions::default()))
+    }
+}
+
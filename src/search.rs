@@ -0,0 +1,863 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use crate::board::ChessBoard;
+use crate::moves::ChessMove;
+use crate::pieces::ChessPiece;
+use crate::ordering::{MoveOrdering, MovePicker, MAX_PLY};
+use crate::tt::{Bound, TableEntry, TranspositionTable, DEFAULT_TABLE_ENTRIES};
+use crate::time::TimeManager;
+use crate::syzygy::{Tablebase, Wdl};
+use crate::nnue::{Network, Accumulator};
+use crate::eval::EvalParams;
+
+pub const MATE: i32 = 30000;
+pub const INFINITY: i32 = 32000;
+
+// a capture that can't bring the score back up to alpha even with this much
+// positional compensation isn't worth looking at
+const DELTA_MARGIN: i32 = 200;
+
+// how far the static eval may sit above beta (per ply of depth left) before
+// the node is assumed to fail high without searching
+const REVERSE_FUTILITY_MARGIN: i32 = 120;
+
+// how far below alpha the static eval may sit, by depth left, before quiet
+// moves are assumed not to help
+const FUTILITY_MARGINS: [i32; 3] = [0, 200, 450];
+
+// half width of the first window tried around the previous iteration's score
+const ASPIRATION_WINDOW: i32 = 40;
+
+// nodes between looks at the clock
+const CLOCK_CHECK_INTERVAL: u64 = 1024;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SearchConfig {
+    pub quiescence: bool, // keep searching captures past the depth limit
+    pub quiescence_checks: bool, // also try checking moves on the first quiescence ply
+    pub delta_pruning: bool, // skip captures that can't raise alpha
+    pub null_move: bool, // pass the turn and see if the opponent still can't catch up
+    pub late_move_reductions: bool, // search quiet moves late in the list less deeply
+    pub futility: bool, // skip quiet moves near the leaves when far behind
+    pub reverse_futility: bool, // cut nodes near the leaves when far ahead
+    pub check_extensions: bool, // search one ply deeper when in check
+    pub principal_variation: bool, // zero window searches after the first move
+    pub aspiration_windows: bool, // narrow root window around the last score
+    pub threads: usize, // lazy smp search threads, 1 keeps the search deterministic
+    pub multi_pv: usize, // how many distinct root moves to report lines for
+    pub evaluator: Evaluator
+}
+
+// the network is only used once one is loaded, until then the search
+// falls back to the classical evaluation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Evaluator { Classical, Nnue }
+
+// one line of analysis, starting with a root move
+#[derive(Clone, Debug, PartialEq)]
+pub struct PvLine {
+    pub score: i32,
+    pub moves: Vec<ChessMove>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub best_move: Option<ChessMove>,
+    pub score: i32,
+    pub depth: u8,
+    pub nodes: u64,
+    pub helper_nodes: u64, // the part of nodes searched by lazy smp helper threads
+    pub lines: Vec<PvLine> // best first, up to multi_pv of them
+}
+
+#[derive(Clone, Debug)]
+pub struct Searcher {
+    config: SearchConfig,
+    ordering: MoveOrdering,
+    table: Arc<TranspositionTable>,
+    nodes: u64,
+    root_depth: u8,
+    clock: Option<TimeManager>,
+    stopped: bool,
+    helper: Option<Arc<AtomicBool>>, // set on helper threads, raised when the main thread is done
+    tablebase: Option<Arc<Tablebase>>,
+    root_excluded: Vec<ChessMove>, // root moves the tablebase says throw the result away
+    network: Option<Arc<Network>>,
+    accumulators: Vec<Accumulator>, // the network's hidden layer per ply, while in use
+    params: Arc<EvalParams> // for the classical evaluation
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            quiescence: true,
+            quiescence_checks: false,
+            delta_pruning: true,
+            null_move: true,
+            late_move_reductions: true,
+            futility: true,
+            reverse_futility: true,
+            check_extensions: true,
+            principal_variation: true,
+            aspiration_windows: true,
+            threads: 1,
+            multi_pv: 1,
+            evaluator: Evaluator::Classical
+        }
+    }
+}
+
+impl SearchConfig {
+    // plain alpha-beta with quiescence, the baseline to compare features against
+    pub fn minimal() -> Self {
+        SearchConfig {
+            quiescence: true,
+            quiescence_checks: false,
+            delta_pruning: false,
+            null_move: false,
+            late_move_reductions: false,
+            futility: false,
+            reverse_futility: false,
+            check_extensions: false,
+            principal_variation: false,
+            aspiration_windows: false,
+            threads: 1,
+            multi_pv: 1,
+            evaluator: Evaluator::Classical
+        }
+    }
+}
+
+fn captured_value(board: &ChessBoard, chess_move: &ChessMove) -> i32 {
+    let captured = match chess_move.is_capture() {
+        true => board.tile(&chess_move.dst()).piece().unwrap_or(ChessPiece::Pawn).value(),
+        false => 0
+    };
+
+    // a promotion wins the difference between the new piece and the pawn
+    let promoted = chess_move.promotion()
+        .map_or(0, |piece| piece.value() - ChessPiece::Pawn.value());
+
+    captured + promoted
+}
+
+fn is_quiet(chess_move: &ChessMove) -> bool {
+    !chess_move.is_capture() && chess_move.promotion().is_none()
+}
+
+fn is_mate_score(score: i32) -> bool {
+    score.abs() > MATE - MAX_PLY as i32
+}
+
+#[allow(dead_code)]
+impl Searcher {
+    pub fn new(config: SearchConfig) -> Self {
+        Searcher {
+            config,
+            ordering: MoveOrdering::new(),
+            table: Arc::new(TranspositionTable::new(DEFAULT_TABLE_ENTRIES)),
+            nodes: 0,
+            root_depth: 0,
+            clock: None,
+            stopped: false,
+            helper: None,
+            tablebase: None,
+            root_excluded: vec![],
+            network: None,
+            accumulators: vec![],
+            params: Arc::new(EvalParams::default())
+        }
+    }
+
+    pub fn config(&self) -> SearchConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: SearchConfig) {
+        self.config = config;
+    }
+
+    pub fn ordering(&self) -> &MoveOrdering {
+        &self.ordering
+    }
+
+    pub fn table(&self) -> &TranspositionTable {
+        &self.table
+    }
+
+    // endgame tables to probe at the root and inside the tree. none (or an
+    // empty set) searches as usual
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) {
+        self.tablebase = tablebase;
+    }
+
+    pub fn tablebase(&self) -> Option<&Tablebase> {
+        self.tablebase.as_deref()
+    }
+
+    // the network for Evaluator::Nnue
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.network = network;
+    }
+
+    pub fn network(&self) -> Option<&Network> {
+        self.network.as_deref()
+    }
+
+    // weights for the classical evaluation, e.g. from tuning
+    pub fn set_eval_params(&mut self, params: EvalParams) {
+        self.params = Arc::new(params);
+    }
+
+    pub fn eval_params(&self) -> &EvalParams {
+        &self.params
+    }
+
+    // forget everything learned from earlier searches, e.g. for a new game
+    pub fn clear(&mut self) {
+        self.ordering.clear();
+        self.table.clear();
+    }
+
+    // iterative deepening up to the given depth. each iteration is a full
+    // search, so the result is always from the deepest completed one
+    pub fn search(&mut self, board: &ChessBoard, depth: u8) -> SearchResult {
+        self.clock = None;
+        self.iterate_smp(board, depth)
+    }
+
+    // same, but the clock decides when to stop. an iteration cut off by the
+    // hard limit is thrown away
+    pub fn search_timed(&mut self, board: &ChessBoard, max_depth: u8, clock: TimeManager) -> SearchResult {
+        self.clock = Some(clock);
+        let result = self.iterate_smp(board, max_depth);
+        self.clock = None;
+
+        result
+    }
+
+    // lazy smp: helper threads search the same root through the shared table,
+    // each starting at a different depth so they spread out over the tree
+    // instead of repeating the main thread. their results only reach the main
+    // thread through the table, the answer is always the main thread's
+    fn iterate_smp(&mut self, board: &ChessBoard, depth: u8) -> SearchResult {
+        if self.config.threads <= 1 {
+            return self.iterate(board, depth, 1);
+        }
+
+        let done = Arc::new(AtomicBool::new(false));
+
+        let helpers: Vec<Searcher> = (1..self.config.threads)
+            .map(|_| Searcher {
+                config: self.config,
+                ordering: MoveOrdering::new(),
+                table: Arc::clone(&self.table),
+                nodes: 0,
+                root_depth: 0,
+                clock: None,
+                stopped: false,
+                helper: Some(Arc::clone(&done)),
+                tablebase: self.tablebase.clone(),
+                root_excluded: vec![],
+                network: self.network.clone(),
+                accumulators: vec![],
+                params: Arc::clone(&self.params)
+            })
+            .collect();
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = helpers.into_iter()
+                .enumerate()
+                .map(|(index, mut helper)| {
+                    let board = *board;
+                    scope.spawn(move || {
+                        helper.iterate(&board, MAX_PLY as u8 / 2, 2 + index as u8 % 2);
+                        helper.nodes
+                    })
+                })
+                .collect();
+
+            let mut result = self.iterate(board, depth, 1);
+            done.store(true, Ordering::Relaxed);
+
+            result.helper_nodes = handles.into_iter()
+                .map(|handle| handle.join().expect("search thread panicked"))
+                .sum::<u64>();
+            result.nodes += result.helper_nodes;
+
+            result
+        })
+    }
+
+    fn iterate(&mut self, board: &ChessBoard, depth: u8, first_depth: u8) -> SearchResult {
+        self.nodes = 0;
+        self.stopped = false;
+
+        // with the position in the tables, only the moves that keep the
+        // best result are searched
+        let allowed = self.tablebase.as_ref().and_then(|tablebase| tablebase.root_moves(board));
+        self.root_excluded = match allowed {
+            Some(allowed) => board.legal_moves().into_iter()
+                .filter(|chess_move| !allowed.contains(chess_move))
+                .collect(),
+            None => vec![]
+        };
+
+        let mut result = SearchResult {
+            best_move: None,
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            helper_nodes: 0,
+            lines: vec![]
+        };
+
+        for current in first_depth.min(depth.max(1))..=depth.max(1) {
+            if current > first_depth && self.clock.as_ref().is_some_and(|clock| !clock.should_start_iteration()) {
+                break;
+            }
+
+            let (score, lines) = self.iteration(board, current, &result.lines);
+            let best_move = lines.first().map(|line| line.moves[0]);
+
+            // a partial first iteration is still better than no move at all
+            if self.stopped && result.best_move.is_some() {
+                break;
+            }
+
+            if let Some(clock) = self.clock.as_mut() {
+                clock.update(best_move != result.best_move && current > 1, score);
+            }
+
+            result = SearchResult {
+                best_move,
+                score,
+                depth: current,
+                nodes: self.nodes,
+                helper_nodes: 0,
+                lines
+            };
+
+            if self.stopped {
+                break;
+            }
+        }
+
+        result
+    }
+
+    // one depth of iterative deepening. for multipv the root is searched again
+    // for every line with the moves already reported left out, so each line
+    // gets an exact score of its own
+    fn iteration(&mut self, board: &ChessBoard, depth: u8, previous: &[PvLine]) -> (i32, Vec<PvLine>) {
+        let mut lines = vec![];
+        let mut excluded = self.root_excluded.clone();
+
+        for index in 0..self.config.multi_pv.max(1) {
+            let target = previous.get(index).map_or(0, |line| line.score);
+            let (score, best_move) = self.aspiration(board, depth, target, &excluded);
+
+            let best_move = match best_move {
+                Some(best_move) => best_move,
+                None if index == 0 => return (score, lines), // mate or stalemate
+                None => break // fewer legal moves than lines asked for
+            };
+
+            excluded.push(best_move);
+            lines.push(PvLine {
+                score,
+                moves: self.principal_variation(board, best_move, depth as usize)
+            });
+
+            if self.stopped {
+                break;
+            }
+        }
+
+        (lines[0].score, lines)
+    }
+
+    // follows the best moves stored in the table from the root move on. stops
+    // at anything that isn't legal (a clobbered slot) or repeats a position
+    fn principal_variation(&self, board: &ChessBoard, first: ChessMove, length: usize) -> Vec<ChessMove> {
+        let mut moves = vec![first];
+        let mut board = *board;
+        board.make_move(&first);
+
+        let mut seen = vec![board.hash()];
+
+        while moves.len() < length {
+            let next = match self.table.probe(board.hash(), 0).and_then(|entry| entry.best_move) {
+                Some(next) if board.legal_moves().contains(&next) => next,
+                _ => break
+            };
+
+            board.make_move(&next);
+            if seen.contains(&board.hash()) {
+                break;
+            }
+
+            seen.push(board.hash());
+            moves.push(next);
+        }
+
+        moves
+    }
+
+    fn out_of_time(&mut self) -> bool {
+        if !self.stopped && self.nodes.is_multiple_of(CLOCK_CHECK_INTERVAL) {
+            self.stopped = self.clock.as_ref().is_some_and(|clock| clock.should_stop())
+                || self.helper.as_ref().is_some_and(|done| done.load(Ordering::Relaxed));
+        }
+
+        self.stopped
+    }
+
+    // searches a narrow window around the last score first, widening on the
+    // side it fails until the score lands inside
+    fn aspiration(&mut self, board: &ChessBoard, depth: u8, previous: i32, excluded: &[ChessMove]) -> (i32, Option<ChessMove>) {
+        if !self.config.aspiration_windows || depth < 4 || is_mate_score(previous) {
+            return self.root(board, depth, -INFINITY, INFINITY, excluded);
+        }
+
+        let mut delta = ASPIRATION_WINDOW;
+        let mut alpha = previous - delta;
+        let mut beta = previous + delta;
+
+        loop {
+            let (score, best_move) = self.root(board, depth, alpha, beta, excluded);
+
+            if self.stopped {
+                return (score, best_move);
+            }
+
+            if score <= alpha && alpha > -INFINITY {
+                alpha = (score - delta).max(-INFINITY);
+            } else if score >= beta && beta < INFINITY {
+                beta = (score + delta).min(INFINITY);
+            } else {
+                return (score, best_move);
+            }
+
+            delta *= 2;
+        }
+    }
+
+    fn root(&mut self, board: &ChessBoard, depth: u8, mut alpha: i32, beta: i32, excluded: &[ChessMove]) -> (i32, Option<ChessMove>) {
+        self.root_depth = depth;
+        self.refresh_network(board);
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+
+        // the previous iteration's best move is in the table and goes first
+        let table_move = self.table.probe(board.hash(), 0)
+            .and_then(|entry| entry.best_move);
+
+        let moves = board.legal_moves().into_iter()
+            .filter(|chess_move| !excluded.contains(chess_move))
+            .collect();
+
+        let picker = MovePicker::new(board, moves, &self.ordering, table_move, 0);
+
+        for (index, chess_move) in picker.enumerate() {
+            let child = self.play(board, Some(&chess_move), 1);
+
+            let score = self.search_child(&child, depth, alpha, beta, 1, index, 0);
+
+            if self.stopped && best_move.is_some() {
+                break;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(chess_move);
+            }
+
+            if score >= beta {
+                break;
+            }
+
+            alpha = alpha.max(score);
+        }
+
+        if best_move.is_none() {
+            return (self.terminal_score(board, 0), None);
+        }
+
+        // the table only knows about the full root, not a multipv leftover
+        if self.stopped || !excluded.is_empty() {
+            return (best_score, best_move);
+        }
+
+        self.table.store(board.hash(), 0, TableEntry {
+            hash: board.hash(),
+            best_move,
+            score: best_score,
+            depth,
+            bound: bound(best_score, original_alpha, beta)
+        });
+
+        (best_score, best_move)
+    }
+
+    // searches a child node the principal variation way: the first move gets
+    // the full window, later ones a zero window (and maybe less depth) first
+    // and only get the full treatment if they look like they beat alpha
+    #[allow(clippy::too_many_arguments)]
+    fn search_child(&mut self, child: &ChessBoard, depth: u8, alpha: i32, beta: i32, ply: i32, index: usize, reduction: u8) -> i32 {
+        let window = match self.config.principal_variation && index > 0 {
+            true => alpha + 1,
+            false => beta
+        };
+
+        let mut score = -self.negamax(child, depth - 1 - reduction, -window, -alpha, ply, true);
+
+        if reduction > 0 && score > alpha {
+            score = -self.negamax(child, depth - 1, -window, -alpha, ply, true);
+        }
+
+        if window != beta && score > alpha && score < beta {
+            score = -self.negamax(child, depth - 1, -beta, -alpha, ply, true);
+        }
+
+        score
+    }
+
+    fn uses_network(&self) -> bool {
+        self.config.evaluator == Evaluator::Nnue && self.network.is_some() && !self.accumulators.is_empty()
+    }
+
+    fn refresh_network(&mut self, board: &ChessBoard) {
+        let accumulator = match (&self.network, self.config.evaluator) {
+            (Some(network), Evaluator::Nnue) => network.refresh(board),
+            _ => None
+        };
+
+        let Some(accumulator) = accumulator else {
+            return self.accumulators.clear();
+        };
+        match self.accumulators.len() == MAX_PLY {
+            true => self.accumulators[0] = accumulator,
+            false => self.accumulators = vec![accumulator; MAX_PLY]
+        }
+    }
+
+    fn evaluate(&self, board: &ChessBoard, ply: i32) -> i32 {
+        match (&self.network, self.uses_network()) {
+            (Some(network), true) => network.evaluate(&self.accumulators[ply as usize], board.side_to_move()),
+            _ => board.evaluate_with(&self.params)
+        }
+    }
+
+    // the child position (a null move without one), with the network's
+    // accumulator for its ply made from the parent's
+    fn play(&mut self, board: &ChessBoard, chess_move: Option<&ChessMove>, ply: i32) -> ChessBoard {
+        let mut child = *board;
+        match chess_move {
+            Some(chess_move) => child.make_move(chess_move),
+            None => child.make_null_move()
+        }
+
+        if let (Some(network), true) = (&self.network, self.uses_network()) {
+            let (parents, children) = self.accumulators.split_at_mut(ply as usize);
+            children[0].clone_from(&parents[ply as usize - 1]);
+
+            // a move the network can't follow ends its part in this search
+            let followed = match chess_move {
+                Some(chess_move) => network.update(&mut children[0], board, chess_move).is_ok(),
+                None => true
+            };
+
+            if !followed {
+                self.accumulators.clear();
+            }
+        }
+
+        child
+    }
+
+    fn terminal_score(&self, board: &ChessBoard, ply: i32) -> i32 {
+        // a variant win counts the same as a mate
+        match board.in_check(board.side_to_move()) || board.variant_outcome().is_some() {
+            true => -MATE + ply, // prefer the quickest mate
+            false => 0 // stalemate
+        }
+    }
+
+    fn negamax(&mut self, board: &ChessBoard, mut depth: u8, mut alpha: i32, beta: i32, ply: i32, allow_null: bool) -> i32 {
+        if self.out_of_time() {
+            return 0;
+        }
+
+        if ply as usize >= MAX_PLY - 1 {
+            return self.evaluate(board, ply);
+        }
+
+        let side = board.side_to_move();
+        let in_check = board.in_check(side);
+
+        // don't let a check push the evasion past the horizon. capped so a
+        // long run of checks can't keep the search going forever
+        if in_check && self.config.check_extensions && ply < 2 * self.root_depth as i32 {
+            depth += 1;
+        }
+
+        if depth == 0 {
+            return match self.config.quiescence {
+                true => self.quiescence(board, alpha, beta, ply, 0),
+                false => {
+                    self.nodes += 1;
+                    self.evaluate(board, ply)
+                }
+            };
+        }
+
+        self.nodes += 1;
+
+        let hash = board.hash();
+        let original_alpha = alpha;
+        let pv_node = beta - alpha > 1;
+
+        let entry = self.table.probe(hash, ply);
+        if let Some(entry) = entry {
+            if entry.depth >= depth && !pv_node {
+                match entry.bound {
+                    Bound::Exact => return entry.score,
+                    Bound::Lower if entry.score >= beta => return entry.score,
+                    Bound::Upper if entry.score <= alpha => return entry.score,
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(score) = self.probe_tablebase(board, hash, depth, alpha, beta, ply) {
+            return score;
+        }
+
+        let static_eval = match in_check {
+            true => -INFINITY,
+            false => self.evaluate(board, ply)
+        };
+
+        let prunable = !pv_node && !in_check && !is_mate_score(beta);
+
+        // so far ahead that even a generous margin per ply keeps it above beta
+        if self.config.reverse_futility && prunable && depth <= 3
+            && static_eval - REVERSE_FUTILITY_MARGIN * depth as i32 >= beta {
+            return static_eval;
+        }
+
+        // if passing the turn still fails high, a real move will too. not
+        // trusted without pieces, where zugzwang makes passing the best move
+        if self.config.null_move && allow_null && prunable && depth >= 3
+            && static_eval >= beta && board.non_pawn_material(side) > 0 {
+            let reduction = 2 + depth / 6;
+
+            let child = self.play(board, None, ply + 1);
+
+            let score = -self.negamax(&child, depth.saturating_sub(1 + reduction), -beta, -beta + 1, ply + 1, false);
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score >= beta {
+                return match is_mate_score(score) {
+                    true => beta,
+                    false => score
+                };
+            }
+        }
+
+        let futile = self.config.futility && prunable && (depth as usize) < FUTILITY_MARGINS.len()
+            && static_eval + FUTILITY_MARGINS[depth as usize] <= alpha;
+
+        let moves = board.legal_moves();
+        if moves.is_empty() {
+            return self.terminal_score(board, ply);
+        }
+
+        let table_move = entry.and_then(|entry| entry.best_move);
+        let picker = MovePicker::new(board, moves, &self.ordering, table_move, ply as usize);
+
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+
+        for (index, chess_move) in picker.enumerate() {
+            let child = self.play(board, Some(&chess_move), ply + 1);
+
+            let quiet = is_quiet(&chess_move);
+            let gives_check = child.in_check(side.opposite());
+
+            if futile && quiet && !gives_check && index > 0 {
+                continue;
+            }
+
+            // quiet moves this far down the list rarely matter, so look at
+            // them less deeply unless they prove otherwise
+            let reduction = match self.config.late_move_reductions && depth >= 3 && index >= 3
+                && quiet && !in_check && !gives_check {
+                true => match index >= 6 { true => 2, false => 1 }.min(depth - 2),
+                false => 0
+            };
+
+            let score = self.search_child(&child, depth, alpha, beta, ply + 1, index, reduction);
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(chess_move);
+            }
+
+            if score >= beta {
+                if quiet {
+                    self.ordering.record_cutoff(&chess_move, ply as usize, depth);
+                }
+                break;
+            }
+
+            alpha = alpha.max(score);
+        }
+
+        self.table.store(hash, ply, TableEntry {
+            hash,
+            best_move,
+            score: best_score,
+            depth,
+            bound: bound(best_score, original_alpha, beta)
+        });
+
+        best_score
+    }
+
+    // a win or loss from the tables only bounds the score, the search may
+    // still find a quicker mate, so those only end the node when they cut off
+    #[allow(clippy::too_many_arguments)]
+    fn probe_tablebase(&self, board: &ChessBoard, hash: u64, depth: u8, alpha: i32, beta: i32, ply: i32) -> Option<i32> {
+        let tablebase = self.tablebase.as_ref().filter(|tablebase| tablebase.covers(board))?;
+        let wdl = tablebase.probe_wdl(board)?;
+        let score = wdl.score(ply);
+
+        let bound = match wdl {
+            Wdl::Win => Bound::Lower,
+            Wdl::Loss => Bound::Upper,
+            _ => Bound::Exact
+        };
+
+        let cutoff = match bound {
+            Bound::Exact => true,
+            Bound::Lower => score >= beta,
+            Bound::Upper => score <= alpha
+        };
+
+        if !cutoff {
+            return None;
+        }
+
+        self.table.store(hash, ply, TableEntry {
+            hash,
+            best_move: None,
+            score,
+            depth: depth.saturating_add(6).min(MAX_PLY as u8),
+            bound
+        });
+
+        Some(score)
+    }
+
+    // searches only the moves that change material (and, if enabled, checks
+    // on the first ply) until the position is quiet, so the depth limit never
+    // cuts an exchange in half
+    pub fn quiescence(&mut self, board: &ChessBoard, mut alpha: i32, beta: i32, ply: i32, qply: u8) -> i32 {
+        self.nodes += 1;
+
+        if self.out_of_time() {
+            return 0;
+        }
+
+        if ply as usize >= MAX_PLY - 1 {
+            return self.evaluate(board, ply);
+        }
+
+        let side = board.side_to_move();
+        let in_check = board.in_check(side);
+
+        // in check every evasion has to be looked at, standing pat isn't an option
+        let stand_pat = match in_check {
+            true => -INFINITY,
+            false => self.evaluate(board, ply)
+        };
+
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+
+        alpha = alpha.max(stand_pat);
+
+        let moves = board.legal_moves();
+        if moves.is_empty() && (in_check || board.variant_outcome().is_some()) {
+            return -MATE + ply;
+        }
+
+        let with_checks = self.config.quiescence_checks && qply == 0;
+
+        let mut candidates: Vec<(i32, ChessMove)> = moves.into_iter()
+            .filter(|chess_move| in_check || chess_move.is_capture() || chess_move.promotion().is_some() || (with_checks && {
+                let mut child = *board;
+                child.make_move(chess_move);
+                child.in_check(side.opposite())
+            }))
+            .map(|chess_move| (captured_value(board, &chess_move), chess_move))
+            .collect();
+
+        // biggest gains first
+        candidates.sort_by_key(|(gain, _)| -gain);
+
+        let mut best = stand_pat;
+
+        for (gain, chess_move) in candidates {
+            if !in_check && chess_move.is_capture() {
+                if self.config.delta_pruning && stand_pat + gain + DELTA_MARGIN < alpha {
+                    continue;
+                }
+
+                // losing captures only make things worse
+                if chess_move.promotion().is_none() && board.see(&chess_move) < 0 {
+                    continue;
+                }
+            }
+
+            let child = self.play(board, Some(&chess_move), ply + 1);
+
+            let score = -self.quiescence(&child, -beta, -alpha, ply + 1, qply + 1);
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score >= beta {
+                return score;
+            }
+
+            best = best.max(score);
+            alpha = alpha.max(score);
+        }
+
+        best
+    }
+}
+
+fn bound(score: i32, alpha: i32, beta: i32) -> Bound {
+    match score {
+        score if score >= beta => Bound::Lower,
+        score if score <= alpha => Bound::Upper,
+        _ => Bound::Exact
+    }
+}
@@ -0,0 +1,71 @@
+use crate::board::ChessBoard;
+use crate::moves::{ChessMove, ChessMoveExt};
+use crate::pieces::ChessPiece;
+use crate::math::V2;
+
+// static exchange evaluation. plays out every capture on the destination tile,
+// least valuable attacker first, and lets each side stop once continuing would
+// lose material. the board copy is updated as pieces come off, so sliders
+// lined up behind each other join in on their own
+
+#[allow(dead_code)]
+impl ChessBoard {
+    // net material won by the side making the move, in centipawns
+    pub fn see(&self, chess_move: &ChessMove) -> i32 {
+        let src = chess_move.src();
+        let dst = chess_move.dst();
+
+        let mut board = *self;
+        let mover = board.tile(&src);
+
+        let (mut attacker_value, mut side) = match (mover.piece(), mover.color()) {
+            (Some(piece), Some(color)) => (piece.value(), color.opposite()),
+            _ => return 0
+        };
+
+        // an en passant victim sits beside the capturing pawn, not on dst
+        let captured_value = match chess_move.ext() {
+            ChessMoveExt::EpCapture => {
+                board.clear(&V2 { x: dst.x, y: src.y });
+                ChessPiece::Pawn.value()
+            },
+            _ => board.tile(&dst).piece().map_or(0, |piece| piece.value())
+        };
+
+        board.clear(&src);
+        board.place(&dst, mover);
+
+        // gains[n] is what the side making the nth capture has won if the
+        // exchange stops right after it
+        let mut gains = vec![captured_value];
+
+        loop {
+            let cheapest = board.attackers_of(&dst, side).into_iter()
+                .min_by_key(|attacker| board.tile(attacker).piece().map_or(0, |piece| piece.value()));
+
+            let attacker = match cheapest {
+                Some(attacker) => attacker,
+                None => break
+            };
+
+            gains.push(attacker_value - gains.last().unwrap());
+
+            let tile = board.tile(&attacker);
+            attacker_value = tile.piece().map_or(0, |piece| piece.value());
+
+            board.clear(&attacker);
+            board.place(&dst, tile);
+            side = side.opposite();
+        }
+
+        // walk back up the exchange, each side taking the better of capturing
+        // or standing pat
+        while gains.len() > 1 {
+            let last = gains.pop().unwrap();
+            let previous = gains.last_mut().unwrap();
+            *previous = -(-*previous).max(last);
+        }
+
+        gains[0]
+    }
+}
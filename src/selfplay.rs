@@ -0,0 +1,454 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::board::ChessBoard;
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::search::{Searcher, SearchConfig};
+use crate::time::{TimeControl, TimeManager};
+use crate::ordering::MAX_PLY;
+use crate::eval::EvalParams;
+use crate::nnue::Network;
+use crate::pgn::{PgnGame, GameResult, insufficient_material};
+
+// self-play matches between two engine configurations. every opening is
+// played twice with the colors swapped, games end naturally or by
+// adjudication, and the results add up to an elo estimate, the likelihood
+// of superiority and a sequential probability ratio test
+
+// z for a two sided 95% interval
+const CONFIDENCE_95: f64 = 1.959964;
+
+#[derive(Clone, Debug)]
+pub struct Engine {
+    pub name: String,
+    pub config: SearchConfig,
+    pub params: EvalParams,
+    pub network: Option<Arc<Network>>
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MoveLimit {
+    Depth(u8),
+    MoveTime(Duration),
+    Clock(TimeControl) // the same for both sides, running out loses
+}
+
+// scores are centipawns from white's side, as both engines report them
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Adjudication {
+    pub win_score: i32, // a side is this far ahead..
+    pub win_plies: usize, // ..for this many plies in a row, by both engines
+    pub draw_score: i32, // the score stays this close to even..
+    pub draw_plies: usize, // ..for this many plies in a row..
+    pub draw_after: usize, // ..once the game is this many plies long
+    pub max_plies: usize // a draw, however the game stands
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64, // the change is no better than this..
+    pub elo1: f64, // ..or at least this good
+    pub alpha: f64, // false positive rate
+    pub beta: f64 // false negative rate
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SprtDecision { AcceptH0, AcceptH1, Continue }
+
+// from the first engine's side
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MatchStats {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32
+}
+
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    pub pgn: PgnGame,
+    pub result: GameResult,
+    pub termination: &'static str,
+    pub first_is_white: bool
+}
+
+pub struct MatchRunner {
+    engines: [Engine; 2],
+    limit: MoveLimit,
+    adjudication: Adjudication,
+    openings: Vec<ChessBoard>,
+    sprt: Option<Sprt>,
+    event: String,
+    stats: MatchStats,
+    played: usize
+}
+
+fn erf(x: f64) -> f64 {
+    // abramowitz and stegun 7.1.26, good to about 1e-7
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let value = 1.0 - poly * (-x * x).exp();
+
+    match x < 0.0 {
+        true => -value,
+        false => value
+    }
+}
+
+fn score_to_elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+// one fen or epd per line, blank lines and # comments skipped. epd
+// operations after the four board fields are ignored
+pub fn load_openings<P: AsRef<Path>>(path: P) -> Result<Vec<ChessBoard>, &'static str> {
+    let text = fs::read_to_string(path).map_err(|_| "could not read openings")?;
+
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let counters = words.len() >= 6 && words[4..6].iter().all(|word| word.parse::<u32>().is_ok());
+            let length = match counters {
+                true => 6,
+                false => words.len().min(4)
+            };
+            ChessBoard::from_fen(ChessPieceColor::White, &words[..length].join(" "))
+        })
+        .collect()
+}
+
+#[allow(dead_code)]
+impl Engine {
+    pub fn new(name: &str, config: SearchConfig) -> Self {
+        Engine {
+            name: name.to_string(),
+            config,
+            params: EvalParams::default(),
+            network: None
+        }
+    }
+
+    fn searcher(&self) -> Searcher {
+        let mut searcher = Searcher::new(self.config);
+        searcher.set_eval_params(self.params.clone());
+        searcher.set_network(self.network.clone());
+        searcher
+    }
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Adjudication {
+            win_score: 1000,
+            win_plies: 8,
+            draw_score: 10,
+            draw_plies: 16,
+            draw_after: 80,
+            max_plies: 400
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Sprt {
+    // log likelihood ratio of elo1 over elo0, using the normal approximation
+    // of the mean score
+    pub fn llr(&self, stats: &MatchStats) -> f64 {
+        let games = stats.games() as f64;
+        if games == 0.0 {
+            return 0.0;
+        }
+
+        let score = stats.score();
+        let variance = (stats.wins as f64 * (1.0 - score).powi(2)
+            + stats.draws as f64 * (0.5 - score).powi(2)
+            + stats.losses as f64 * score.powi(2)) / games;
+
+        if variance <= 0.0 {
+            return 0.0;
+        }
+
+        let (score0, score1) = (elo_to_score(self.elo0), elo_to_score(self.elo1));
+        (score1 - score0) * (2.0 * score - score0 - score1) / (2.0 * variance / games)
+    }
+
+    // below the first the change is rejected, above the second accepted
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    pub fn decision(&self, stats: &MatchStats) -> SprtDecision {
+        let llr = self.llr(stats);
+        let (lower, upper) = self.bounds();
+
+        match (llr <= lower, llr >= upper) {
+            (true, _) => SprtDecision::AcceptH0,
+            (_, true) => SprtDecision::AcceptH1,
+            _ => SprtDecision::Continue
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl MatchStats {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn add(&mut self, result: GameResult, first_is_white: bool) {
+        match (result, first_is_white) {
+            (GameResult::WhiteWins, true) | (GameResult::BlackWins, false) => self.wins += 1,
+            (GameResult::WhiteWins, false) | (GameResult::BlackWins, true) => self.losses += 1,
+            (GameResult::Draw, _) => self.draws += 1,
+            (GameResult::Unfinished, _) => ()
+        }
+    }
+
+    // points per game, 0.5 when nothing has been played
+    pub fn score(&self) -> f64 {
+        match self.games() {
+            0 => 0.5,
+            games => (self.wins as f64 + self.draws as f64 / 2.0) / games as f64
+        }
+    }
+
+    // infinite after a clean sweep either way
+    pub fn elo(&self) -> f64 {
+        score_to_elo(self.score())
+    }
+
+    // half the width of the 95% confidence interval around the elo
+    pub fn elo_error(&self) -> f64 {
+        let games = self.games() as f64;
+        if games == 0.0 {
+            return f64::INFINITY;
+        }
+
+        let score = self.score();
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2)) / games;
+        let margin = CONFIDENCE_95 * (variance / games).sqrt();
+
+        let low = (score - margin).max(f64::MIN_POSITIVE);
+        let high = (score + margin).min(1.0 - f64::EPSILON);
+        (score_to_elo(high) - score_to_elo(low)) / 2.0
+    }
+
+    // likelihood of superiority, how sure we can be the first engine is the
+    // stronger one. draws don't tell the two apart
+    pub fn los(&self) -> f64 {
+        let decisive = (self.wins + self.losses) as f64;
+        if decisive == 0.0 {
+            return 0.5;
+        }
+
+        0.5 * (1.0 + erf((self.wins as f64 - self.losses as f64) / (2.0 * decisive).sqrt()))
+    }
+}
+
+#[allow(dead_code)]
+impl MatchRunner {
+    pub fn new(first: Engine, second: Engine, limit: MoveLimit) -> Self {
+        MatchRunner {
+            engines: [first, second],
+            limit,
+            adjudication: Adjudication::default(),
+            openings: vec![ChessBoard::new(ChessPieceColor::White)],
+            sprt: None,
+            event: "self-play".to_string(),
+            stats: MatchStats::default(),
+            played: 0
+        }
+    }
+
+    // keeps the start position if the suite is empty
+    pub fn set_openings(&mut self, openings: Vec<ChessBoard>) {
+        if !openings.is_empty() {
+            self.openings = openings;
+        }
+    }
+
+    pub fn set_adjudication(&mut self, adjudication: Adjudication) {
+        self.adjudication = adjudication;
+    }
+
+    pub fn set_sprt(&mut self, sprt: Option<Sprt>) {
+        self.sprt = sprt;
+    }
+
+    pub fn set_event(&mut self, event: &str) {
+        self.event = event.to_string();
+    }
+
+    pub fn stats(&self) -> MatchStats {
+        self.stats
+    }
+
+    // plays up to `games` more games, openings in turn and each one twice
+    // with the colors swapped, calling back after every game. stops early
+    // once the sprt, if there is one, has decided
+    pub fn run<F: FnMut(&GameRecord, &MatchStats)>(&mut self, games: usize, mut on_game: F) -> MatchStats {
+        for _ in 0..games {
+            if let Some(sprt) = self.sprt {
+                if sprt.decision(&self.stats) != SprtDecision::Continue {
+                    break;
+                }
+            }
+
+            let opening = self.openings[(self.played / 2) % self.openings.len()];
+            let first_is_white = self.played.is_multiple_of(2);
+
+            let record = self.play_game(&opening, first_is_white);
+            self.stats.add(record.result, first_is_white);
+            self.played += 1;
+
+            on_game(&record, &self.stats);
+        }
+
+        self.stats
+    }
+
+    pub fn play_game(&self, opening: &ChessBoard, first_is_white: bool) -> GameRecord {
+        let mut searchers = [self.engines[0].searcher(), self.engines[1].searcher()];
+        let white = match first_is_white {
+            true => 0,
+            false => 1
+        };
+
+        let mut pgn = PgnGame::new(*opening);
+        pgn.set_tag("Event", &self.event);
+        pgn.set_tag("Round", &(self.played + 1).to_string());
+        pgn.set_tag("White", &self.engines[white].name);
+        pgn.set_tag("Black", &self.engines[1 - white].name);
+
+        let mut clocks = match self.limit {
+            MoveLimit::Clock(control) => [control; 2],
+            _ => [TimeControl { remaining: Duration::MAX, increment: Duration::ZERO, moves_to_go: None }; 2]
+        };
+
+        let mut board = *opening;
+        let mut history = vec![board.hash()];
+        let mut halfmove_clock = 0;
+        let (mut win_streak, mut draw_streak) = (0, 0);
+        let mut last_sign = 0;
+
+        let (result, termination) = loop {
+            let mover = board.side_to_move();
+            let moves = board.legal_moves();
+
+            if moves.is_empty() {
+                break match board.in_check(mover) {
+                    true => (GameResult::win_for(mover.opposite()), "checkmate"),
+                    false => (GameResult::Draw, "stalemate")
+                };
+            }
+
+            if history.iter().filter(|hash| **hash == board.hash()).count() >= 3 {
+                break (GameResult::Draw, "threefold repetition");
+            }
+
+            if halfmove_clock >= 100 {
+                break (GameResult::Draw, "fifty move rule");
+            }
+
+            if insufficient_material(&board) {
+                break (GameResult::Draw, "insufficient material");
+            }
+
+            if pgn.moves().len() >= self.adjudication.max_plies {
+                break (GameResult::Draw, "adjudication");
+            }
+
+            let side = match mover {
+                ChessPieceColor::White => white,
+                ChessPieceColor::Black => 1 - white
+            };
+
+            let start = Instant::now();
+            let search = match self.limit {
+                MoveLimit::Depth(depth) => searchers[side].search(&board, depth),
+                MoveLimit::MoveTime(time) => searchers[side].search_timed(&board, MAX_PLY as u8 / 2, TimeManager::fixed(time)),
+                MoveLimit::Clock(_) => searchers[side].search_timed(&board, MAX_PLY as u8 / 2, TimeManager::new(&clocks[side]))
+            };
+            let elapsed = start.elapsed();
+
+            if let MoveLimit::Clock(control) = self.limit {
+                let clock = &mut clocks[side];
+                if elapsed > clock.remaining {
+                    break (GameResult::win_for(mover.opposite()), "time forfeit");
+                }
+
+                clock.remaining = clock.remaining - elapsed + control.increment;
+                if let Some(moves_to_go) = clock.moves_to_go {
+                    clock.moves_to_go = match moves_to_go {
+                        0 | 1 => control.moves_to_go,
+                        _ => Some(moves_to_go - 1)
+                    };
+
+                    // a new time control starts with another full allowance
+                    if moves_to_go <= 1 {
+                        clock.remaining += control.remaining;
+                    }
+                }
+            }
+
+            let chess_move = search.best_move.unwrap_or(moves[0]);
+
+            // adjudication counts plies where the score agrees, from white's side
+            let score = match mover {
+                ChessPieceColor::White => search.score,
+                ChessPieceColor::Black => -search.score
+            };
+
+            let sign = match (score >= self.adjudication.win_score, score <= -self.adjudication.win_score) {
+                (true, _) => 1,
+                (_, true) => -1,
+                _ => 0
+            };
+            win_streak = match sign != 0 && sign == last_sign {
+                true => win_streak + 1,
+                false => (sign != 0) as usize
+            };
+            last_sign = sign;
+
+            draw_streak = match score.abs() <= self.adjudication.draw_score {
+                true => draw_streak + 1,
+                false => 0
+            };
+
+            let capture_or_pawn = chess_move.is_capture() || board.tile(&chess_move.src()).piece() == Some(ChessPiece::Pawn);
+            halfmove_clock = match capture_or_pawn {
+                true => 0,
+                false => halfmove_clock + 1
+            };
+
+            board.make_move(&chess_move);
+            pgn.push(chess_move);
+            history.push(board.hash());
+
+            if win_streak >= self.adjudication.win_plies {
+                break match sign > 0 {
+                    true => (GameResult::WhiteWins, "adjudication"),
+                    false => (GameResult::BlackWins, "adjudication")
+                };
+            }
+
+            if draw_streak >= self.adjudication.draw_plies && pgn.moves().len() >= self.adjudication.draw_after {
+                break (GameResult::Draw, "adjudication");
+            }
+        };
+
+        pgn.set_result(result);
+        pgn.set_tag("Termination", termination);
+
+        GameRecord { pgn, result, termination, first_is_white }
+    }
+}
@@ -0,0 +1,496 @@
+use std::fmt;
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{self, Visitor, MapAccess, SeqAccess};
+use serde::ser::SerializeMap;
+
+use crate::board::{ChessBoard, ChessTile};
+use crate::moves::{ChessMove, ChessMoveExt};
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::math::V2;
+use crate::variant::Variant;
+use crate::pgn::{PgnGame, GameResult};
+
+// serde support, behind the serde feature. readable formats like json get
+// the notations people already know: fen for positions, uci for the moves
+// of a game and pieces and colors by name. binary formats get the bits the
+// types are kept in, which come back exactly as they went out.
+//
+// a move on its own doesn't know which way up its board is, so it can't
+// name its squares. it's written as the tiles it goes between, counted like
+// V2 from the top left, and the kind of move. inside a game the board is
+// known and the moves are uci
+
+const KINDS: [(ChessMoveExt, &str); 15] = [
+    (ChessMoveExt::Quiet, "quiet"),
+    (ChessMoveExt::DoublePawnPush, "double_pawn_push"),
+    (ChessMoveExt::KingCastle, "king_castle"),
+    (ChessMoveExt::QueenCastle, "queen_castle"),
+    (ChessMoveExt::Captures, "capture"),
+    (ChessMoveExt::EpCapture, "en_passant"),
+    (ChessMoveExt::Drop, "drop"),
+    (ChessMoveExt::KnightPromotion, "knight_promotion"),
+    (ChessMoveExt::BishopPromotion, "bishop_promotion"),
+    (ChessMoveExt::RookPromotion, "rook_promotion"),
+    (ChessMoveExt::QueenPromotion, "queen_promotion"),
+    (ChessMoveExt::KnightPromoCapture, "knight_promotion_capture"),
+    (ChessMoveExt::BishopPromoCapture, "bishop_promotion_capture"),
+    (ChessMoveExt::RookPromoCapture, "rook_promotion_capture"),
+    (ChessMoveExt::QueenPromoCapture, "queen_promotion_capture")
+];
+
+const PIECE_NAMES: [(ChessPiece, &str); 9] = [
+    (ChessPiece::Pawn, "pawn"), (ChessPiece::Knight, "knight"), (ChessPiece::Bishop, "bishop"),
+    (ChessPiece::Rook, "rook"), (ChessPiece::Queen, "queen"), (ChessPiece::King, "king"),
+    (ChessPiece::Archbishop, "archbishop"), (ChessPiece::Chancellor, "chancellor"), (ChessPiece::Amazon, "amazon")
+];
+
+// files, ranks, player, chess960, variant, turn (2), last move (4), checks
+// (2) and pockets (10), then a byte for each tile a row at a time
+const BOARD_HEADER: usize = 23;
+
+impl Serialize for ChessPieceColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match serializer.is_human_readable() {
+            true => serializer.serialize_str(match self {
+                ChessPieceColor::White => "white",
+                ChessPieceColor::Black => "black"
+            }),
+            false => serializer.serialize_bool(*self == ChessPieceColor::White)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ChessPieceColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return Ok(match bool::deserialize(deserializer)? {
+                true => ChessPieceColor::White,
+                false => ChessPieceColor::Black
+            });
+        }
+
+        match String::deserialize(deserializer)?.as_str() {
+            "white" => Ok(ChessPieceColor::White),
+            "black" => Ok(ChessPieceColor::Black),
+            _ => Err(de::Error::custom("not a color"))
+        }
+    }
+}
+
+impl Serialize for ChessPiece {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match serializer.is_human_readable() {
+            true => {
+                let (_, name) = PIECE_NAMES.iter().find(|(piece, _)| piece == self).unwrap();
+                serializer.serialize_str(name)
+            },
+            false => serializer.serialize_u8(*self as u8)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ChessPiece {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let piece = match deserializer.is_human_readable() {
+            true => {
+                let name = String::deserialize(deserializer)?;
+                PIECE_NAMES.iter().find(|(_, other)| *other == name).map(|(piece, _)| *piece)
+            },
+            false => ChessPiece::from(u8::deserialize(deserializer)?).ok().flatten()
+        };
+
+        piece.ok_or_else(|| de::Error::custom("not a valid piece"))
+    }
+}
+
+// x and y, the same in either kind of format
+impl Serialize for V2 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.x, self.y).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for V2 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (x, y) = <(u16, u16)>::deserialize(deserializer)?;
+        Ok(V2 { x, y })
+    }
+}
+
+// an empty tile is null
+#[derive(Serialize, Deserialize)]
+struct TileFields {
+    piece: ChessPiece,
+    color: ChessPieceColor,
+    #[serde(default)]
+    moved: bool,
+    #[serde(default)]
+    promoted: bool
+}
+
+impl Serialize for ChessTile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_u8(self.bits());
+        }
+
+        let fields = match (self.piece(), self.color()) {
+            (Some(piece), Some(color)) => Some(TileFields { piece, color, moved: self.has_moved(), promoted: self.is_promoted() }),
+            _ => None
+        };
+
+        fields.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChessTile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return ChessTile::from_bits(u8::deserialize(deserializer)?).map_err(de::Error::custom);
+        }
+
+        let Some(fields) = Option::<TileFields>::deserialize(deserializer)? else {
+            return Ok(ChessTile::new(None, None).unwrap());
+        };
+
+        let mut tile = ChessTile::new(Some(fields.piece), Some(fields.color)).map_err(de::Error::custom)?;
+        if fields.moved {
+            tile = tile.moved();
+        }
+        if fields.promoted {
+            tile = tile.promoted();
+        }
+
+        Ok(tile)
+    }
+}
+
+// a drop has the piece instead of a tile it came from, and a promotion to a
+// fairy piece names it on top of a queen promotion
+#[derive(Serialize, Deserialize)]
+struct MoveFields {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from: Option<V2>,
+    to: V2,
+    kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    piece: Option<ChessPiece>
+}
+
+// four bits for each coordinate and four for the kind, then four for the
+// piece of a fairy promotion
+fn fits_u32(bits: u32) -> bool {
+    match (bits >> 24, bits >> 20 & 0xf) {
+        (0, 0) => true,
+        (0, fairy) => ChessMove(bits).ext().promotion_piece() == Some(ChessPiece::Queen)
+            && ChessPiece::from(fairy as u8).ok().flatten().is_some_and(|piece| !piece.is_standard()),
+        _ => false
+    }
+}
+
+impl Serialize for ChessMove {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_u32(self.0);
+        }
+
+        let (_, kind) = KINDS.iter().find(|(ext, _)| *ext == self.ext()).unwrap();
+        MoveFields {
+            from: (!self.is_drop()).then(|| self.src()),
+            to: self.dst(),
+            kind: kind.to_string(),
+            piece: self.dropped().or(self.promotion().filter(|piece| !piece.is_standard()))
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChessMove {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return match u32::deserialize(deserializer)? {
+                bits if fits_u32(bits) => Ok(ChessMove(bits)),
+                _ => Err(de::Error::custom("not a valid move"))
+            };
+        }
+
+        let fields = MoveFields::deserialize(deserializer)?;
+        let ext = KINDS.iter()
+            .find(|(_, kind)| *kind == fields.kind)
+            .map(|(ext, _)| *ext)
+            .ok_or_else(|| de::Error::custom("not a kind of move"))?;
+
+        let fits = |v2: &V2| v2.x < 16 && v2.y < 16;
+
+        match (ext, fields.from, fields.piece) {
+            (ChessMoveExt::Drop, None, Some(piece)) if fits(&fields.to) => Ok(ChessMove::drop(piece, &fields.to)),
+            (ChessMoveExt::Drop, _, _) => Err(de::Error::custom("a drop needs a piece and no tile it came from")),
+            (ChessMoveExt::QueenPromotion | ChessMoveExt::QueenPromoCapture, Some(from), Some(piece))
+                if !piece.is_standard() && fits(&from) && fits(&fields.to) => Ok(ChessMove::promote(&from, &fields.to, piece, ext.is_capture())),
+            (ext, Some(from), None) if fits(&from) && fits(&fields.to) => Ok(ChessMove::raw(&from, &fields.to, ext as u16)),
+            _ => Err(de::Error::custom("a move needs the tiles it goes between"))
+        }
+    }
+}
+
+// the variant and which side is at the bottom aren't in the fen. a bare fen
+// string reads as well, white at the bottom
+#[derive(Serialize, Deserialize)]
+struct BoardFields {
+    fen: String,
+    #[serde(default)]
+    variant: Option<String>,
+    #[serde(default)]
+    player: Option<ChessPieceColor>,
+    #[serde(default)]
+    chess960: bool
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BoardText {
+    Fen(String),
+    Fields(BoardFields)
+}
+
+fn board_bytes(board: &ChessBoard) -> Vec<u8> {
+    let variant = Variant::ALL.iter().position(|variant| *variant == board.variant()).unwrap();
+    let last_move = board.last_turn().map_or(0, |chess_move| chess_move.0);
+
+    let mut bytes = vec![
+        board.files() as u8,
+        board.ranks() as u8,
+        (board.player_color() == ChessPieceColor::White) as u8,
+        board.is_chess960() as u8,
+        variant as u8
+    ];
+
+    bytes.extend(board.turn().to_le_bytes());
+    bytes.extend(last_move.to_le_bytes());
+    bytes.extend(board.checks());
+    bytes.extend(board.pockets().iter().flatten());
+    bytes.extend(board.squares().map(|v2| board.tile(&v2).bits()));
+
+    bytes
+}
+
+fn board_from_bytes(bytes: &[u8]) -> Result<ChessBoard, &'static str> {
+    if bytes.len() < BOARD_HEADER {
+        return Err("not a board");
+    }
+
+    let player = match bytes[2] {
+        0 => ChessPieceColor::Black,
+        _ => ChessPieceColor::White
+    };
+
+    let mut board = ChessBoard::empty(player, bytes[0] as u16, bytes[1] as u16)?;
+    let tiles = &bytes[BOARD_HEADER..];
+    if tiles.len() != (board.files() * board.ranks()) as usize {
+        return Err("not a board");
+    }
+
+    for (v2, bits) in board.squares().zip(tiles) {
+        board.place(&v2, ChessTile::from_bits(*bits)?);
+    }
+
+    board.set_chess960(bytes[3] != 0);
+    board.set_variant(*Variant::ALL.get(bytes[4] as usize).ok_or("not a variant")?);
+    board.set_turn(u16::from_le_bytes([bytes[5], bytes[6]]));
+
+    let on_board = |v2: V2| board.contains(v2.x as i8, v2.y as i8);
+    let last_move = match u32::from_le_bytes(bytes[7..11].try_into().unwrap()) {
+        0 => None,
+        bits if fits_u32(bits) && on_board(ChessMove(bits).src()) && on_board(ChessMove(bits).dst()) => Some(ChessMove(bits)),
+        _ => return Err("not a valid move")
+    };
+    board.set_last_move(last_move);
+
+    board.set_checks([bytes[11], bytes[12]]);
+    let mut pockets = [[0; 5]; 2];
+    for (index, count) in bytes[13..BOARD_HEADER].iter().enumerate() {
+        pockets[index / 5][index % 5] = *count;
+    }
+    board.set_pockets(pockets);
+
+    Ok(board)
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+        Ok(bytes.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(bytes)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = vec![];
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
+
+impl Serialize for ChessBoard {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_bytes(&board_bytes(self));
+        }
+
+        BoardFields {
+            fen: self.to_fen(),
+            variant: Some(self.variant().uci_name().to_string()),
+            player: Some(self.player_color()),
+            chess960: self.is_chess960()
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChessBoard {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            let bytes = deserializer.deserialize_byte_buf(BytesVisitor)?;
+            return board_from_bytes(&bytes).map_err(de::Error::custom);
+        }
+
+        let fields = match BoardText::deserialize(deserializer)? {
+            BoardText::Fen(fen) => BoardFields { fen, variant: None, player: None, chess960: false },
+            BoardText::Fields(fields) => fields
+        };
+
+        let mut board = ChessBoard::from_fen_sized(fields.player.unwrap_or(ChessPieceColor::White), &fields.fen)
+            .map_err(de::Error::custom)?;
+
+        if let Some(name) = fields.variant {
+            board.set_variant(Variant::parse(&name).ok_or_else(|| de::Error::custom("not a variant"))?);
+        }
+        if fields.chess960 {
+            board.set_chess960(true);
+        }
+
+        Ok(board)
+    }
+}
+
+// tags as an object, in the order they were set
+struct Tags(Vec<(String, String)>);
+
+impl Serialize for Tags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, value) in &self.0 {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+struct TagsVisitor;
+
+impl<'de> Visitor<'de> for TagsVisitor {
+    type Value = Tags;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of tags")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut tags = vec![];
+        while let Some(tag) = map.next_entry()? {
+            tags.push(tag);
+        }
+        Ok(Tags(tags))
+    }
+}
+
+impl<'de> Deserialize<'de> for Tags {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(TagsVisitor)
+    }
+}
+
+// the moves are uci strings in readable formats and the moves' own bits
+// otherwise. either way they have to be legal to be read back
+#[derive(Serialize, Deserialize)]
+struct GameFields<M> {
+    tags: Tags,
+    start: ChessBoard,
+    moves: Vec<M>,
+    result: String
+}
+
+impl Serialize for PgnGame {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (tags, start, result) = (Tags(self.tags().to_vec()), *self.start(), self.result().as_str().to_string());
+
+        if !serializer.is_human_readable() {
+            return GameFields { tags, start, moves: self.moves().to_vec(), result }.serialize(serializer);
+        }
+
+        let mut board = start;
+        let moves = self.moves().iter()
+            .map(|chess_move| {
+                let text = board.move_to_uci(chess_move);
+                board.make_move(chess_move);
+                text
+            })
+            .collect();
+
+        GameFields { tags, start, moves, result }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PgnGame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (tags, start, result, moves) = match deserializer.is_human_readable() {
+            true => {
+                let fields = GameFields::<String>::deserialize(deserializer)?;
+                let mut board = fields.start;
+                let mut moves = vec![];
+
+                for text in &fields.moves {
+                    let chess_move = board.parse_uci_move(text).ok_or_else(|| de::Error::custom("illegal move in game"))?;
+                    board.make_move(&chess_move);
+                    moves.push(chess_move);
+                }
+
+                (fields.tags, fields.start, fields.result, moves)
+            },
+            false => {
+                let fields = GameFields::<ChessMove>::deserialize(deserializer)?;
+                let mut board = fields.start;
+
+                for chess_move in &fields.moves {
+                    if !board.legal_moves().contains(chess_move) {
+                        return Err(de::Error::custom("illegal move in game"));
+                    }
+                    board.make_move(chess_move);
+                }
+
+                (fields.tags, fields.start, fields.result, fields.moves)
+            }
+        };
+
+        let mut game = PgnGame::new(start);
+        for (name, value) in &tags.0 {
+            game.set_tag(name, value);
+        }
+        for chess_move in moves {
+            game.push(chess_move);
+        }
+        game.set_result(GameResult::parse(&result).ok_or_else(|| de::Error::custom("not a game result"))?);
+
+        Ok(game)
+    }
+}
@@ -0,0 +1,249 @@
+use crate::board::ChessBoard;
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::math::V2;
+
+// standalone svg diagrams. squares are rects, pieces the unicode chess
+// glyphs drawn as text, arrows lines with a marker head. like the text
+// renderer the board is drawn from a side, its a file on the left
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgTheme {
+    pub light: String,
+    pub dark: String,
+    pub highlight: String, // marked squares and the last move
+    pub check: String,
+    pub arrow: String,
+    pub labels: String, // coordinates, on the border
+    pub border: String
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgOptions {
+    pub size: u32, // width and height in pixels, border included
+    pub coordinates: bool,
+    pub flipped: bool, // seen from the other side than the player's
+    pub last_move: bool, // highlight the last move and a king in check
+    pub highlights: Vec<V2>,
+    pub arrows: Vec<(V2, V2)>,
+    pub theme: SvgTheme
+}
+
+#[allow(dead_code)]
+impl SvgTheme {
+    pub fn brown() -> Self {
+        SvgTheme {
+            light: "#f0d9b5".to_string(),
+            dark: "#b58863".to_string(),
+            highlight: "#cdd26a".to_string(),
+            check: "#e05050".to_string(),
+            arrow: "#15781b".to_string(),
+            labels: "#f0d9b5".to_string(),
+            border: "#404040".to_string()
+        }
+    }
+
+    pub fn blue() -> Self {
+        SvgTheme {
+            light: "#dee3e6".to_string(),
+            dark: "#8ca2ad".to_string(),
+            highlight: "#9bc700".to_string(),
+            check: "#e05050".to_string(),
+            arrow: "#003088".to_string(),
+            labels: "#dee3e6".to_string(),
+            border: "#2f3e46".to_string()
+        }
+    }
+}
+
+impl Default for SvgTheme {
+    fn default() -> Self {
+        Self::brown()
+    }
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            size: 400,
+            coordinates: true,
+            flipped: false,
+            last_move: true,
+            highlights: vec![],
+            arrows: vec![],
+            theme: SvgTheme::default()
+        }
+    }
+}
+
+fn glyph(piece: ChessPiece) -> char {
+    match piece {
+        ChessPiece::King => '♚',
+        ChessPiece::Queen => '♛',
+        ChessPiece::Rook => '♜',
+        ChessPiece::Bishop => '♝',
+        ChessPiece::Knight => '♞',
+        ChessPiece::Pawn => '♟',
+        // no glyphs for these, their letters will do
+        ChessPiece::Archbishop => 'A',
+        ChessPiece::Chancellor => 'C',
+        ChessPiece::Amazon => 'M'
+    }
+}
+
+#[allow(dead_code)]
+impl ChessBoard {
+    // column and row on the diagram, counted from the top left
+    fn diagram_square(&self, v2: &V2, viewer: ChessPieceColor) -> (u32, u32) {
+        let index = self.square_index(v2) as u32;
+        let (files, ranks) = (self.files() as u32, self.ranks() as u32);
+        let (file, rank) = (index % files, index / files);
+
+        match viewer {
+            ChessPieceColor::White => (file, ranks - 1 - rank),
+            ChessPieceColor::Black => (files - 1 - file, rank)
+        }
+    }
+
+    pub fn to_svg(&self, options: &SvgOptions) -> String {
+        let viewer = match options.flipped {
+            true => self.player_color().opposite(),
+            false => self.player_color()
+        };
+
+        let size = options.size as f64;
+        let border = match options.coordinates {
+            true => size / 20.0,
+            false => 0.0
+        };
+        // square tiles, a board wider than it is tall leaves room below
+        let square = (size - 2.0 * border) / self.files().max(self.ranks()) as f64;
+        let theme = &options.theme;
+
+        // top left corner and centre of a square
+        let corner = |v2: &V2| {
+            let (column, row) = self.diagram_square(v2, viewer);
+            (border + column as f64 * square, border + row as f64 * square)
+        };
+        let centre = |v2: &V2| {
+            let (x, y) = corner(v2);
+            (x + square / 2.0, y + square / 2.0)
+        };
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{size}\" height=\"{size}\" viewBox=\"0 0 {size} {size}\">\n",
+            size = options.size
+        );
+
+        svg.push_str(&format!(
+            "<defs><marker id=\"arrowhead\" viewBox=\"0 0 10 10\" refX=\"5\" refY=\"5\" markerWidth=\"3\" markerHeight=\"3\" orient=\"auto\"><path d=\"M0,0 L10,5 L0,10 z\" fill=\"{}\"/></marker></defs>\n",
+            theme.arrow
+        ));
+
+        if options.coordinates {
+            svg.push_str(&format!("<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>\n", size, size, theme.border));
+        }
+
+        let mut marked = options.highlights.clone();
+        if let Some(last_move) = self.last_turn().filter(|_| options.last_move) {
+            marked.push(last_move.src());
+            marked.push(last_move.dst());
+        }
+
+        let mover = self.side_to_move();
+        let in_check = options.last_move && self.in_check(mover);
+
+        let files = self.files() as usize;
+
+        for v2 in self.squares() {
+            let (x, y) = corner(&v2);
+
+            // a1 is dark
+            let index = self.square_index(&v2);
+            let light = (index % files + index / files) % 2 == 1;
+            let tile = self.tile(&v2);
+
+            let fill = match (tile.piece(), tile.color()) {
+                (Some(ChessPiece::King), Some(color)) if in_check && color == mover => &theme.check,
+                _ if marked.contains(&v2) => &theme.highlight,
+                _ if light => &theme.light,
+                _ => &theme.dark
+            };
+
+            svg.push_str(&format!(
+                "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"{}\"/>\n",
+                x, y, square, square, fill
+            ));
+        }
+
+        if options.coordinates {
+            let font = border * 0.7;
+
+            for index in 0..files {
+                let file = (b'a' + index as u8) as char;
+                let column = match viewer {
+                    ChessPieceColor::White => index,
+                    ChessPieceColor::Black => files - 1 - index
+                };
+                let x = border + (column as f64 + 0.5) * square;
+
+                for y in [border / 2.0, size - border / 2.0] {
+                    svg.push_str(&format!(
+                        "<text x=\"{:.2}\" y=\"{:.2}\" font-size=\"{:.2}\" font-family=\"sans-serif\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+                        x, y, font, theme.labels, file
+                    ));
+                }
+            }
+
+            let ranks = self.ranks() as usize;
+            for (index, rank) in (1..=ranks).enumerate() {
+                let row = match viewer {
+                    ChessPieceColor::White => ranks - 1 - index,
+                    ChessPieceColor::Black => index
+                };
+                let y = border + (row as f64 + 0.5) * square;
+
+                for x in [border / 2.0, size - border / 2.0] {
+                    svg.push_str(&format!(
+                        "<text x=\"{:.2}\" y=\"{:.2}\" font-size=\"{:.2}\" font-family=\"sans-serif\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+                        x, y, font, theme.labels, rank
+                    ));
+                }
+            }
+        }
+
+        // solid glyphs for both sides, white ones filled white and outlined
+        for v2 in self.squares() {
+            let tile = self.tile(&v2);
+            let (Some(piece), Some(color)) = (tile.piece(), tile.color()) else {
+                continue;
+            };
+
+            let (x, y) = centre(&v2);
+            let (fill, stroke) = match color {
+                ChessPieceColor::White => ("#ffffff", "#000000"),
+                ChessPieceColor::Black => ("#000000", "#000000")
+            };
+
+            svg.push_str(&format!(
+                "<text x=\"{:.2}\" y=\"{:.2}\" font-size=\"{:.2}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"{:.2}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+                x, y, square * 0.8, fill, stroke, square / 60.0, glyph(piece)
+            ));
+        }
+
+        // arrows stop short of the centre so the head doesn't cover the piece
+        for (from, to) in &options.arrows {
+            let ((x1, y1), (x2, y2)) = (centre(from), centre(to));
+            let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt().max(1.0);
+            let shorten = (square * 0.3).min(length / 2.0);
+            let (x2, y2) = (x2 - (x2 - x1) / length * shorten, y2 - (y2 - y1) / length * shorten);
+
+            svg.push_str(&format!(
+                "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"{}\" stroke-width=\"{:.2}\" stroke-linecap=\"round\" stroke-opacity=\"0.8\" marker-end=\"url(#arrowhead)\"/>\n",
+                x1, y1, x2, y2, theme.arrow, square / 6.0
+            ));
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
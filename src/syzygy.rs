@@ -0,0 +1,1281 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::board::ChessBoard;
+use crate::moves::ChessMove;
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::search::MATE;
+use crate::ordering::MAX_PLY;
+use crate::math::V2;
+use crate::variant::Variant;
+
+// syzygy endgame tablebases. a table like KRvK.rtbw stores win / draw / loss
+// for every position with that material, KRvK.rtbz the distance to the next
+// capture or pawn move (dtz) that keeps the result. tables are found by
+// scanning directories, and only read from disk the first time they're
+// probed. a missing or broken table makes the probe fail, never the search.
+//
+// the file format follows the reference prober: positions are turned into an
+// index by grouping pieces and using the board's symmetries, and the values
+// are huffman coded blocks of "recursive pairing" symbols
+
+// wins and losses found in the tables rank below real mates but above any
+// evaluation
+pub const TB_WIN: i32 = MATE - 2 * MAX_PLY as i32;
+
+const MAX_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// per table flags
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+// result for the side to move. a cursed win is a win that the fifty move
+// rule turns into a draw, a blessed loss the same the other way around
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl { Loss = -2, BlessedLoss = -1, Draw = 0, CursedWin = 1, Win = 2 }
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum TableKind { Wdl, Dtz }
+
+// squares are numbered a1 = 0 to h8 = 63, pieces the way the tables write
+// them: 1 to 6 for white pawn to king, 9 to 14 for black
+struct Position {
+    pieces: Vec<(usize, u8)>, // by square
+    side: usize // 0 white, 1 black
+}
+
+// what the file name says about a table, known before it's ever read
+#[derive(Clone, Debug)]
+struct TableInfo {
+    key: String, // KRvK, with the first side as white
+    key2: String, // KvKR, the same material with the colors swapped
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    pawn_count: [usize; 2] // leading side, other side
+}
+
+#[derive(Clone, Debug, Default)]
+struct PairsData {
+    flags: u8,
+    max_sym_len: u8,
+    min_sym_len: u8, // also the value when the whole table is one value
+    num_blocks: usize,
+    block_size: usize,
+    span: usize,
+    lowest_sym: usize,
+    btree: usize,
+    block_length: usize,
+    block_length_size: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    data: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    pieces: [u8; MAX_PIECES],
+    group_idx: [u64; MAX_PIECES + 1],
+    group_len: [usize; MAX_PIECES + 1],
+    map_idx: [u16; 4] // win, loss, cursed win, blessed loss
+}
+
+// a table read into memory. offsets in PairsData point into bytes
+struct Table {
+    bytes: Vec<u8>,
+    sides: usize,
+    map: usize,
+    items: Vec<PairsData> // [side][file], four files when there are pawns
+}
+
+struct TableFiles {
+    info: TableInfo,
+    wdl_path: PathBuf,
+    dtz_path: Option<PathBuf>,
+    wdl: OnceLock<Option<Table>>,
+    dtz: OnceLock<Option<Table>>
+}
+
+#[derive(Default)]
+pub struct Tablebase {
+    tables: Vec<TableFiles>,
+    keys: HashMap<String, usize>,
+    max_pieces: usize
+}
+
+// the lookup tables the index encoding is built from
+struct Indices {
+    binomial: [[u64; 64]; MAX_PIECES],
+    map_pawns: [usize; 64],
+    map_b1h1h7: [usize; 64],
+    map_a1d1d4: [usize; 64],
+    map_kk: [[usize; 64]; 10],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6]
+}
+
+fn file_of(square: usize) -> usize {
+    square % 8
+}
+
+fn rank_of(square: usize) -> usize {
+    square / 8
+}
+
+// above (positive) or below (negative) the a1-h8 diagonal
+fn off_diagonal(square: usize) -> i32 {
+    rank_of(square) as i32 - file_of(square) as i32
+}
+
+fn indices() -> &'static Indices {
+    static INDICES: OnceLock<Indices> = OnceLock::new();
+    INDICES.get_or_init(build_indices)
+}
+
+fn build_indices() -> Indices {
+    let mut indices = Indices {
+        binomial: [[0; 64]; MAX_PIECES],
+        map_pawns: [0; 64],
+        map_b1h1h7: [0; 64],
+        map_a1d1d4: [0; 64],
+        map_kk: [[0; 64]; 10],
+        lead_pawn_idx: [[0; 64]; 6],
+        lead_pawns_size: [[0; 4]; 6]
+    };
+
+    // squares below the a1-h8 diagonal, 0 to 27
+    let mut code = 0;
+    for square in 0..64 {
+        if off_diagonal(square) < 0 {
+            indices.map_b1h1h7[square] = code;
+            code += 1;
+        }
+    }
+
+    // the a1-d1-d4 triangle, 0 to 9 with the diagonal last
+    let mut diagonal = vec![];
+    code = 0;
+    for square in 0..28 {
+        if off_diagonal(square) < 0 && file_of(square) <= 3 {
+            indices.map_a1d1d4[square] = code;
+            code += 1;
+        } else if off_diagonal(square) == 0 && file_of(square) <= 3 {
+            diagonal.push(square);
+        }
+    }
+
+    for square in diagonal {
+        indices.map_a1d1d4[square] = code;
+        code += 1;
+    }
+
+    // the 462 ways to place two kings with the first in the triangle. if the
+    // first is on the diagonal the second may not be above it
+    let mut both_on_diagonal = vec![];
+    code = 0;
+    for index in 0..10 {
+        for first in 0..28 {
+            if indices.map_a1d1d4[first] != index || (index == 0 && first != 1) {
+                continue;
+            }
+
+            for second in 0..64 {
+                let touching = file_of(first).abs_diff(file_of(second)) <= 1
+                    && rank_of(first).abs_diff(rank_of(second)) <= 1;
+
+                if touching || (off_diagonal(first) == 0 && off_diagonal(second) > 0) {
+                    continue;
+                }
+
+                match off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                    true => both_on_diagonal.push((index, second)),
+                    false => {
+                        indices.map_kk[index][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    for (index, second) in both_on_diagonal {
+        indices.map_kk[index][second] = code;
+        code += 1;
+    }
+
+    // binomial[k][n], ways to pick k of n
+    indices.binomial[0][0] = 1;
+    for n in 1..64 {
+        for k in 0..MAX_PIECES.min(n + 1) {
+            indices.binomial[k][n] = match k {
+                0 => 0,
+                _ => indices.binomial[k - 1][n - 1]
+            } + match k < n {
+                true => indices.binomial[k][n - 1],
+                false => 0
+            };
+        }
+    }
+
+    // a2-h7 numbered so the pawn with the highest number is the leading one:
+    // nearest the edge, and lowest on its file
+    let mut available: i32 = 47;
+    for lead in 1..6 {
+        for file in 0..4 {
+            let mut index = 0;
+
+            for rank in 1..7 {
+                let square = file + 8 * rank;
+
+                if lead == 1 {
+                    indices.map_pawns[square] = available as usize;
+                    indices.map_pawns[square ^ 7] = (available - 1) as usize;
+                    available -= 2;
+                }
+
+                indices.lead_pawn_idx[lead][square] = index;
+                index += indices.binomial[lead - 1][indices.map_pawns[square]];
+            }
+
+            indices.lead_pawns_size[lead][file] = index;
+        }
+    }
+
+    indices
+}
+
+fn piece_code(piece: ChessPiece, color: ChessPieceColor) -> u8 {
+    let code = match piece {
+        ChessPiece::Pawn => 1,
+        ChessPiece::Knight => 2,
+        ChessPiece::Bishop => 3,
+        ChessPiece::Rook => 4,
+        ChessPiece::Queen => 5,
+        ChessPiece::King => 6,
+        _ => unreachable!("tables only cover the six usual pieces")
+    };
+
+    match color {
+        ChessPieceColor::White => code,
+        ChessPieceColor::Black => code | 8
+    }
+}
+
+// the letters of one side in table name order, e.g. KRP
+fn side_letters(counts: &[usize; 7]) -> String {
+    [(6, 'K'), (5, 'Q'), (4, 'R'), (3, 'B'), (2, 'N'), (1, 'P')].iter()
+        .map(|(code, letter)| letter.to_string().repeat(counts[*code]))
+        .collect()
+}
+
+impl Position {
+    fn new(board: &ChessBoard) -> Self {
+        let mut pieces = vec![];
+
+        for index in 0..64 {
+            let v2 = V2 { x: index % 8, y: index / 8 };
+            let tile = board.tile(&v2);
+
+            if let (Some(piece), Some(color)) = (tile.piece(), tile.color()) {
+                pieces.push((board.square_index(&v2), piece_code(piece, color)));
+            }
+        }
+
+        pieces.sort();
+
+        Position {
+            pieces,
+            side: board.side_to_move().index()
+        }
+    }
+
+    fn material_key(&self) -> String {
+        let mut counts = [[0; 7]; 2];
+        for (_, code) in &self.pieces {
+            counts[(code >> 3) as usize][(code & 7) as usize] += 1;
+        }
+
+        format!("{}v{}", side_letters(&counts[0]), side_letters(&counts[1]))
+    }
+}
+
+impl TableInfo {
+    // from a file name like KRPvKR
+    fn parse(name: &str) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+
+        let count = |side: &str, letter: char| side.chars().filter(|c| *c == letter).count();
+        let valid = |side: &str| side.starts_with('K') && count(side, 'K') == 1
+            && side.chars().all(|c| "KQRBNP".contains(c));
+
+        if !valid(white) || !valid(black) || white.len() + black.len() > MAX_PIECES {
+            return None;
+        }
+
+        let mut counts = [[0; 7]; 2];
+        for (side, letters) in [white, black].iter().enumerate() {
+            for (code, letter) in [(1, 'P'), (2, 'N'), (3, 'B'), (4, 'R'), (5, 'Q'), (6, 'K')] {
+                counts[side][code] = count(letters, letter);
+            }
+        }
+
+        let key = format!("{}v{}", side_letters(&counts[0]), side_letters(&counts[1]));
+        let key2 = format!("{}v{}", side_letters(&counts[1]), side_letters(&counts[0]));
+
+        let has_unique_pieces = counts.iter()
+            .any(|side| side[1..6].contains(&1));
+
+        // the side with fewer pawns leads, it compresses better
+        let (white_pawns, black_pawns) = (counts[0][1], counts[1][1]);
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+
+        Some(TableInfo {
+            key,
+            key2,
+            piece_count: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            pawn_count: match white_leads {
+                true => [white_pawns, black_pawns],
+                false => [black_pawns, white_pawns]
+            }
+        })
+    }
+
+    fn symmetric(&self) -> bool {
+        self.key == self.key2
+    }
+}
+
+fn byte(bytes: &[u8], at: usize) -> Option<u8> {
+    bytes.get(at).copied()
+}
+
+fn u16_le(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_le(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u32_be(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_be(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+impl PairsData {
+    // the two symbols a pair expands into, twelve bits each
+    fn left(&self, bytes: &[u8], symbol: usize) -> usize {
+        let at = self.btree + 3 * symbol;
+        match (byte(bytes, at), byte(bytes, at + 1)) {
+            (Some(low), Some(high)) => ((high as usize & 0xf) << 8) | low as usize,
+            _ => 0xfff
+        }
+    }
+
+    fn right(&self, bytes: &[u8], symbol: usize) -> usize {
+        let at = self.btree + 3 * symbol;
+        match (byte(bytes, at + 1), byte(bytes, at + 2)) {
+            (Some(low), Some(high)) => ((high as usize) << 4) | (low as usize >> 4),
+            _ => 0xfff
+        }
+    }
+
+    fn lowest(&self, bytes: &[u8], length: usize) -> u64 {
+        u16_le(bytes, self.lowest_sym + 2 * length).unwrap_or(0) as u64
+    }
+
+    // which pieces are encoded together, and what each group's index is
+    // multiplied by. order says where the leading group and the other side's
+    // pawns go among the rest
+    fn set_groups(&mut self, info: &TableInfo, order: [u8; 2], file: usize) {
+        let indices = indices();
+        let mut n = 0;
+        let mut first_len: i32 = match (info.has_pawns, info.has_unique_pieces) {
+            (true, _) => 0,
+            (false, true) => 3,
+            (false, false) => 2
+        };
+
+        self.group_len[0] = 1;
+        for i in 1..info.piece_count {
+            first_len -= 1;
+            match first_len > 0 || self.pieces[i] == self.pieces[i - 1] {
+                true => self.group_len[n] += 1,
+                false => {
+                    n += 1;
+                    self.group_len[n] = 1;
+                }
+            }
+        }
+
+        n += 1;
+        self.group_len[n] = 0;
+
+        let both_pawns = info.has_pawns && info.pawn_count[1] > 0;
+        let mut next = match both_pawns {
+            true => 2,
+            false => 1
+        };
+
+        let mut free_squares = 64 - self.group_len[0] - match both_pawns {
+            true => self.group_len[1],
+            false => 0
+        };
+
+        let mut index: u64 = 1;
+        let mut k = 0;
+
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                self.group_idx[0] = index;
+                index *= match (info.has_pawns, info.has_unique_pieces) {
+                    (true, _) => indices.lead_pawns_size[self.group_len[0]][file],
+                    (false, true) => 31332,
+                    (false, false) => 462
+                };
+            } else if k == order[1] as usize {
+                self.group_idx[1] = index;
+                index *= indices.binomial[self.group_len[1]][48 - self.group_len[0]];
+            } else {
+                self.group_idx[next] = index;
+                index *= indices.binomial[self.group_len[next]][free_squares];
+                free_squares -= self.group_len[next];
+                next += 1;
+            }
+
+            k += 1;
+        }
+
+        self.group_idx[n] = index;
+    }
+
+    // reads the huffman code description, returns where the next one starts
+    fn set_sizes(&mut self, bytes: &[u8], mut at: usize) -> Option<usize> {
+        self.flags = byte(bytes, at)?;
+        at += 1;
+
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            self.min_sym_len = byte(bytes, at)?;
+            return Some(at + 1);
+        }
+
+        let groups = self.group_len.iter().position(|len| *len == 0)?;
+        let table_size = self.group_idx[groups];
+
+        self.block_size = 1 << byte(bytes, at)?;
+        self.span = 1 << byte(bytes, at + 1)?;
+        self.sparse_index_size = table_size.div_ceil(self.span as u64) as usize;
+
+        let padding = byte(bytes, at + 2)? as usize;
+        self.num_blocks = u32_le(bytes, at + 3)? as usize;
+        self.block_length_size = self.num_blocks + padding;
+
+        self.max_sym_len = byte(bytes, at + 7)?;
+        self.min_sym_len = byte(bytes, at + 8)?;
+        at += 9;
+
+        if self.min_sym_len == 0 || self.max_sym_len < self.min_sym_len || self.max_sym_len > 32 {
+            return None;
+        }
+
+        // canonical huffman: longer codes have lower values, so base64[l] is
+        // the lowest code of length l padded out to 64 bits
+        self.lowest_sym = at;
+        let lengths = (self.max_sym_len - self.min_sym_len) as usize + 1;
+        self.base64 = vec![0; lengths];
+
+        for i in (0..lengths - 1).rev() {
+            self.base64[i] = self.base64[i + 1]
+                .wrapping_add(self.lowest(bytes, i))
+                .wrapping_sub(self.lowest(bytes, i + 1)) / 2;
+        }
+
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            *base <<= 64 - i - self.min_sym_len as usize;
+        }
+
+        at += 2 * lengths;
+
+        let symbols = u16_le(bytes, at)? as usize;
+        at += 2;
+
+        self.btree = at;
+        self.symlen = vec![0; symbols];
+
+        let mut visited = vec![false; symbols];
+        for symbol in 0..symbols {
+            if !visited[symbol] {
+                self.symlen[symbol] = self.set_symlen(bytes, symbol, &mut visited);
+            }
+        }
+
+        Some(at + 3 * symbols + (symbols & 1))
+    }
+
+    // how many values (minus one) a symbol stands for once fully expanded
+    fn set_symlen(&mut self, bytes: &[u8], symbol: usize, visited: &mut [bool]) -> u8 {
+        visited[symbol] = true;
+
+        let right = self.right(bytes, symbol);
+        if right == 0xfff {
+            return 0;
+        }
+
+        let left = self.left(bytes, symbol);
+        if left >= self.symlen.len() || right >= self.symlen.len() {
+            return 0; // a broken table, the probe will come out wrong but safe
+        }
+
+        for child in [left, right] {
+            if !visited[child] {
+                self.symlen[child] = self.set_symlen(bytes, child, visited);
+            }
+        }
+
+        self.symlen[left].wrapping_add(self.symlen[right]).wrapping_add(1)
+    }
+
+    // the stored value at an index
+    fn decompress(&self, bytes: &[u8], index: u64) -> Option<i32> {
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(self.min_sym_len as i32);
+        }
+
+        // the sparse index points near the right block, walk from there
+        let k = (index / self.span as u64) as usize;
+        if k >= self.sparse_index_size {
+            return None;
+        }
+
+        let entry = self.sparse_index + 6 * k;
+        let mut block = u32_le(bytes, entry)? as usize;
+        let mut offset = u16_le(bytes, entry + 4)? as i64;
+
+        offset += (index % self.span as u64) as i64 - (self.span / 2) as i64;
+
+        let block_length = |block: usize| u16_le(bytes, self.block_length + 2 * block).map(|length| length as i64);
+
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        // read codes until we reach the symbol holding our value
+        let mut at = self.data + block * self.block_size;
+        let mut buffer = u64_be(bytes, at)?;
+        let mut buffer_size = 64;
+        at += 8;
+
+        let min = self.min_sym_len as usize;
+        let mut symbol;
+
+        loop {
+            let mut length = 0;
+            while length + 1 < self.base64.len() && buffer < self.base64[length] {
+                length += 1;
+            }
+
+            symbol = (buffer.wrapping_sub(self.base64[length]) >> (64 - length - min)) as usize;
+            symbol += self.lowest(bytes, length) as usize;
+
+            let count = *self.symlen.get(symbol)? as i64 + 1;
+            if offset < count {
+                break;
+            }
+
+            offset -= count;
+            buffer <<= length + min;
+            buffer_size -= (length + min) as i32;
+
+            if buffer_size <= 32 {
+                buffer_size += 32;
+                buffer |= (u32_be(bytes, at).unwrap_or(0) as u64) << (64 - buffer_size);
+                at += 4;
+            }
+        }
+
+        // pairs expand into adjacent runs, so walk down to the one leaf we need
+        while *self.symlen.get(symbol)? != 0 {
+            let left = self.left(bytes, symbol);
+            let count = *self.symlen.get(left)? as i64 + 1;
+
+            match offset < count {
+                true => symbol = left,
+                false => {
+                    offset -= count;
+                    symbol = self.right(bytes, symbol);
+                }
+            }
+        }
+
+        Some(self.left(bytes, symbol) as i32)
+    }
+}
+
+impl Table {
+    fn load(path: &Path, info: &TableInfo, kind: TableKind) -> Option<Self> {
+        let bytes = fs::read(path).ok()?;
+
+        let magic = match kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC
+        };
+
+        if bytes.get(0..4)? != magic {
+            return None;
+        }
+
+        Self::parse(bytes, info, kind)
+    }
+
+    fn parse(bytes: Vec<u8>, info: &TableInfo, kind: TableKind) -> Option<Self> {
+        let flags = byte(&bytes, 4)?;
+        if (flags & 2 != 0) != info.has_pawns {
+            return None;
+        }
+
+        let sides = match kind == TableKind::Wdl && !info.symmetric() {
+            true => 2,
+            false => 1
+        };
+
+        let files = match info.has_pawns {
+            true => 4,
+            false => 1
+        };
+
+        let both_pawns = info.has_pawns && info.pawn_count[1] > 0;
+        let mut items = vec![PairsData::default(); 8];
+        let mut at = 5;
+
+        for file in 0..files {
+            let first = byte(&bytes, at)?;
+            let second = match both_pawns {
+                true => byte(&bytes, at + 1)?,
+                false => 0xff
+            };
+
+            let orders = [[first & 0xf, second & 0xf], [first >> 4, second >> 4]];
+            at += 1 + both_pawns as usize;
+
+            for k in 0..info.piece_count {
+                let piece = byte(&bytes, at)?;
+                for (side, item) in items.iter_mut().skip(file).step_by(4).take(sides).enumerate() {
+                    item.pieces[k] = match side {
+                        0 => piece & 0xf,
+                        _ => piece >> 4
+                    };
+                }
+                at += 1;
+            }
+
+            for (side, order) in orders.iter().enumerate().take(sides) {
+                items[4 * side + file].set_groups(info, *order, file);
+            }
+        }
+
+        at += at & 1;
+
+        let slots: Vec<usize> = (0..files)
+            .flat_map(|file| (0..sides).map(move |side| 4 * side + file))
+            .collect();
+
+        for slot in &slots {
+            at = items[*slot].set_sizes(&bytes, at)?;
+        }
+
+        let map = at;
+        if kind == TableKind::Dtz {
+            for item in items.iter_mut().take(files) {
+                if item.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+
+                match item.flags & FLAG_WIDE != 0 {
+                    true => {
+                        at += at & 1;
+                        for i in 0..4 {
+                            item.map_idx[i] = ((at - map) / 2 + 1) as u16;
+                            at += 2 * u16_le(&bytes, at)? as usize + 2;
+                        }
+                    },
+                    false => for i in 0..4 {
+                        item.map_idx[i] = (at - map + 1) as u16;
+                        at += byte(&bytes, at)? as usize + 1;
+                    }
+                }
+            }
+
+            at += at & 1;
+        }
+
+        for slot in &slots {
+            items[*slot].sparse_index = at;
+            at += 6 * items[*slot].sparse_index_size;
+        }
+
+        for slot in &slots {
+            items[*slot].block_length = at;
+            at += 2 * items[*slot].block_length_size;
+        }
+
+        for slot in &slots {
+            at = (at + 0x3f) & !0x3f;
+            items[*slot].data = at;
+            at += items[*slot].num_blocks * items[*slot].block_size;
+        }
+
+        // a truncated file can't be probed safely
+        if at > bytes.len() {
+            return None;
+        }
+
+        Some(Table { bytes, sides, map, items })
+    }
+
+    // dtz tables and symmetric wdl tables only have the one side
+    fn item(&self, side: usize, file: usize) -> &PairsData {
+        &self.items[4 * (side % self.sides) + file]
+    }
+
+    // the raw table value for a position, or none when a dtz table only has
+    // the other side to move
+    fn probe(&self, info: &TableInfo, kind: TableKind, position: &Position, wdl: Wdl) -> Option<Option<i32>> {
+        let indices = indices();
+
+        // tables are written with white as the stronger side, and symmetric
+        // ones only with white to move. anything else is mirrored first
+        let symmetric_black = info.symmetric() && position.side == 1;
+        let flip = symmetric_black || position.material_key() != info.key;
+
+        let flip_color = match flip { true => 8, false => 0 };
+        let flip_squares = match flip { true => 56, false => 0 };
+        let side = flip as usize ^ position.side;
+
+        let mut squares = [0usize; MAX_PIECES];
+        let mut pieces = [0u8; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_count = 0;
+        let mut lead = 0;
+        let mut file = 0;
+
+        // pawn tables are split by the file of the leading pawn
+        if info.has_pawns {
+            lead = self.item(0, 0).pieces[0] ^ flip_color;
+
+            for (square, code) in &position.pieces {
+                if *code == lead {
+                    squares[size] = square ^ flip_squares;
+                    size += 1;
+                }
+            }
+
+            lead_count = size;
+
+            let leading = (0..lead_count).max_by_key(|i| indices.map_pawns[squares[*i]])?;
+            squares.swap(0, leading);
+
+            file = file_of(squares[0]).min(7 - file_of(squares[0]));
+        }
+
+        if kind == TableKind::Dtz {
+            let stored = (self.item(0, file).flags & FLAG_STM) as usize;
+            if stored != side && (info.has_pawns || !info.symmetric()) {
+                return Some(None);
+            }
+        }
+
+        for (square, code) in &position.pieces {
+            if info.has_pawns && *code == lead {
+                continue;
+            }
+
+            if size == MAX_PIECES {
+                return None;
+            }
+
+            squares[size] = square ^ flip_squares;
+            pieces[size] = code ^ flip_color;
+            size += 1;
+        }
+
+        let data = self.item(side, file);
+
+        // the same piece order the table was written in
+        for i in lead_count..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if data.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // mirror so the leading piece is on files a-d
+        if file_of(squares[0]) > 3 {
+            for square in squares.iter_mut().take(size) {
+                *square ^= 7;
+            }
+        }
+
+        let mut index = match info.has_pawns {
+            true => {
+                let mut index = indices.lead_pawn_idx[lead_count][squares[0]];
+
+                squares[1..lead_count].sort_by_key(|square| indices.map_pawns[*square]);
+                for (i, square) in squares.iter().enumerate().take(lead_count).skip(1) {
+                    index += indices.binomial[i][indices.map_pawns[*square]];
+                }
+
+                index
+            },
+            false => Self::piece_index(info, data, &mut squares[..size])
+        };
+
+        index *= data.group_idx[0];
+
+        let mut start = data.group_len[0];
+        let mut remaining_pawns = info.has_pawns && info.pawn_count[1] > 0;
+        let mut next = 1;
+
+        while data.group_len[next] != 0 {
+            let len = data.group_len[next];
+            squares[start..start + len].sort();
+
+            let mut n = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let adjust = squares[..start].iter().filter(|other| square > **other).count();
+                let pawn_rows = match remaining_pawns { true => 8, false => 0 };
+
+                n += indices.binomial[i + 1][square.checked_sub(adjust + pawn_rows)?];
+            }
+
+            remaining_pawns = false;
+            index += n * data.group_idx[next];
+            start += len;
+            next += 1;
+        }
+
+        let value = data.decompress(&self.bytes, index)?;
+
+        Some(Some(match kind {
+            TableKind::Wdl => value - 2,
+            TableKind::Dtz => self.map_dtz(file, value, wdl)?
+        }))
+    }
+
+    // index of the leading group of a table without pawns, using all eight
+    // symmetries of the board
+    fn piece_index(info: &TableInfo, data: &PairsData, squares: &mut [usize]) -> u64 {
+        let indices = indices();
+
+        if rank_of(squares[0]) > 3 {
+            for square in squares.iter_mut() {
+                *square ^= 56;
+            }
+        }
+
+        // the first leading piece off the diagonal goes below it
+        for i in 0..data.group_len[0] {
+            match off_diagonal(squares[i]) {
+                0 => continue,
+                off if off > 0 => for square in squares[i..].iter_mut() {
+                    *square = ((*square >> 3) | (*square << 3)) & 63;
+                },
+                _ => ()
+            }
+            break;
+        }
+
+        if !info.has_unique_pieces {
+            return indices.map_kk[indices.map_a1d1d4[squares[0]]][squares[1]] as u64;
+        }
+
+        let adjust1 = (squares[1] > squares[0]) as usize;
+        let adjust2 = (squares[2] > squares[0]) as usize + (squares[2] > squares[1]) as usize;
+
+        let index = if off_diagonal(squares[0]) != 0 {
+            (indices.map_a1d1d4[squares[0]] * 63 + (squares[1] - adjust1)) * 62 + squares[2] - adjust2
+        } else if off_diagonal(squares[1]) != 0 {
+            (6 * 63 + rank_of(squares[0]) * 28 + indices.map_b1h1h7[squares[1]]) * 62 + squares[2] - adjust2
+        } else if off_diagonal(squares[2]) != 0 {
+            6 * 63 * 62 + 4 * 28 * 62
+                + rank_of(squares[0]) * 7 * 28
+                + (rank_of(squares[1]) - adjust1) * 28
+                + indices.map_b1h1h7[squares[2]]
+        } else {
+            6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28
+                + rank_of(squares[0]) * 7 * 6
+                + (rank_of(squares[1]) - adjust1) * 6
+                + (rank_of(squares[2]) - adjust2)
+        };
+
+        index as u64
+    }
+
+    // dtz values are stored by frequency, per result, and in moves rather
+    // than plies where that loses nothing. turn them back into plies
+    fn map_dtz(&self, file: usize, mut value: i32, wdl: Wdl) -> Option<i32> {
+        let data = self.item(0, file);
+        let flags = data.flags;
+
+        if flags & FLAG_MAPPED != 0 {
+            let slot = match wdl {
+                Wdl::Win | Wdl::Draw => 0,
+                Wdl::Loss => 1,
+                Wdl::CursedWin => 2,
+                Wdl::BlessedLoss => 3
+            };
+
+            let index = data.map_idx[slot] as usize + value as usize;
+
+            value = match flags & FLAG_WIDE != 0 {
+                true => u16_le(&self.bytes, self.map + 2 * index)? as i32,
+                false => byte(&self.bytes, self.map + index)? as i32
+            };
+        }
+
+        let in_plies = match wdl {
+            Wdl::Win => flags & FLAG_WIN_PLIES != 0,
+            Wdl::Loss => flags & FLAG_LOSS_PLIES != 0,
+            _ => false
+        };
+
+        if !in_plies && wdl != Wdl::Draw {
+            value *= 2;
+        }
+
+        Some(value + 1)
+    }
+}
+
+impl Wdl {
+    fn from(value: i32) -> Self {
+        match value {
+            v if v <= -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win
+        }
+    }
+
+    pub fn opposite(&self) -> Self {
+        Wdl::from(-(*self as i32))
+    }
+
+    // a search score for the result, found this many plies from the root.
+    // the fifty move rule draws cursed wins, so they only score a hair above
+    // a draw
+    pub fn score(&self, ply: i32) -> i32 {
+        match self {
+            Wdl::Win => TB_WIN - ply,
+            Wdl::CursedWin => 1,
+            Wdl::Draw => 0,
+            Wdl::BlessedLoss => -1,
+            Wdl::Loss => -TB_WIN + ply
+        }
+    }
+
+    // the dtz of the move before a capture or pawn move with this result
+    fn dtz_before_zeroing(&self) -> i32 {
+        match self {
+            Wdl::Win => 1,
+            Wdl::CursedWin => 101,
+            Wdl::Draw => 0,
+            Wdl::BlessedLoss => -101,
+            Wdl::Loss => -1
+        }
+    }
+}
+
+fn is_zeroing(board: &ChessBoard, chess_move: &ChessMove) -> bool {
+    chess_move.is_capture() || board.tile(&chess_move.src()).piece() == Some(ChessPiece::Pawn)
+}
+
+#[allow(dead_code)]
+impl Tablebase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // every table found in the directories, separated like a PATH. missing
+    // directories are skipped
+    pub fn open(paths: &str) -> Self {
+        let mut tablebase = Self::new();
+
+        for path in paths.split([':', ';']).filter(|path| !path.is_empty() && *path != "<empty>") {
+            tablebase.add_directory(path);
+        }
+
+        tablebase
+    }
+
+    // returns how many new tables were found
+    pub fn add_directory<P: AsRef<Path>>(&mut self, directory: P) -> usize {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(_) => return 0
+        };
+
+        let mut found = 0;
+
+        for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+            if path.extension().and_then(|extension| extension.to_str()) != Some("rtbw") {
+                continue;
+            }
+
+            let info = match path.file_stem().and_then(|stem| stem.to_str()).and_then(TableInfo::parse) {
+                Some(info) => info,
+                None => continue
+            };
+
+            if self.keys.contains_key(&info.key) {
+                continue;
+            }
+
+            let dtz_path = Some(path.with_extension("rtbz")).filter(|dtz| dtz.exists());
+
+            self.max_pieces = self.max_pieces.max(info.piece_count);
+            self.keys.insert(info.key.clone(), self.tables.len());
+            self.keys.insert(info.key2.clone(), self.tables.len());
+            self.tables.push(TableFiles {
+                info,
+                wdl_path: path,
+                dtz_path,
+                wdl: OnceLock::new(),
+                dtz: OnceLock::new()
+            });
+
+            found += 1;
+        }
+
+        found
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    // the most pieces any table has, zero without tables
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    // whether a probe is worth trying. the tables know nothing of castling
+    pub fn covers(&self, board: &ChessBoard) -> bool {
+        let pieces = board.squares()
+            .filter(|v2| board.tile(v2).piece().is_some())
+            .count();
+
+        pieces <= self.max_pieces
+            && board.variant() == Variant::Standard
+            && board.is_orthodox()
+            && board.castling_rights(ChessPieceColor::White) == (false, false)
+            && board.castling_rights(ChessPieceColor::Black) == (false, false)
+    }
+
+    fn probe_table(&self, board: &ChessBoard, kind: TableKind, wdl: Wdl) -> Option<Option<i32>> {
+        let position = Position::new(board);
+
+        // two bare kings aren't worth a file
+        if position.pieces.len() == 2 {
+            return Some(Some(0));
+        }
+
+        let files = &self.tables[*self.keys.get(&position.material_key())?];
+
+        let table = match kind {
+            TableKind::Wdl => files.wdl.get_or_init(|| Table::load(&files.wdl_path, &files.info, kind)),
+            TableKind::Dtz => files.dtz.get_or_init(|| {
+                files.dtz_path.as_ref().and_then(|path| Table::load(path, &files.info, kind))
+            })
+        };
+
+        table.as_ref()?.probe(&files.info, kind, &position, wdl)
+    }
+
+    // the tables may store anything for positions where the side to move has
+    // a winning capture (and dtz tables for winning pawn moves too), so those
+    // moves are searched and the best of them and the stored value counts.
+    // the flag says the best result comes from such a move
+    fn search(&self, board: &ChessBoard, pawn_moves: bool) -> Option<(Wdl, bool)> {
+        let moves = board.legal_moves();
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+
+        for chess_move in &moves {
+            let pawn = board.tile(&chess_move.src()).piece() == Some(ChessPiece::Pawn);
+            if !(chess_move.is_capture() || pawn_moves && pawn) {
+                continue;
+            }
+
+            searched += 1;
+
+            let mut child = *board;
+            child.make_move(chess_move);
+
+            let (value, _) = self.search(&child, false)?;
+            let value = value.opposite();
+
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        // nothing left unsearched, so the stored value (which may be wrong,
+        // e.g. with an en passant capture on) isn't needed
+        let no_more_moves = searched > 0 && searched == moves.len();
+
+        let value = match no_more_moves {
+            true => best,
+            false => Wdl::from(self.probe_table(board, TableKind::Wdl, Wdl::Draw)??)
+        };
+
+        match best >= value {
+            true => Some((best, best > Wdl::Draw || no_more_moves)),
+            false => Some((value, false))
+        }
+    }
+
+    pub fn probe_wdl(&self, board: &ChessBoard) -> Option<Wdl> {
+        self.search(board, false).map(|(wdl, _)| wdl)
+    }
+
+    // plies to the next capture or pawn move with best play, positive when
+    // the side to move wins. over 100 means the fifty move rule gets there
+    // first. zero for a draw
+    pub fn probe_dtz(&self, board: &ChessBoard) -> Option<i32> {
+        let (wdl, zeroing) = self.search(board, true)?;
+
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+
+        if zeroing {
+            return Some(wdl.dtz_before_zeroing());
+        }
+
+        let cursed = match wdl {
+            Wdl::CursedWin | Wdl::BlessedLoss => 100,
+            _ => 0
+        };
+
+        if let Some(dtz) = self.probe_table(board, TableKind::Dtz, wdl)? {
+            return Some((dtz + cursed) * (wdl as i32).signum());
+        }
+
+        // the table only has the other side to move, so look one ply ahead
+        // for the move that keeps the result the quickest
+        let mut best = i32::MAX;
+
+        for chess_move in board.legal_moves() {
+            let zeroing = is_zeroing(board, &chess_move);
+
+            let mut child = *board;
+            child.make_move(&chess_move);
+
+            let mut dtz = match zeroing {
+                true => -self.probe_wdl(&child)?.dtz_before_zeroing(),
+                false => -self.probe_dtz(&child)?
+            };
+
+            if dtz == 1 && child.in_check(child.side_to_move()) && child.legal_moves().is_empty() {
+                best = 1;
+            }
+
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+
+            if dtz < best && dtz.signum() == (wdl as i32).signum() {
+                best = dtz;
+            }
+        }
+
+        match best {
+            i32::MAX => Some(-1), // no moves, mated
+            best => Some(best)
+        }
+    }
+
+    // the root moves that keep the best result the tables allow. when
+    // winning only the ones closest to converting are kept, so the search
+    // can't wander off and let the win slip under the fifty move rule
+    pub fn root_moves(&self, board: &ChessBoard) -> Option<Vec<ChessMove>> {
+        if !self.covers(board) {
+            return None;
+        }
+
+        let mut ranked = vec![];
+
+        for chess_move in board.legal_moves() {
+            let mut child = *board;
+            child.make_move(&chess_move);
+
+            let mut dtz = match is_zeroing(board, &chess_move) {
+                true => self.probe_wdl(&child)?.opposite().dtz_before_zeroing(),
+                false => match -self.probe_dtz(&child)? {
+                    dtz if dtz > 0 => dtz + 1,
+                    dtz if dtz < 0 => dtz - 1,
+                    dtz => dtz
+                }
+            };
+
+            // mate is as close as converting gets
+            if dtz == 2 && child.in_check(child.side_to_move()) && child.legal_moves().is_empty() {
+                dtz = 1;
+            }
+
+            let rank = match dtz {
+                dtz if dtz > 0 => 1000 - dtz,
+                dtz if dtz < 0 => -1000 - dtz,
+                _ => 0
+            };
+
+            ranked.push((rank, chess_move));
+        }
+
+        let best = ranked.iter().map(|(rank, _)| *rank).max()?;
+
+        Some(ranked.into_iter()
+            .filter(|(rank, _)| *rank == best)
+            .map(|(_, chess_move)| chess_move)
+            .collect())
+    }
+}
+
+impl std::fmt::Debug for Tablebase {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Tablebase")
+            .field("tables", &self.tables.len())
+            .field("max_pieces", &self.max_pieces)
+            .finish()
+    }
+}
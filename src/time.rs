@@ -0,0 +1,179 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// clock handling for timed games. each move gets a soft budget, after which
+// no new iteration is started, and a hard budget, after which the search is
+// cut off mid iteration. the soft budget stretches while the search can't
+// make up its mind and shrinks back once it settles
+
+// moves assumed to be left in the game when the clock doesn't say
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+// time kept back for the gui and the network between our moves
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+// the hard budget may go this many times over the soft one
+const HARD_FACTOR: u32 = 4;
+
+// how much the soft budget may stretch at most
+const MAX_SCALE: f64 = 2.5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimeControl {
+    pub remaining: Duration, // on our clock
+    pub increment: Duration, // added after every move
+    pub moves_to_go: Option<u32> // until the next time control, none for sudden death
+}
+
+// shared between the search and whoever is driving it (a gui thread, or the
+// other search threads) so they can stop it or end pondering
+#[derive(Clone, Debug)]
+pub struct TimeHandle {
+    start: Instant,
+    stopped: Arc<AtomicBool>,
+    pondering: Arc<AtomicBool>,
+    ponder_hit_at: Arc<AtomicU64> // in milliseconds since the search started
+}
+
+#[derive(Clone, Debug)]
+pub struct TimeManager {
+    soft: Duration,
+    hard: Duration,
+    scale: f64,
+    last_score: Option<i32>,
+    handle: TimeHandle
+}
+
+#[allow(dead_code)]
+impl TimeHandle {
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    // the opponent played the move we guessed, so the clocks apply from
+    // here. the time is in place before pondering ends, so a search that
+    // sees it end never counts the pondering against us
+    pub fn ponder_hit(&self) {
+        let elapsed = self.start.elapsed().as_millis() as u64;
+        self.ponder_hit_at.store(elapsed, Ordering::Relaxed);
+        self.pondering.store(false, Ordering::Release);
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.load(Ordering::Acquire)
+    }
+}
+
+#[allow(dead_code)]
+impl TimeManager {
+    pub fn new(control: &TimeControl) -> Self {
+        let moves_to_go = control.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+
+        // never plan on using time we don't have
+        let available = control.remaining.saturating_sub(MOVE_OVERHEAD);
+
+        let soft = (control.remaining / moves_to_go + control.increment * 3 / 4).min(available);
+        let hard = (soft * HARD_FACTOR).min(available * 4 / 5).max(soft.min(available));
+
+        Self::with_limits(soft, hard)
+    }
+
+    // exactly this long, no more and no less
+    pub fn fixed(time: Duration) -> Self {
+        Self::with_limits(time, time)
+    }
+
+    fn with_limits(soft: Duration, hard: Duration) -> Self {
+        TimeManager {
+            soft,
+            hard,
+            scale: 1.0,
+            last_score: None,
+            handle: TimeHandle {
+                start: Instant::now(),
+                stopped: Arc::default(),
+                pondering: Arc::default(),
+                ponder_hit_at: Arc::default()
+            }
+        }
+    }
+
+    // starts out pondering: the clocks don't apply until the opponent plays
+    // the move we guessed and the handle's ponder_hit is called
+    pub fn pondering(self) -> Self {
+        self.handle.pondering.store(true, Ordering::Relaxed);
+        self
+    }
+
+    // no clock at all, the search goes on until it's stopped
+    pub fn infinite() -> Self {
+        Self::fixed(Duration::ZERO).pondering()
+    }
+
+    pub fn handle(&self) -> TimeHandle {
+        self.handle.clone()
+    }
+
+    // time spent on our own clock. pondering is on the opponent's time, so the
+    // count only starts at the ponder hit
+    pub fn elapsed(&self) -> Duration {
+        let ponder = Duration::from_millis(self.handle.ponder_hit_at.load(Ordering::Relaxed));
+        self.handle.start.elapsed().saturating_sub(ponder)
+    }
+
+    pub fn soft_limit(&self) -> Duration {
+        self.soft.mul_f64(self.scale).min(self.hard)
+    }
+
+    pub fn hard_limit(&self) -> Duration {
+        self.hard
+    }
+
+    // called after every completed iteration. a new best move or a falling
+    // score means the position is trickier than it looked, so allow more time.
+    // a search that keeps agreeing with itself can stop a bit early
+    pub fn update(&mut self, best_move_changed: bool, score: i32) {
+        let mut scale: f64 = 0.8;
+
+        if best_move_changed {
+            scale += 0.5;
+        }
+
+        if let Some(last_score) = self.last_score {
+            let drop = last_score - score;
+            if drop > 30 {
+                scale += 0.5;
+            }
+            if drop > 100 {
+                scale += 0.5;
+            }
+        }
+
+        // blend with the previous scale so a single iteration can't swing it
+        self.scale = ((self.scale + scale) / 2.0).clamp(0.5, MAX_SCALE);
+        self.last_score = Some(score);
+    }
+
+    // worth starting another iteration? the next one usually takes a few times
+    // longer than everything so far, so don't start it past half the budget
+    pub fn should_start_iteration(&self) -> bool {
+        if self.handle.is_stopped() {
+            return false;
+        }
+
+        self.handle.is_pondering() || self.elapsed() < self.soft_limit() / 2
+    }
+
+    pub fn should_stop(&self) -> bool {
+        if self.handle.is_stopped() {
+            return true;
+        }
+
+        !self.handle.is_pondering() && self.elapsed() >= self.hard
+    }
+}
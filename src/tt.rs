@@ -0,0 +1,142 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::moves::ChessMove;
+use crate::search::MATE;
+
+// transposition table. a fixed number of slots indexed by the zobrist hash,
+// always replaced on store. the full hash is kept to tell apart positions
+// that land in the same slot
+
+pub const DEFAULT_TABLE_ENTRIES: usize = 1 << 18;
+
+// scores this close to mate are stored relative to the node instead of the root
+const MATE_BOUND: i32 = MATE - 1000;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bound { Exact, Lower, Upper }
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TableEntry {
+    pub hash: u64,
+    pub best_move: Option<ChessMove>,
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound
+}
+
+// a slot is two words: the data, and the hash xored with the data. a thread
+// that reads a slot halfway through another thread's write gets a pair that
+// doesn't xor back to its hash and treats it as a miss, so the table can be
+// shared between search threads without any locking
+#[derive(Debug)]
+pub struct TranspositionTable {
+    entries: Vec<(AtomicU64, AtomicU64)>
+}
+
+#[allow(dead_code)]
+impl TranspositionTable {
+    pub fn new(entries: usize) -> Self {
+        TranspositionTable {
+            entries: (0..entries.max(1))
+                .map(|_| (AtomicU64::new(0), AtomicU64::new(0)))
+                .collect()
+        }
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.entries.len() as u64) as usize
+    }
+
+    pub fn clear(&self) {
+        for (key, data) in &self.entries {
+            key.store(0, Ordering::Relaxed);
+            data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    // how many slots hold an entry
+    pub fn filled(&self) -> usize {
+        self.entries.iter().filter(|(_, data)| data.load(Ordering::Relaxed) != 0).count()
+    }
+
+    // the stored entry with its score made relative to the root again
+    pub fn probe(&self, hash: u64, ply: i32) -> Option<TableEntry> {
+        let (key, data) = &self.entries[self.index(hash)];
+        let data = data.load(Ordering::Relaxed);
+
+        if data == 0 || key.load(Ordering::Relaxed) ^ data != hash {
+            return None;
+        }
+
+        let entry = unpack(hash, data);
+
+        Some(TableEntry {
+            score: from_table(entry.score, ply),
+            ..entry
+        })
+    }
+
+    pub fn store(&self, hash: u64, ply: i32, entry: TableEntry) {
+        let (key, data) = &self.entries[self.index(hash)];
+
+        let packed = pack(&TableEntry {
+            score: to_table(entry.score, ply),
+            ..entry
+        });
+
+        data.store(packed, Ordering::Relaxed);
+        key.store(hash ^ packed, Ordering::Relaxed);
+    }
+}
+
+// move in the low 24 bits (zero for none, a8a8 is never a real move), then
+// the score, the depth and the bound. the top bit marks the slot as used
+fn pack(entry: &TableEntry) -> u64 {
+    let chess_move = entry.best_move.map_or(0, |chess_move| chess_move.0) as u64;
+    let score = entry.score as i16 as u16 as u64;
+
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2
+    };
+
+    chess_move | score << 24 | (entry.depth as u64) << 40 | bound << 48 | 1 << 63
+}
+
+fn unpack(hash: u64, data: u64) -> TableEntry {
+    let best_move = match data & 0xffffff {
+        0 => None,
+        chess_move => Some(ChessMove(chess_move as u32))
+    };
+
+    let bound = match (data >> 48) & 0x3 {
+        0 => Bound::Exact,
+        1 => Bound::Lower,
+        _ => Bound::Upper
+    };
+
+    TableEntry {
+        hash,
+        best_move,
+        score: (data >> 24) as u16 as i16 as i32,
+        depth: (data >> 40) as u8,
+        bound
+    }
+}
+
+fn to_table(score: i32, ply: i32) -> i32 {
+    match score {
+        score if score > MATE_BOUND => score + ply,
+        score if score < -MATE_BOUND => score - ply,
+        score => score
+    }
+}
+
+fn from_table(score: i32, ply: i32) -> i32 {
+    match score {
+        score if score > MATE_BOUND => score - ply,
+        score if score < -MATE_BOUND => score + ply,
+        score => score
+    }
+}
@@ -0,0 +1,158 @@
+use std::fs;
+use std::path::Path;
+
+use crate::board::ChessBoard;
+use crate::eval::EvalParams;
+use crate::pieces::ChessPieceColor;
+
+// texel tuning. every labelled position's static evaluation is squashed into
+// an expected score with a sigmoid, and the parameters are nudged one at a
+// time for as long as that brings the expected scores closer to the results
+// the games actually had
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LabelledPosition {
+    pub board: ChessBoard,
+    pub result: f64 // for white: 1 a win, 0.5 a draw, 0 a loss
+}
+
+#[derive(Clone, Debug)]
+pub struct Tuner {
+    positions: Vec<(Vec<(usize, i32)>, f64)>, // evaluation terms and result
+    scale: f64 // how steep the sigmoid is
+}
+
+fn result_value(word: &str) -> Option<f64> {
+    match word.trim_matches(|c| "\"[];".contains(c)) {
+        "1-0" | "1.0" => Some(1.0),
+        "0-1" | "0.0" => Some(0.0),
+        "1/2-1/2" | "0.5" => Some(0.5),
+        _ => None
+    }
+}
+
+// a fen or epd followed somewhere by the result, as `1-0`, `c9 "1/2-1/2";`
+// or `[0.5]`
+pub fn parse_labelled(line: &str) -> Option<LabelledPosition> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+
+    // fen has two move counters after the four epd fields
+    let counters = words.len() >= 6 && words[4..6].iter().all(|word| word.parse::<u32>().is_ok());
+    let length = match counters {
+        true => 6,
+        false => 4
+    };
+
+    let board = ChessBoard::from_fen(ChessPieceColor::White, &words.get(..length)?.join(" ")).ok()?;
+    let result = words[length..].iter().find_map(|word| result_value(word))?;
+
+    Some(LabelledPosition { board, result })
+}
+
+// lines that don't parse are skipped
+pub fn load_positions<P: AsRef<Path>>(path: P) -> Result<Vec<LabelledPosition>, &'static str> {
+    let text = fs::read_to_string(path).map_err(|_| "could not read labelled positions")?;
+    Ok(text.lines().filter_map(parse_labelled).collect())
+}
+
+#[allow(dead_code)]
+impl Tuner {
+    pub fn new(positions: &[LabelledPosition]) -> Self {
+        Tuner {
+            positions: positions.iter()
+                .map(|position| (position.board.eval_terms(), position.result))
+                .collect(),
+            scale: 1.0
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    fn sigmoid(&self, score: f64) -> f64 {
+        1.0 / (1.0 + 10f64.powf(-self.scale * score / 400.0))
+    }
+
+    // mean squared difference between results and expected scores
+    fn loss_of(&self, weights: &[i32]) -> f64 {
+        let total: f64 = self.positions.iter()
+            .map(|(terms, result)| {
+                let score: i32 = terms.iter().map(|(index, sign)| sign * weights[*index]).sum();
+                (result - self.sigmoid(score as f64)).powi(2)
+            })
+            .sum();
+
+        total / self.positions.len().max(1) as f64
+    }
+
+    pub fn loss(&self, params: &EvalParams) -> f64 {
+        self.loss_of(&params.to_vec())
+    }
+
+    // the steepness that fits the current parameters best, found by
+    // narrowing down on the lowest loss. done once before tuning, so the
+    // tuning changes the evaluation instead of its scale
+    pub fn fit_scale(&mut self, params: &EvalParams) -> f64 {
+        let weights = params.to_vec();
+        let (mut low, mut high) = (0.01, 4.0);
+
+        for _ in 0..60 {
+            let third = (high - low) / 3.0;
+
+            self.scale = low + third;
+            let lower = self.loss_of(&weights);
+            self.scale = high - third;
+            let higher = self.loss_of(&weights);
+
+            match lower < higher {
+                true => high -= third,
+                false => low += third
+            }
+        }
+
+        self.scale = (low + high) / 2.0;
+        self.scale
+    }
+
+    // local search: each parameter in turn goes up or down a centipawn if
+    // that lowers the loss. stops after `passes` rounds or a round with no
+    // change
+    pub fn tune(&self, params: &EvalParams, passes: usize) -> EvalParams {
+        let mut weights = params.to_vec();
+        let mut best = self.loss_of(&weights);
+
+        for _ in 0..passes {
+            let mut improved = false;
+
+            for index in 0..weights.len() {
+                for step in [1, -1] {
+                    weights[index] += step;
+                    let loss = self.loss_of(&weights);
+
+                    if loss < best {
+                        best = loss;
+                        improved = true;
+                        break;
+                    }
+
+                    weights[index] -= step;
+                }
+            }
+
+            if !improved {
+                break;
+            }
+        }
+
+        EvalParams::from_slice(&weights).unwrap()
+    }
+}
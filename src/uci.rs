@@ -0,0 +1,446 @@
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::board::ChessBoard;
+use crate::pieces::ChessPieceColor;
+use crate::search::{Searcher, SearchConfig, SearchResult, PvLine, Evaluator, MATE};
+use crate::time::{TimeControl, TimeManager, TimeHandle};
+use crate::ordering::MAX_PLY;
+use crate::book::{OpeningBook, BookSelection};
+use crate::syzygy::Tablebase;
+use crate::nnue::Network;
+use crate::eval::EvalParams;
+use crate::variant::Variant;
+
+// universal chess interface. commands are handled one line at a time and the
+// replies handed back, so the protocol can be driven from stdin or from a
+// test. searches with a limit (depth, movetime or a clock) run to completion
+// before the next command is read. `go ponder` and `go infinite` search on
+// their own thread instead, and their bestmove is held back until `ponderhit`
+// or `stop`. poll hands it over once it's ready
+
+const ENGINE_NAME: &str = "chess-engine";
+const ENGINE_AUTHOR: &str = "Barkerprooks";
+
+// how deep `go` searches when it isn't given any limit
+const DEFAULT_DEPTH: u8 = 6;
+
+const MAX_THREADS: usize = 256;
+const MAX_MULTI_PV: usize = 256;
+
+// how often run checks on a search in the background between commands
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+// a search on its own thread. the searcher goes with it and comes back when
+// it's done, so what it learned is kept
+struct BackgroundSearch {
+    handle: TimeHandle,
+    thread: JoinHandle<(Searcher, SearchResult)>,
+    board: ChessBoard,
+    start: Instant,
+    waiting: bool // pondering or infinite, the bestmove waits for ponderhit or stop
+}
+
+pub struct UciEngine {
+    board: ChessBoard,
+    config: SearchConfig,
+    searcher: Searcher,
+    book: Option<OpeningBook>,
+    own_book: bool, // play from the book before searching
+    book_selection: BookSelection,
+    chess960: bool, // castling moves are sent and read as the king taking its rook
+    variant: Variant,
+    background: Option<BackgroundSearch>
+}
+
+fn score_to_uci(score: i32) -> String {
+    if score.abs() > MATE - MAX_PLY as i32 {
+        // plies to mate, rounded up to whole moves
+        let moves = (MATE - score.abs() + 1) / 2;
+        return match score > 0 {
+            true => format!("mate {}", moves),
+            false => format!("mate -{}", moves)
+        };
+    }
+
+    format!("cp {}", score)
+}
+
+// the `info` line for one principal variation
+pub fn info_line(board: &ChessBoard, result: &SearchResult, index: usize, line: &PvLine, elapsed: Duration) -> String {
+    let millis = elapsed.as_millis().max(1) as u64;
+
+    let pv: Vec<String> = line.moves.iter()
+        .scan(*board, |board, chess_move| {
+            let text = board.move_to_uci(chess_move);
+            board.make_move(chess_move);
+            Some(text)
+        })
+        .collect();
+
+    format!(
+        "info depth {} multipv {} score {} nodes {} nps {} time {} pv {}",
+        result.depth,
+        index + 1,
+        score_to_uci(line.score),
+        result.nodes,
+        result.nodes * 1000 / millis,
+        millis,
+        pv.join(" ")
+    )
+}
+
+// the info lines and the bestmove for a finished search
+fn search_replies(board: &ChessBoard, result: &SearchResult, elapsed: Duration) -> Vec<String> {
+    let mut replies: Vec<String> = result.lines.iter()
+        .enumerate()
+        .map(|(index, line)| info_line(board, result, index, line, elapsed))
+        .collect();
+
+    replies.push(match result.best_move {
+        Some(best_move) => format!("bestmove {}", board.move_to_uci(&best_move)),
+        None => "bestmove 0000".to_string()
+    });
+
+    replies
+}
+
+// everything between `from` and `to` (or the end of the line), for option
+// names and values that may contain spaces
+fn words_between(words: &[&str], from: &str, to: Option<&str>) -> Option<String> {
+    let start = words.iter().position(|word| *word == from)? + 1;
+    let end = words.iter()
+        .position(|word| Some(*word) == to)
+        .filter(|end| *end >= start)
+        .unwrap_or(words.len());
+
+    Some(words[start..end].join(" "))
+}
+
+fn option_value<'a>(words: &[&'a str], name: &str) -> Option<&'a str> {
+    words.iter()
+        .position(|word| *word == name)
+        .and_then(|index| words.get(index + 1))
+        .copied()
+}
+
+#[allow(dead_code)]
+impl UciEngine {
+    pub fn new() -> Self {
+        let config = SearchConfig::default();
+
+        UciEngine {
+            board: ChessBoard::new(ChessPieceColor::White),
+            config,
+            searcher: Searcher::new(config),
+            book: None,
+            own_book: false,
+            book_selection: BookSelection::Weighted,
+            chess960: false,
+            variant: Variant::Standard,
+            background: None
+        }
+    }
+
+    // hands the engine a book and turns OwnBook on, for callers that don't
+    // go through setoption
+    pub fn set_book(&mut self, book: OpeningBook, selection: BookSelection) {
+        self.book = Some(book);
+        self.own_book = true;
+        self.book_selection = selection;
+    }
+
+    pub fn board(&self) -> &ChessBoard {
+        &self.board
+    }
+
+    pub fn config(&self) -> SearchConfig {
+        self.config
+    }
+
+    // reads commands until `quit` or the end of the input. the input is read
+    // on its own thread, so a search in the background can report while
+    // we're waiting for the next command
+    pub fn run<R: BufRead + Send + 'static, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in input.lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        loop {
+            let replies = match lines.recv_timeout(POLL_INTERVAL) {
+                Ok(line) => {
+                    let line = line?;
+                    if line.trim() == "quit" {
+                        break;
+                    }
+                    self.handle(&line)
+                },
+                Err(RecvTimeoutError::Timeout) => self.poll(),
+                Err(RecvTimeoutError::Disconnected) => break
+            };
+
+            for reply in replies {
+                writeln!(output, "{}", reply)?;
+            }
+            output.flush()?;
+        }
+
+        // nobody is left to hear about it
+        if let Some(background) = self.background.take() {
+            background.handle.stop();
+            let _ = background.thread.join();
+        }
+
+        Ok(())
+    }
+
+    // the bestmove of a search in the background, once it's finished and
+    // allowed to say so
+    pub fn poll(&mut self) -> Vec<String> {
+        match &self.background {
+            Some(background) if !background.waiting && background.thread.is_finished() => self.finish(),
+            _ => vec![]
+        }
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.background.is_some()
+    }
+
+    fn finish(&mut self) -> Vec<String> {
+        let Some(background) = self.background.take() else {
+            return vec![];
+        };
+
+        let (searcher, result) = background.thread.join().expect("search thread panicked");
+        self.searcher = searcher;
+
+        search_replies(&background.board, &result, background.start.elapsed())
+    }
+
+    pub fn handle(&mut self, line: &str) -> Vec<String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.first() {
+            Some(&"uci") => vec![
+                format!("id name {}", ENGINE_NAME),
+                format!("id author {}", ENGINE_AUTHOR),
+                format!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS),
+                format!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTI_PV),
+                "option name OwnBook type check default false".to_string(),
+                "option name BookFile type string default <empty>".to_string(),
+                "option name BookSelection type combo default Weighted var Weighted var Best".to_string(),
+                "option name SyzygyPath type string default <empty>".to_string(),
+                "option name UseNNUE type check default false".to_string(),
+                "option name EvalFile type string default <empty>".to_string(),
+                "option name ParamFile type string default <empty>".to_string(),
+                "option name UCI_Chess960 type check default false".to_string(),
+                format!(
+                    "option name UCI_Variant type combo default chess{}",
+                    Variant::ALL.iter().map(|variant| format!(" var {}", variant.uci_name())).collect::<String>()
+                ),
+                "uciok".to_string()
+            ],
+            Some(&"isready") => vec!["readyok".to_string()],
+            Some(&"ucinewgame") => {
+                self.board = ChessBoard::new_variant(ChessPieceColor::White, self.variant);
+                self.board.set_chess960(self.chess960);
+                self.searcher.clear();
+                vec![]
+            },
+            Some(&"setoption") => {
+                self.set_option(&words);
+                vec![]
+            },
+            Some(&"position") => {
+                self.set_position(&words);
+                vec![]
+            },
+            Some(&"go") => self.go(&words),
+            Some(&"ponderhit") => match &mut self.background {
+                Some(background) => {
+                    background.handle.ponder_hit();
+                    background.waiting = false;
+                    self.poll()
+                },
+                None => vec![]
+            },
+            Some(&"stop") => match &self.background {
+                Some(background) => {
+                    background.handle.stop();
+                    self.finish()
+                },
+                None => vec![]
+            },
+            _ => vec![] // unknown commands are ignored, as the protocol asks
+        }
+    }
+
+    fn set_option(&mut self, words: &[&str]) {
+        let name = words_between(words, "name", Some("value")).unwrap_or_default();
+        let value = words_between(words, "value", None).unwrap_or_default();
+        let number = value.parse::<usize>().ok();
+
+        match (name.as_str(), number) {
+            ("Threads", Some(threads)) => self.config.threads = threads.clamp(1, MAX_THREADS),
+            ("MultiPV", Some(lines)) => self.config.multi_pv = lines.clamp(1, MAX_MULTI_PV),
+            ("OwnBook", _) => self.own_book = value == "true",
+            ("BookFile", _) => self.book = OpeningBook::load(&value).ok(),
+            ("BookSelection", _) => self.book_selection = match value.as_str() {
+                "Best" => BookSelection::Best,
+                _ => BookSelection::Weighted
+            },
+            ("SyzygyPath", _) => {
+                let tablebase = Tablebase::open(&value);
+                self.searcher.set_tablebase(Some(Arc::new(tablebase)).filter(|tablebase| !tablebase.is_empty()));
+            },
+            ("UseNNUE", _) => self.config.evaluator = match value.as_str() {
+                "true" => Evaluator::Nnue,
+                _ => Evaluator::Classical
+            },
+            ("EvalFile", _) => self.searcher.set_network(Network::load(&value).ok().map(Arc::new)),
+            ("ParamFile", _) => self.searcher.set_eval_params(EvalParams::load(&value).unwrap_or_default()),
+            ("UCI_Chess960", _) => {
+                self.chess960 = value == "true";
+                self.board.set_chess960(self.chess960);
+            },
+            ("UCI_Variant", _) => {
+                self.variant = Variant::parse(&value).unwrap_or(Variant::Standard);
+                self.board.set_variant(self.variant);
+            },
+            _ => ()
+        }
+
+        // keep what the table has learned, only the settings change
+        self.searcher.set_config(self.config);
+    }
+
+    // `position startpos` or `position fen <fen>`, then the moves played
+    // from there. a position that can't be read leaves the board as it was
+    fn set_position(&mut self, words: &[&str]) {
+        let board = match words.get(1) {
+            Some(&"startpos") => Ok(ChessBoard::new_variant(ChessPieceColor::White, self.variant)),
+            Some(&"fen") => {
+                let fen = words_between(words, "fen", Some("moves")).unwrap_or_default();
+                ChessBoard::from_fen(ChessPieceColor::White, &fen)
+            },
+            _ => return
+        };
+
+        let Ok(mut board) = board else {
+            return;
+        };
+
+        board.set_chess960(self.chess960 || board.is_chess960());
+        if self.variant != Variant::Standard {
+            board.set_variant(self.variant);
+        }
+        self.board = board;
+
+        let moves = words.iter()
+            .skip_while(|word| **word != "moves")
+            .skip(1);
+
+        for text in moves {
+            match self.board.parse_uci_move(text) {
+                Some(chess_move) => self.board.make_move(&chess_move),
+                None => break
+            }
+        }
+    }
+
+    fn go(&mut self, words: &[&str]) -> Vec<String> {
+        // a gui shouldn't start a search over a running one, but if it does
+        // the old one is dropped
+        if let Some(background) = self.background.take() {
+            background.handle.stop();
+            let _ = background.thread.join();
+        }
+
+        let ponder = words.contains(&"ponder");
+        let infinite = words.contains(&"infinite");
+
+        // the book's lines are for standard chess on the usual board. a book
+        // move would come back at once, which pondering can't do
+        let orthodox = self.board.variant() == Variant::Standard && self.board.is_orthodox();
+        let book_move = match (&mut self.book, self.own_book && orthodox && !ponder && !infinite) {
+            (Some(book), true) => book.pick(&self.board, self.book_selection),
+            _ => None
+        };
+
+        if let Some(book_move) = book_move {
+            return vec![format!("bestmove {}", self.board.move_to_uci(&book_move))];
+        }
+
+        let number = |name: &str| option_value(words, name).and_then(|value| value.parse::<u64>().ok());
+
+        let (remaining, increment) = match self.board.side_to_move() {
+            ChessPieceColor::White => (number("wtime"), number("winc")),
+            ChessPieceColor::Black => (number("btime"), number("binc"))
+        };
+
+        let clock = match (number("movetime"), remaining) {
+            (Some(movetime), _) => Some(TimeManager::fixed(Duration::from_millis(movetime))),
+            (None, Some(remaining)) => Some(TimeManager::new(&TimeControl {
+                remaining: Duration::from_millis(remaining),
+                increment: Duration::from_millis(increment.unwrap_or(0)),
+                moves_to_go: number("movestogo").map(|moves| moves as u32)
+            })),
+            (None, None) => None
+        };
+
+        let start = Instant::now();
+
+        // deeper than the search keeps killers for is as good as forever
+        let depth = number("depth").map(|depth| depth.min(MAX_PLY as u64 / 2) as u8);
+
+        // pondering is on the opponent's time, the clock only starts at the
+        // ponder hit. without a clock the search goes on until it's stopped
+        let clock = match clock {
+            _ if infinite => Some(TimeManager::infinite()),
+            Some(clock) if ponder => Some(clock.pondering()),
+            None if ponder => Some(TimeManager::infinite()),
+            clock => clock
+        };
+
+        let result = match (ponder || infinite, clock) {
+            (true, Some(clock)) => {
+                let handle = clock.handle();
+                let mut searcher = self.searcher.clone();
+                let board = self.board;
+                let depth = depth.unwrap_or(MAX_PLY as u8 / 2);
+
+                self.background = Some(BackgroundSearch {
+                    handle,
+                    thread: thread::spawn(move || {
+                        let result = searcher.search_timed(&board, depth, clock);
+                        (searcher, result)
+                    }),
+                    board,
+                    start,
+                    waiting: true
+                });
+
+                return vec![];
+            },
+            (_, Some(clock)) => self.searcher.search_timed(&self.board, depth.unwrap_or(MAX_PLY as u8 / 2), clock),
+            (_, None) => self.searcher.search(&self.board, depth.unwrap_or(DEFAULT_DEPTH))
+        };
+
+        search_replies(&self.board, &result, start.elapsed())
+    }
+}
+
+impl Default for UciEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,209 @@
+use crate::board::ChessBoard;
+use crate::pieces::{ChessPiece, ChessPieceColor};
+use crate::pgn::GameResult;
+use crate::notation::START_FEN;
+use crate::math::{V2, KING_OFFSETS};
+
+// rule variants. the board carries one and the move generator, the legality
+// filter, the game end checks and the evaluation ask it what's different.
+// every variant ending here means the side to move has lost
+//
+//   king of the hill  a king reaching one of the four centre squares wins
+//   three-check       checking the other king for the third time wins
+//   atomic            captures explode, taking out the capturer and every
+//                     piece but pawns around the tile. blowing up the enemy
+//                     king wins, blowing up your own isn't allowed
+//   horde             white has a horde of pawns and no king, and loses once
+//                     they're all gone. pawns on the first rank may double step
+//   crazyhouse        captured pieces can be dropped back in, see crazyhouse
+
+pub const HORDE_FEN: &str = "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1";
+pub const THREE_CHECK_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1";
+pub const CRAZYHOUSE_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1";
+
+// capablanca chess is the usual rules on a 10x8 board with an archbishop
+// and a chancellor each, the board size and pieces come from the fen
+pub const CAPABLANCA_FEN: &str = "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1";
+
+const HILL: [&str; 4] = ["d4", "e4", "d5", "e5"];
+
+// bonus for each check given in three-check, the third one is the game
+const CHECK_BONUS: [i32; 3] = [0, 150, 400];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Variant { Standard, KingOfTheHill, ThreeCheck, Atomic, Horde, Crazyhouse }
+
+#[allow(dead_code)]
+impl Variant {
+    pub const ALL: [Variant; 6] = [
+        Variant::Standard, Variant::KingOfTheHill, Variant::ThreeCheck, Variant::Atomic, Variant::Horde,
+        Variant::Crazyhouse
+    ];
+
+    // the name in a pgn variant tag
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "Standard",
+            Variant::KingOfTheHill => "King of the Hill",
+            Variant::ThreeCheck => "Three-check",
+            Variant::Atomic => "Atomic",
+            Variant::Horde => "Horde",
+            Variant::Crazyhouse => "Crazyhouse"
+        }
+    }
+
+    // the name for the uci UCI_Variant option
+    pub fn uci_name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "chess",
+            Variant::KingOfTheHill => "kingofthehill",
+            Variant::ThreeCheck => "3check",
+            Variant::Atomic => "atomic",
+            Variant::Horde => "horde",
+            Variant::Crazyhouse => "crazyhouse"
+        }
+    }
+
+    // either kind of name, ignoring case, spaces and dashes
+    pub fn parse(name: &str) -> Option<Self> {
+        let name: String = name.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+
+        match name.as_str() {
+            "standard" | "chess" => Some(Variant::Standard),
+            "kingofthehill" | "koth" => Some(Variant::KingOfTheHill),
+            "threecheck" | "3check" => Some(Variant::ThreeCheck),
+            "atomic" => Some(Variant::Atomic),
+            "horde" => Some(Variant::Horde),
+            "crazyhouse" | "zh" => Some(Variant::Crazyhouse),
+            _ => None
+        }
+    }
+
+    pub fn start_fen(&self) -> &'static str {
+        match self {
+            Variant::Horde => HORDE_FEN,
+            Variant::ThreeCheck => THREE_CHECK_FEN,
+            Variant::Crazyhouse => CRAZYHOUSE_FEN,
+            _ => START_FEN
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl ChessBoard {
+    // the variant's start position
+    pub fn new_variant(player_color: ChessPieceColor, variant: Variant) -> Self {
+        let mut board = ChessBoard::from_fen(player_color, variant.start_fen())
+            .expect("variant start positions are valid fen");
+        board.set_variant(variant);
+        board
+    }
+
+    // checks the color has given so far, only counted in three-check
+    pub fn checks_given(&self, color: ChessPieceColor) -> u8 {
+        self.checks()[color.index()]
+    }
+
+    pub fn set_checks_given(&mut self, color: ChessPieceColor, checks: u8) {
+        let mut given = self.checks();
+        given[color.index()] = checks;
+        self.set_checks(given);
+    }
+
+    fn on_hill(&self, v2: &V2) -> bool {
+        HILL.contains(&self.square_name(v2).as_str())
+    }
+
+    // the winner when the game has ended by the variant's own rules.
+    // checkmate and stalemate are left to the move generator
+    pub fn variant_outcome(&self) -> Option<(GameResult, &'static str)> {
+        let mover = self.side_to_move();
+        let lost = Some((GameResult::win_for(mover.opposite()), match self.variant() {
+            Variant::KingOfTheHill => "king of the hill",
+            Variant::ThreeCheck => "three checks",
+            Variant::Atomic => "king exploded",
+            _ => "horde destroyed"
+        }));
+
+        let over = match self.variant() {
+            Variant::Standard | Variant::Crazyhouse => false,
+            Variant::KingOfTheHill => self.king_square(mover.opposite()).is_some_and(|king| self.on_hill(&king)),
+            Variant::ThreeCheck => self.checks_given(mover.opposite()) >= 3,
+            Variant::Atomic => self.king_square(mover).is_none(),
+            Variant::Horde => self.squares().all(|v2| self.tile(&v2).color() != Some(ChessPieceColor::White))
+        };
+
+        match over {
+            true => lost,
+            false => None
+        }
+    }
+
+    // the capture on dst blows up the capturer and every piece but pawns
+    // next to it. the captured piece is already gone by now
+    pub(crate) fn explode(&mut self, dst: &V2) {
+        self.clear(dst);
+
+        for (x, y) in KING_OFFSETS {
+            if let Some(v2) = self.offset(dst, x, y) {
+                if self.tile(&v2).piece().is_some_and(|piece| piece != ChessPiece::Pawn) {
+                    self.clear(&v2);
+                }
+            }
+        }
+    }
+
+    // in atomic kings can't capture, so one next to the other king is safe
+    // from everything, and only the other pieces give check
+    pub(crate) fn atomic_check(&self, king: &V2, color: ChessPieceColor) -> bool {
+        let touching = KING_OFFSETS.iter()
+            .filter_map(|(x, y)| self.offset(king, *x, *y))
+            .any(|v2| self.tile(&v2).piece() == Some(ChessPiece::King));
+
+        !touching && self.attackers_of(king, color.opposite()).iter()
+            .any(|attacker| self.tile(attacker).piece() != Some(ChessPiece::King))
+    }
+
+    // whether the side that just moved was allowed to. in atomic the own
+    // king has to survive, and exploding the other one wins even from check
+    pub(crate) fn king_safe_after_move(&self, side: ChessPieceColor) -> bool {
+        match self.variant() {
+            Variant::Atomic => self.king_square(side).is_some()
+                && (self.king_square(side.opposite()).is_none() || !self.in_check(side)),
+            _ => !self.in_check(side)
+        }
+    }
+
+    // centipawns for the side to move on top of the usual evaluation
+    pub fn variant_eval(&self) -> i32 {
+        let side = self.side_to_move();
+        let sign = |color: ChessPieceColor| match color == side {
+            true => 1,
+            false => -1
+        };
+
+        match self.variant() {
+            // kings closer to the centre are closer to winning
+            Variant::KingOfTheHill => [ChessPieceColor::White, ChessPieceColor::Black].into_iter()
+                .filter_map(|color| self.king_square(color).map(|king| (color, king)))
+                .map(|(color, king)| {
+                    let distance = HILL.iter()
+                        .filter_map(|name| self.parse_square(name))
+                        .map(|hill| (hill.x as i32 - king.x as i32).abs().max((hill.y as i32 - king.y as i32).abs()))
+                        .min()
+                        .unwrap_or(0);
+                    sign(color) * 30 * (3 - distance)
+                })
+                .sum(),
+            Variant::ThreeCheck => [ChessPieceColor::White, ChessPieceColor::Black].into_iter()
+                .map(|color| sign(color) * CHECK_BONUS[(self.checks_given(color) as usize).min(2)])
+                .sum(),
+            // pieces in hand are worth as much as on the board
+            Variant::Crazyhouse => self.pocket_value(side) - self.pocket_value(side.opposite()),
+            _ => 0
+        }
+    }
+}